@@ -0,0 +1,103 @@
+use flywheelmc_players::world::ChunkSection;
+use protocol::value::BlockState;
+use protocol::registry::RegEntry;
+use criterion::{ criterion_group, criterion_main, Criterion, BatchSize };
+use std::hint::black_box;
+
+mod rle;
+use rle::RleSection;
+
+
+/// The biome bits-per-entry of a direct container in the vanilla biome registry.
+const BIOME_DIRECT_BITS : u8 = 7;
+
+
+fn block(id : u32) -> RegEntry<BlockState> {
+    unsafe { RegEntry::new_unchecked(id) }
+}
+
+/// Returns a section holding `distinct` different blocks, which is stored as
+///  single-valued, indirect, or direct depending on how many there are.
+fn section_of(distinct : u32) -> ChunkSection {
+    let mut section = ChunkSection::empty();
+    if (distinct > 1) {
+        for linear_xyz in 0..4096 {
+            section.bench_set(linear_xyz, block(((linear_xyz as u32) % distinct) + 1));
+        }
+    }
+    section.clear_dirty();
+    section
+}
+
+/// Returns a run-length encoded section holding the same blocks as [`section_of`].
+fn rle_section_of(distinct : u32) -> RleSection {
+    let mut section = RleSection::empty();
+    if (distinct > 1) {
+        for linear_xyz in 0..4096 {
+            section.set(linear_xyz, block(((linear_xyz as u32) % distinct) + 1));
+        }
+        section.collapse();
+    }
+    section.clear_dirty();
+    section
+}
+
+const KINDS : [(&str, u32,); 3] = [
+    ("single",   1,),
+    ("indirect", 16,),
+    ("direct",   1024,)
+];
+
+
+fn bench_get(c : &mut Criterion) {
+    let mut group = c.benchmark_group("get");
+    for (name, distinct,) in KINDS {
+        let section = section_of(distinct);
+        group.bench_function(format!("paletted/{name}"), |b| b.iter(|| {
+            for linear_xyz in 0..4096 {
+                black_box(section.get(black_box(linear_xyz)));
+            }
+        }));
+        let section = rle_section_of(distinct);
+        group.bench_function(format!("rle/{name}"), |b| b.iter(|| {
+            for linear_xyz in 0..4096 {
+                black_box(section.get(black_box(linear_xyz)));
+            }
+        }));
+    }
+    group.finish();
+}
+
+fn bench_set(c : &mut Criterion) {
+    let mut group = c.benchmark_group("set");
+    for (name, distinct,) in KINDS {
+        let section = section_of(distinct);
+        group.bench_function(format!("paletted/{name}"), |b| b.iter_batched_ref(|| section.clone(), |section| {
+            for linear_xyz in 0..4096 {
+                section.bench_set(linear_xyz, block(((linear_xyz as u32) % distinct) + 2));
+            }
+        }, BatchSize::SmallInput));
+        let section = rle_section_of(distinct);
+        group.bench_function(format!("rle/{name}"), |b| b.iter_batched_ref(|| section.clone(), |section| {
+            for linear_xyz in 0..4096 {
+                section.set(linear_xyz, block(((linear_xyz as u32) % distinct) + 2));
+            }
+        }, BatchSize::SmallInput));
+    }
+    group.finish();
+}
+
+fn bench_encode(c : &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, distinct,) in KINDS {
+        let section = section_of(distinct);
+        group.bench_function(format!("paletted/{name}"), |b| b.iter(|| black_box(section.bench_ptc_chunk_section(BIOME_DIRECT_BITS))));
+        let section = rle_section_of(distinct);
+        group.bench_function(format!("rle/{name}"), |b| b.iter(|| black_box(section.ptc_chunk_section())));
+    }
+    group.finish();
+}
+
+
+criterion_group!(benches, bench_get, bench_set, bench_encode);
+criterion_main!(benches);
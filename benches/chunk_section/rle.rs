@@ -0,0 +1,111 @@
+//! The run-length encoded section which [`ChunkSection`](flywheelmc_players::world::ChunkSection)
+//!  replaced, kept as a baseline for the benchmarks.
+
+use flywheelmc_common::prelude::*;
+use protocol::value::BlockState;
+use protocol::value::{
+    ChunkSection as PtcChunkSection,
+    PalettedContainer,
+    PaletteFormat
+};
+use protocol::registry::RegEntry;
+
+
+#[derive(Clone)]
+pub struct RleSection {
+    runs  : Vec<(u16, RegEntry<BlockState>,)>,
+    dirty : BTreeSet<u16>
+}
+
+impl RleSection {
+
+    pub fn empty() -> Self {
+        Self {
+            runs  : vec![(4096, unsafe { RegEntry::new_unchecked(0) },)],
+            dirty : BTreeSet::new()
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = RegEntry<BlockState>> {
+        self.runs.iter().flat_map(|run| std::iter::repeat_n(run.1, run.0 as usize))
+    }
+
+    #[inline]
+    pub fn get(&self, linear_xyz : u16) -> RegEntry<BlockState> {
+        self.iter().nth(linear_xyz as usize).expect("called RleSection::get with out-of-range block index")
+    }
+
+    /// Sets the block state at the given index, splitting the run holding it.
+    pub fn set(&mut self, linear_xyz : u16, block : RegEntry<BlockState>) -> RegEntry<BlockState> {
+        let mut start = 0;
+        let mut i     = 0;
+        while (linear_xyz >= start + self.runs[i].0) {
+            start += self.runs[i].0;
+            i     += 1;
+        }
+        let (len, old,) = self.runs[i];
+        if (old == block) { return old; }
+        let before = linear_xyz - start;
+        let after  = len - before - 1;
+        let mut split = Vec::with_capacity(3);
+        if (before > 0) { split.push((before, old,)); }
+        split.push((1, block,));
+        if (after > 0) { split.push((after, old,)); }
+        self.runs.splice(i..=i, split);
+        self.dirty.insert(linear_xyz);
+        old
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty.clear();
+    }
+
+    /// Makes this section as small as possible.
+    pub fn collapse(&mut self) {
+        for i in (0..(self.runs.len() - 1)).rev() {
+            // Merge runs of the same block state.
+            if (self.runs[i].1 == self.runs[i + 1].1) {
+                self.runs[i].0 += self.runs[i + 1].0;
+                self.runs.remove(i + 1);
+            }
+            // Remove empty runs.
+            if (self.runs[i].0 == 0) {
+                self.runs.remove(i);
+            }
+        }
+        self.runs.shrink_to_fit();
+    }
+
+    pub fn ptc_chunk_section(&self) -> PtcChunkSection {
+        let mut block_count = 0;
+        let block_states;
+        if (self.runs.len() == 1) {
+            block_count  = if (self.runs[0].1.id() != 0) { 4096 } else { 0 };
+            block_states = PalettedContainer {
+                bits_per_entry : 0,
+                format         : PaletteFormat::SingleValued { entry : self.runs[0].1 }
+            };
+        } else {
+            block_states = PalettedContainer {
+                bits_per_entry : 15,
+                format         : PaletteFormat::Direct { data : {
+                    let mut data = [unsafe{ RegEntry::new_unchecked(0) }; 4096];
+                    for (i, block) in self.iter().enumerate() {
+                        if (block.id() != 0) { block_count += 1; }
+                        data[i] = block;
+                    }
+                    data
+                } }
+            }
+        };
+        PtcChunkSection {
+            block_count,
+            block_states,
+            biomes       : PalettedContainer {
+                bits_per_entry : 0,
+                format         : PaletteFormat::SingleValued { entry : unsafe { RegEntry::new_unchecked(0) } }
+            },
+        }
+    }
+
+}
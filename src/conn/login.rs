@@ -7,10 +7,19 @@ use crate::{
     ServerBrand,
     DefaultDim,
     MaxViewDistance,
+    DefaultGamemode,
     Registries,
     RegistryPackets
 };
 use crate::player::{ Player, PlayerJoined };
+use crate::player::gamemode::PlayerGamemode;
+use crate::player::abilities::{ PlayerAbilities, Abilities };
+use crate::player::inventory::PlayerInventory;
+use crate::player::menu::PlayerMenu;
+use crate::player::chat::PlayerChatState;
+use crate::player::scoreboard::PlayerSidebar;
+use crate::player::movement::PlayerPosition;
+use crate::player::permission::{ Permissions, PlayerPermissions, BYPASS_FULL };
 use crate::conn::{ Connection, ConnKeepalive, RealStage, KEEPALIVE_INTERVAL, ACTIVE_CONNS };
 use crate::conn::packet::{ PacketReadEvent, NextStage };
 use crate::conn::play::ConnStatePlay;
@@ -64,6 +73,14 @@ use protocol::mojang::auth_verify::{
 };
 
 
+/// Where players are placed when they join, before any plugin moves them.
+const SPAWN_POS : (f64, f64, f64,) = (0.5, 360.0, 0.5,);
+
+/// The number of connections over the limit which are still authenticated,
+///  in case they can bypass it. Past this, even those players are turned away.
+const BYPASS_FULL_HEADROOM : usize = 16;
+
+
 #[derive(Component)]
 pub(crate) enum ConnStateLogin {
     WaitingForHello,
@@ -104,8 +121,10 @@ pub(crate) fn handle_state(
         r_server_brand : Res<ServerBrand>,
         r_default_dim  : Res<DefaultDim>,
         r_view_dist    : Res<MaxViewDistance>,
+        r_gamemode     : Res<DefaultGamemode>,
         r_regs         : Res<Registries>,
         r_reg_packets  : Res<RegistryPackets>,
+        r_perms        : Res<Permissions>,
     mut ew_joined      : EventWriter<PlayerJoined>,
     mut ew_packet      : EventWriter<PacketReadEvent>
 ) {
@@ -121,8 +140,9 @@ pub(crate) fn handle_state(
                     if let Some(reject) = &r_reject {
                         conn.kick(&reject.0);
                     }
+                    // Players who can bypass the limit are only known after authenticating.
                     if let Some(max_conns) = &r_max_conns
-                        && (ACTIVE_CONNS.load(AtomicOrdering::Relaxed) > max_conns.0)
+                        && (ACTIVE_CONNS.load(AtomicOrdering::Relaxed) > max_conns.0.saturating_add(BYPASS_FULL_HEADROOM))
                     {
                         conn.kick("Server is full");
                         continue;
@@ -219,6 +239,13 @@ pub(crate) fn handle_state(
             ConnStateLogin::HandleMojauth { mojauth } => {
                 // TODO: Check infractions
                 // TODO: Check already logged in network (max 5?)
+                if let Some(max_conns) = &r_max_conns
+                    && (ACTIVE_CONNS.load(AtomicOrdering::Relaxed) > max_conns.0)
+                    && (! r_perms.player_has(mojauth.uuid, BYPASS_FULL))
+                {
+                    conn.kick("Server is full");
+                    continue;
+                }
                 if (unsafe { conn.send_packet_noset(LoginFinishedS2CLoginPacket {
                     uuid     : mojauth.uuid,
                     username : mojauth.name.clone(),
@@ -270,12 +297,15 @@ pub(crate) fn handle_state(
                         },
                         world::ChunkCentre(Dirty::new_dirty(Vec2::<i32>::ZERO)),
                         world::ViewDistance(Ordered::new(NonZeroU8::MIN)),
-                        world::World {
-                            dim_id       : r_default_dim.0.clone(),
-                            dim_type     : r_default_dim.1.clone(),
-                            chunks       : BTreeMap::new(),
-                            ready_chunks : VecDeque::new()
-                        }
+                        world::ChunkView::default(),
+                        PlayerGamemode(Dirty::new(r_gamemode.0)),
+                        PlayerAbilities(Dirty::new_dirty(Abilities::for_gamemode(r_gamemode.0))),
+                        PlayerInventory::default(),
+                        PlayerMenu::default(),
+                        PlayerChatState::default(),
+                        PlayerPermissions::new(r_perms.player_or_default(*uuid)),
+                        PlayerSidebar::default(),
+                        PlayerPosition::new(SPAWN_POS.0, SPAWN_POS.1, SPAWN_POS.2)
                     ));
 
                     if (unsafe { conn.send_packet_noset(FinishConfigurationS2CConfigPacket) }.is_err()) {
@@ -326,7 +356,7 @@ pub(crate) fn handle_state(
                             dim                  : RegEntry::new_unchecked(0),
                             dim_name             : r_default_dim.0.clone(),
                             seed                 : 0,
-                            gamemode             : Gamemode::Creative,
+                            gamemode             : r_gamemode.0,
                             old_gamemode         : Gamemode::None,
                             is_debug             : false,
                             is_flat              : true,
@@ -345,7 +375,8 @@ pub(crate) fn handle_state(
                                         .map(|prop| prop.into())
                                         .collect::<Vec<_>>()
                                         .into()
-                                }
+                                },
+                                PlayerActionEntry::UpdateGamemode(r_gamemode.0)
                             ],)],
                         }) }.is_err()) { continue; }
 
@@ -369,7 +400,7 @@ pub(crate) fn handle_state(
                             dim                  : RegEntry::new_unchecked(0),
                             dim_name             : r_default_dim.0.clone(),
                             seed                 : 0,
-                            gamemode             : Gamemode::Creative,
+                            gamemode             : r_gamemode.0,
                             prev_gamemode        : Gamemode::None,
                             is_debug             : false,
                             is_flat              : true,
@@ -389,9 +420,9 @@ pub(crate) fn handle_state(
 
                         if (unsafe { conn.send_packet_noset(PlayerPositionS2CPlayPacket {
                             teleport_id : 1.into(),
-                            x           : 0.5,
-                            y           : 360.0,
-                            z           : 0.5,
+                            x           : SPAWN_POS.0,
+                            y           : SPAWN_POS.1,
+                            z           : SPAWN_POS.2,
                             vx          : 0.0,
                             vy          : 0.0,
                             vz          : 0.0,
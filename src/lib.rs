@@ -11,10 +11,12 @@
 
 use flywheelmc_common::prelude::*;
 use protocol::packet::s2c::config::RegistryDataS2CConfigPacket;
+use protocol::packet::s2c::play::Gamemode;
 use protocol::value::{ Identifier, Text, TextComponent };
-use protocol::value::{ DimType, EntityType };
+use protocol::value::{ DimType, EntityType, Biome, BlockEntityType, Item, MenuType };
 use protocol::registry::Registry;
 pub use protocol::{ MINECRAFT_VERSION, PROTOCOL_VERSION };
+use std::path::PathBuf;
 
 
 mod conn;
@@ -29,19 +31,27 @@ static KICK_FOOTER : SRwLock<Text> = SRwLock::new(Text::new());
 
 
 pub struct FlywheelMcPlayersPlugin {
-    pub max_conns          : Option<usize>,
-    pub listen_addrs       : SocketAddrs,
-    pub motd               : Text,
-    pub version            : Cow<'static, str>,
-    pub favicon            : Cow<'static, str>,
-    pub compress_threshold : usize,
-    pub mojauth_enabled    : bool,
-    pub server_id          : Cow<'static, str>,
-    pub server_brand       : Cow<'static, str>,
-    pub kick_footer        : Text,
-    pub default_dim_id     : Identifier,
-    pub default_dim_type   : DimType,
-    pub max_view_distance  : NonZeroU8
+    pub max_conns           : Option<usize>,
+    pub listen_addrs        : SocketAddrs,
+    pub motd                : Text,
+    pub version             : Cow<'static, str>,
+    pub favicon             : Cow<'static, str>,
+    pub compress_threshold  : usize,
+    pub mojauth_enabled     : bool,
+    pub server_id           : Cow<'static, str>,
+    pub server_brand        : Cow<'static, str>,
+    pub kick_footer         : Text,
+    pub default_dim_id      : Identifier,
+    pub default_dim_type    : DimType,
+    pub max_view_distance   : NonZeroU8,
+    pub default_gamemode    : Gamemode,
+    pub creative_inventory  : player::inventory::CreativeInventoryPolicy,
+    pub chat_rate_limit     : player::chat::ChatRateLimit,
+    /// The file to load [`Permissions`](player::permission::Permissions) from on startup.
+    pub permissions_file    : Option<PathBuf>,
+    /// The number of bytes of block updates which may be sent per tick, shared
+    ///  by every world and overlay. Remaining changes are sent on later ticks.
+    pub chunk_update_budget : usize
 }
 
 impl Plugin for FlywheelMcPlayersPlugin {
@@ -54,6 +64,18 @@ impl Plugin for FlywheelMcPlayersPlugin {
             .add_event::<player::comms::PlayerCommsActionEvent>()
             .add_event::<world::WorldChunkLoading>()
             .add_event::<world::WorldChunkActionEvent>()
+            .add_event::<world::BlockChanged>()
+            .add_event::<world::SubscribedBlockChanged>()
+            .add_event::<player::interact::BlockBreakAttempt>()
+            .add_event::<player::interact::BlockPlaceAttempt>()
+            .add_event::<player::abilities::PlayerToggleFlight>()
+            .add_event::<player::inventory::HeldSlotChanged>()
+            .add_event::<player::menu::PlayerMenuActionEvent>()
+            .add_event::<player::menu::MenuClicked>()
+            .add_event::<player::menu::MenuClosed>()
+            .add_event::<player::chat::PlayerChat>()
+            .add_event::<player::command::PlayerCommand>()
+            .add_event::<player::command::RefreshCommands>()
             .insert_resource(RejectNewConns(Cow::Borrowed("Server still starting...")))
             .insert_resource(ListenAddrs(self.listen_addrs.clone()))
             .insert_resource(ServerMotd(self.motd.clone()))
@@ -65,9 +87,18 @@ impl Plugin for FlywheelMcPlayersPlugin {
             .insert_resource(ServerBrand(self.server_brand.clone()))
             .insert_resource(DefaultDim(self.default_dim_id.clone(), self.default_dim_type.clone()))
             .insert_resource(MaxViewDistance(self.max_view_distance))
+            .insert_resource(ChunkUpdateBudget(self.chunk_update_budget))
+            .insert_resource(DefaultGamemode(self.default_gamemode))
+            .insert_resource(self.creative_inventory)
+            .insert_resource(self.chat_rate_limit)
+            .insert_resource(player::command::CommandTree::default())
+            .insert_resource(player::permission::Permissions::default())
+            .insert_resource(player::permission::PermissionsFile(self.permissions_file.clone()))
             .insert_resource(Registries::default())
             .insert_resource(RegistryPackets::new(&self.default_dim_id, &self.default_dim_type))
             .add_systems(Startup, start_listener)
+            .add_systems(Startup, world::spawn_default_world)
+            .add_systems(Startup, player::permission::load_permissions)
             .add_systems(Update, conn::read_conn_streams)
             .add_systems(Update, conn::timeout_conns)
             .add_systems(Update, conn::close_conns)
@@ -76,10 +107,38 @@ impl Plugin for FlywheelMcPlayersPlugin {
             .add_systems(Update, conn::login::handle_state)
             .add_systems(Update, conn::play::handle_state)
             .add_systems(Update, player::comms::handle_actions)
+            .add_systems(Update, player::gamemode::update_gamemodes)
+            .add_systems(Update, player::abilities::update_abilities)
+            .add_systems(Update, player::inventory::read_inventory_packets)
+            .add_systems(Update, player::inventory::update_inventories)
+            .add_systems(Update, player::menu::read_menu_packets)
+            .add_systems(Update, player::menu::handle_actions)
+            .add_systems(PreUpdate, player::abilities::read_flight_toggles)
+            .add_systems(PostUpdate, player::abilities::apply_flight_toggles)
+            .add_systems(PreUpdate, player::movement::read_movement.before(player::interact::read_block_interactions))
+            .add_systems(PreUpdate, player::interact::read_block_interactions)
+            .add_systems(PostUpdate, player::interact::apply_block_interactions)
+            .add_systems(Update, player::chat::read_chat_settings)
+            .add_systems(PreUpdate, player::chat::read_chat_messages)
+            .add_systems(PostUpdate, player::chat::broadcast_chat_messages)
+            .add_systems(PreUpdate, player::command::read_commands)
+            .add_systems(PostUpdate, player::command::send_command_trees)
+            .add_systems(Update, player::permission::update_permissions)
+            .add_systems(Update, player::scoreboard::update_sidebars)
+            .add_systems(PostUpdate, player::scoreboard::update_teams)
+            .add_systems(PostUpdate, player::bossbar::update_boss_bars)
             .add_systems(Update, world::read_settings_updates)
+            .add_systems(Update, world::read_chunk_batch_acks)
             .add_systems(Update, world::update_chunk_view)
             .add_systems(Update, world::load_chunks)
-            .add_systems(Update, world::handle_actions);
+            .add_systems(Update, world::finish_generated_chunks)
+            .add_systems(Update, world::update_chunks)
+            .add_systems(Update, world::release_departed_viewers)
+            .add_systems(Update, world::handle_actions)
+            .add_systems(Update, world::emit_block_changes)
+            .add_systems(Update, world::update_storage)
+            .add_systems(Last, world::flush_storage_on_exit)
+            .add_observer(world::flush_removed_world);
         if let Some(max_conns) = self.max_conns {
             app.insert_resource(MaxConnCount(max_conns));
         }
@@ -124,15 +183,43 @@ struct DefaultDim(Identifier, DimType);
 #[derive(Resource)]
 struct MaxViewDistance(NonZeroU8);
 
+#[derive(Resource)]
+struct ChunkUpdateBudget(usize);
+
+#[derive(Resource)]
+struct DefaultGamemode(Gamemode);
+
+/// The world entity spawned on startup using the default dimension.
+#[derive(Resource)]
+pub struct DefaultWorld(Entity);
+impl DefaultWorld {
+    #[inline]
+    pub fn entity(&self) -> Entity { self.0 }
+}
+
 #[derive(Resource)]
 struct Registries {
-    entity_type : Registry<EntityType>
+    entity_type       : Registry<EntityType>,
+    biome             : Registry<Biome>,
+    block_entity_type : Registry<BlockEntityType>,
+    item              : Registry<Item>,
+    menu_type         : Registry<MenuType>
 }
 impl Default for Registries {
     fn default() -> Self { Self {
-        entity_type : EntityType::vanilla_registry()
+        entity_type       : EntityType::vanilla_registry(),
+        biome             : Biome::vanilla_registry(),
+        block_entity_type : BlockEntityType::vanilla_registry(),
+        item              : Item::vanilla_registry(),
+        menu_type         : MenuType::vanilla_registry()
     } }
 }
+impl Registries {
+    /// The bits-per-entry of a direct biome container, based on the size of the biome registry.
+    fn biome_direct_bits(&self) -> u8 {
+        world::bits_for_len(self.biome.len())
+    }
+}
 
 #[derive(Resource)]
 struct RegistryPackets(Vec<RegistryDataS2CConfigPacket>);
@@ -141,7 +228,6 @@ impl RegistryPackets {
         use protocol::registry::Registry;
         use protocol::value::{
             DamageType,
-            Biome,
             WolfVariant,
             PaintingVariant
         };
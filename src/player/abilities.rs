@@ -0,0 +1,145 @@
+use crate::conn::Connection;
+use crate::conn::packet::{ PacketReadEvent, Packet };
+use crate::conn::play::ConnStatePlay;
+use flywheelmc_common::prelude::*;
+use protocol::packet::c2s::play::{
+    C2SPlayPackets,
+    PlayerAbilitiesC2SPlayPacket
+};
+use protocol::packet::s2c::play::{
+    PlayerAbilitiesS2CPlayPacket,
+    Gamemode
+};
+
+
+const FLAG_INVULNERABLE  : u8 = 0x01;
+const FLAG_FLYING        : u8 = 0x02;
+const FLAG_MAY_FLY       : u8 = 0x04;
+const FLAG_INSTANT_BUILD : u8 = 0x08;
+
+
+/// What a player is allowed to do, and how fast they fly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Abilities {
+    pub may_fly       : bool,
+    pub flying        : bool,
+    pub invulnerable  : bool,
+    pub instant_build : bool,
+    pub fly_speed     : f32,
+    /// Scales the player's field of view, as walking speed does in vanilla.
+    pub fov_modifier  : f32
+}
+
+impl Abilities {
+
+    /// The abilities vanilla gives to players in `gamemode`.
+    pub fn for_gamemode(gamemode : Gamemode) -> Self {
+        let (may_fly, flying, invulnerable, instant_build,) = match (gamemode) {
+            Gamemode::Creative  => (true, false, true, true,),
+            Gamemode::Spectator => (true, true, true, false,),
+            _                   => (false, false, false, false,)
+        };
+        Self { may_fly, flying, invulnerable, instant_build, fly_speed : 0.05, fov_modifier : 0.1 }
+    }
+
+    fn flags(&self) -> u8 {
+        (if (self.invulnerable) { FLAG_INVULNERABLE } else { 0 })
+        | (if (self.flying) { FLAG_FLYING } else { 0 })
+        | (if (self.may_fly) { FLAG_MAY_FLY } else { 0 })
+        | (if (self.instant_build) { FLAG_INSTANT_BUILD } else { 0 })
+    }
+
+}
+
+
+/// The abilities of a player.
+///
+/// Players join with the abilities of the default gamemode. Changing a
+///  player's [`PlayerGamemode`](crate::player::gamemode::PlayerGamemode) resets
+///  every flag to those of the new gamemode, keeping the speeds. Flags set in
+///  the same tick as a gamemode change are overwritten.
+#[derive(Component)]
+pub struct PlayerAbilities(pub(crate) Dirty<Abilities>);
+
+impl PlayerAbilities {
+
+    #[inline]
+    pub fn get(&self) -> &Abilities { &self.0 }
+
+    #[inline]
+    pub fn set(&mut self, abilities : Abilities) {
+        *self.0 = abilities;
+    }
+
+}
+
+
+/// A player is trying to start or stop flying.
+///
+/// Decoded in [`PreUpdate`] and applied in [`PostUpdate`]. Systems in [`Update`]
+///  can deny the request through an [`EventMutator`], in which case the player
+///  is sent their abilities back. Players who may not fly arrive already denied.
+#[derive(Event)]
+#[non_exhaustive]
+pub struct PlayerToggleFlight {
+    pub player           : Entity,
+    pub flying           : bool,
+    pub(crate) cancelled : bool
+}
+
+impl PlayerToggleFlight {
+
+    #[inline]
+    pub fn cancel(&mut self) { self.cancelled = true; }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool { self.cancelled }
+
+}
+
+
+pub(crate) fn read_flight_toggles(
+        q_players : Query<(&PlayerAbilities,), (With<ConnStatePlay>,)>,
+    mut er_packet : EventReader<PacketReadEvent>,
+    mut ew_toggle : EventWriter<PlayerToggleFlight>
+) {
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        if let Packet::Play(C2SPlayPackets::PlayerAbilities(PlayerAbilitiesC2SPlayPacket { flags })) = packet
+            && let Ok((abilities,)) = q_players.get(*entity)
+        {
+            let flying = (flags & FLAG_FLYING) != 0;
+            ew_toggle.write(PlayerToggleFlight {
+                player    : *entity,
+                flying,
+                cancelled : flying && (! abilities.get().may_fly)
+            });
+        }
+    }
+}
+
+pub(crate) fn apply_flight_toggles(
+    mut q_players : Query<(&mut PlayerAbilities,)>,
+    mut er_toggle : EventReader<PlayerToggleFlight>
+) {
+    for toggle in er_toggle.read() {
+        let Ok((mut abilities,)) = q_players.get_mut(toggle.player)
+            else { continue; };
+        // Writing resends the abilities, which puts the client back if denied.
+        let flying = if (toggle.cancelled) { abilities.0.flying } else { toggle.flying };
+        abilities.0.flying = flying;
+    }
+}
+
+pub(crate) fn update_abilities(
+    mut q_players : Query<(&mut Connection, &mut PlayerAbilities,), (With<ConnStatePlay>,)>
+) {
+    for (mut conn, mut abilities,) in &mut q_players {
+        if (Dirty::take_dirty(&mut abilities.0)) {
+            let _ = conn.send_packet_play(PlayerAbilitiesS2CPlayPacket {
+                flags        : abilities.0.flags(),
+                fly_speed    : abilities.0.fly_speed,
+                fov_modifier : abilities.0.fov_modifier
+            });
+        }
+    }
+}
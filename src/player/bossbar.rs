@@ -0,0 +1,212 @@
+use crate::conn::Connection;
+use crate::conn::play::ConnStatePlay;
+use flywheelmc_common::prelude::*;
+use protocol::value::Text;
+use protocol::packet::s2c::play::{
+    BossEventS2CPlayPacket,
+    BossEventAction
+};
+pub use protocol::packet::s2c::play::{ BossBarColour, BossBarDivision };
+
+
+const FLAG_DARKEN_SKY : u8 = 0x01;
+const FLAG_BOSS_MUSIC : u8 = 0x02;
+const FLAG_CREATE_FOG : u8 = 0x04;
+
+
+/// Effects a boss bar has on the players who can see it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BossBarFlags {
+    pub darken_sky : bool,
+    pub boss_music : bool,
+    pub create_fog : bool
+}
+
+impl BossBarFlags {
+
+    fn bits(&self) -> u8 {
+        (if (self.darken_sky) { FLAG_DARKEN_SKY } else { 0 })
+        | (if (self.boss_music) { FLAG_BOSS_MUSIC } else { 0 })
+        | (if (self.create_fog) { FLAG_CREATE_FOG } else { 0 })
+    }
+
+}
+
+
+/// A boss bar, shown to a set of players.
+///
+/// Spawn an entity with this component to create the boss bar, and despawn it
+///  to remove it from every viewer. Changed fields are sent at the end of the
+///  tick. Players who leave are removed from the viewers.
+#[derive(Component)]
+pub struct BossBar {
+    uuid     : Uuid,
+    title    : Dirty<Text>,
+    progress : Dirty<f32>,
+    style    : Dirty<(BossBarColour, BossBarDivision,)>,
+    flags    : Dirty<BossBarFlags>,
+    viewers  : BTreeSet<Entity>,
+    added    : BTreeSet<Entity>,
+    removed  : BTreeSet<Entity>
+}
+
+impl BossBar {
+
+    /// Creates a boss bar with a random UUID and no viewers.
+    pub fn new(title : Text, colour : BossBarColour, division : BossBarDivision) -> Self { Self {
+        uuid     : Uuid::from_u128(random::<u128>()),
+        title    : Dirty::new(title),
+        progress : Dirty::new(1.0),
+        style    : Dirty::new((colour, division,)),
+        flags    : Dirty::new(BossBarFlags::default()),
+        viewers  : BTreeSet::new(),
+        added    : BTreeSet::new(),
+        removed  : BTreeSet::new()
+    } }
+
+    #[inline]
+    pub fn uuid(&self) -> Uuid { self.uuid }
+
+    #[inline]
+    pub fn title(&self) -> &Text { &self.title }
+
+    #[inline]
+    pub fn set_title(&mut self, title : Text) {
+        *self.title = title;
+    }
+
+    #[inline]
+    pub fn progress(&self) -> f32 { *self.progress }
+
+    /// Sets how full the bar is, from `0.0` to `1.0`.
+    pub fn set_progress(&mut self, progress : f32) {
+        let progress = progress.clamp(0.0, 1.0);
+        if (*self.progress != progress) {
+            *self.progress = progress;
+        }
+    }
+
+    #[inline]
+    pub fn colour(&self) -> BossBarColour { self.style.0 }
+
+    #[inline]
+    pub fn division(&self) -> BossBarDivision { self.style.1 }
+
+    pub fn set_style(&mut self, colour : BossBarColour, division : BossBarDivision) {
+        if (*self.style != (colour, division,)) {
+            *self.style = (colour, division,);
+        }
+    }
+
+    #[inline]
+    pub fn flags(&self) -> BossBarFlags { *self.flags }
+
+    pub fn set_flags(&mut self, flags : BossBarFlags) {
+        if (*self.flags != flags) {
+            *self.flags = flags;
+        }
+    }
+
+    #[inline]
+    pub fn viewers(&self) -> &BTreeSet<Entity> { &self.viewers }
+
+    pub fn show_to(&mut self, player : Entity) {
+        if (self.viewers.insert(player) && (! self.removed.remove(&player))) {
+            self.added.insert(player);
+        }
+    }
+
+    pub fn hide_from(&mut self, player : Entity) {
+        if (self.viewers.remove(&player) && (! self.added.remove(&player))) {
+            self.removed.insert(player);
+        }
+    }
+
+    fn add_packet(&self) -> BossEventS2CPlayPacket {
+        BossEventS2CPlayPacket {
+            uuid   : self.uuid,
+            action : BossEventAction::Add {
+                title    : self.title.to_nbt(),
+                progress : *self.progress,
+                colour   : self.style.0,
+                division : self.style.1,
+                flags    : self.flags.bits()
+            }
+        }
+    }
+
+}
+
+
+pub(crate) fn update_boss_bars(
+    mut q_players : Query<(&mut Connection,), (With<ConnStatePlay>,)>,
+    mut q_bars    : Query<(Entity, &mut BossBar,)>,
+    mut rc_bars   : RemovedComponents<BossBar>,
+    mut rc_conns  : RemovedComponents<Connection>,
+    mut l_sent    : Local<BTreeMap<Entity, (Uuid, BTreeSet<Entity>,)>>
+) {
+    let departed = rc_conns.read().collect::<BTreeSet<_>>();
+
+    for entity in rc_bars.read() {
+        let Some((uuid, viewers,)) = l_sent.remove(&entity)
+            else { continue; };
+        for viewer in viewers {
+            if let Ok((mut conn,)) = q_players.get_mut(viewer) {
+                let _ = conn.send_packet_play(BossEventS2CPlayPacket { uuid, action : BossEventAction::Remove });
+            }
+        }
+    }
+
+    for (entity, mut bar,) in &mut q_bars {
+        // Players who left can not be sent anything.
+        if (! departed.is_empty()) {
+            bar.viewers.retain(|viewer| ! departed.contains(viewer));
+            bar.added.retain(|viewer| ! departed.contains(viewer));
+            bar.removed.retain(|viewer| ! departed.contains(viewer));
+        }
+        let bar = &mut *bar;
+
+        for viewer in mem::take(&mut bar.removed) {
+            if let Ok((mut conn,)) = q_players.get_mut(viewer) {
+                let _ = conn.send_packet_play(BossEventS2CPlayPacket { uuid : bar.uuid, action : BossEventAction::Remove });
+            }
+        }
+
+        let title_changed    = Dirty::take_dirty(&mut bar.title);
+        let progress_changed = Dirty::take_dirty(&mut bar.progress);
+        let style_changed    = Dirty::take_dirty(&mut bar.style);
+        let flags_changed    = Dirty::take_dirty(&mut bar.flags);
+        let added            = mem::take(&mut bar.added);
+        let mut waiting      = Vec::new();
+        for &viewer in &bar.viewers {
+            let Ok((mut conn,)) = q_players.get_mut(viewer) else {
+                // Players who are still joining are shown the bar once they are playing.
+                if (added.contains(&viewer)) { waiting.push(viewer); }
+                continue;
+            };
+            if (added.contains(&viewer)) {
+                let _ = conn.send_packet_play(bar.add_packet());
+                continue;
+            }
+            if (title_changed) {
+                let _ = conn.send_packet_play(BossEventS2CPlayPacket { uuid : bar.uuid, action : BossEventAction::UpdateTitle(bar.title.to_nbt()) });
+            }
+            if (progress_changed) {
+                let _ = conn.send_packet_play(BossEventS2CPlayPacket { uuid : bar.uuid, action : BossEventAction::UpdateProgress(*bar.progress) });
+            }
+            if (style_changed) {
+                let _ = conn.send_packet_play(BossEventS2CPlayPacket { uuid : bar.uuid, action : BossEventAction::UpdateStyle {
+                    colour   : bar.style.0,
+                    division : bar.style.1
+                } });
+            }
+            if (flags_changed) {
+                let _ = conn.send_packet_play(BossEventS2CPlayPacket { uuid : bar.uuid, action : BossEventAction::UpdateFlags(bar.flags.bits()) });
+            }
+        }
+
+        bar.added.extend(waiting);
+
+        l_sent.insert(entity, (bar.uuid, bar.viewers.clone(),));
+    }
+}
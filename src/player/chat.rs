@@ -0,0 +1,164 @@
+use crate::conn::Connection;
+use crate::conn::packet::{ PacketReadEvent, Packet };
+use crate::conn::play::ConnStatePlay;
+use crate::player::Player;
+use crate::player::comms::{ PlayerCommsActionEvent, PlayerCommsAction };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Text, TextComponent, TextColour, ChatMode };
+use protocol::packet::c2s::config::{
+    C2SConfigPackets,
+    ClientInformationC2SConfigPacket
+};
+use protocol::packet::c2s::play::{
+    C2SPlayPackets,
+    ChatC2SPlayPacket,
+    ClientInformationC2SPlayPacket
+};
+
+
+/// How many chat messages a player may send in a period of time.
+///
+/// Messages over the limit are dropped, and the player is told to slow down.
+#[derive(Resource, Clone, Copy)]
+pub struct ChatRateLimit {
+    pub messages : usize,
+    pub per      : Duration
+}
+
+
+/// The chat state of a player.
+#[derive(Component)]
+pub struct PlayerChatState {
+    muted      : bool,
+    visibility : ChatMode,
+    recent     : VecDeque<Instant>
+}
+
+impl Default for PlayerChatState {
+    fn default() -> Self { Self {
+        muted      : false,
+        visibility : ChatMode::Enabled,
+        recent     : VecDeque::new()
+    } }
+}
+
+impl PlayerChatState {
+
+    /// Returns `true` if messages sent by this player are denied.
+    #[inline]
+    pub fn is_muted(&self) -> bool { self.muted }
+
+    #[inline]
+    pub fn set_muted(&mut self, muted : bool) { self.muted = muted; }
+
+    /// Returns `true` if this player wants to see chat from other players.
+    #[inline]
+    pub fn shows_chat(&self) -> bool { matches!(self.visibility, ChatMode::Enabled) }
+
+    /// Records a message sent now, returning `false` if it goes over `limit`.
+    fn record(&mut self, limit : &ChatRateLimit) -> bool {
+        let now = Instant::now();
+        while let Some(&sent) = self.recent.front()
+            && (now.duration_since(sent) >= limit.per)
+        { self.recent.pop_front(); }
+        if (self.recent.len() >= limit.messages) { return false; }
+        self.recent.push_back(now);
+        true
+    }
+
+}
+
+
+/// A player sent a chat message.
+///
+/// Decoded in [`PreUpdate`] and broadcast in [`PostUpdate`] to every player
+///  who has chat enabled. Systems in [`Update`] can change `formatted` or deny
+///  the message through an [`EventMutator`]. Messages from muted players
+///  arrive already denied.
+#[derive(Event)]
+#[non_exhaustive]
+pub struct PlayerChat {
+    pub player           : Entity,
+    pub message          : String,
+    /// The text broadcast to other players, `<username> message` by default.
+    pub formatted        : Text,
+    pub(crate) cancelled : bool
+}
+
+impl PlayerChat {
+
+    #[inline]
+    pub fn cancel(&mut self) { self.cancelled = true; }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool { self.cancelled }
+
+}
+
+
+pub(crate) fn read_chat_settings(
+    mut q_players : Query<(&mut PlayerChatState,),>,
+    mut er_packet : EventReader<PacketReadEvent>
+) {
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        if let Ok((mut state,)) = q_players.get_mut(*entity)
+            && let    Packet::Config(C2SConfigPackets::ClientInformation(ClientInformationC2SConfigPacket { info }))
+                    | Packet::Play(C2SPlayPackets::ClientInformation(ClientInformationC2SPlayPacket { info })) = packet
+        { state.visibility = info.chat_mode; }
+    }
+}
+
+pub(crate) fn read_chat_messages(
+    mut q_players : Query<(&Player, &Connection, &mut PlayerChatState,), (With<ConnStatePlay>,)>,
+    mut er_packet : EventReader<PacketReadEvent>,
+    mut ew_chat   : EventWriter<PlayerChat>,
+    mut ew_comms  : EventWriter<PlayerCommsActionEvent>,
+        r_limit   : Res<ChatRateLimit>
+) {
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        if let Packet::Play(C2SPlayPackets::Chat(ChatC2SPlayPacket { message, .. })) = packet
+            && let Ok((player, conn, mut state,)) = q_players.get_mut(*entity)
+        {
+            if (! state.record(&r_limit)) {
+                trace!("Dropping chat message from peer {}: rate limited", conn.peer_addr());
+                ew_comms.write(PlayerCommsActionEvent {
+                    entity : *entity,
+                    action : PlayerCommsAction::Chat {
+                        message : Text::from(vec![
+                            TextComponent::of_literal("You are sending messages too quickly.").colour(TextColour::Red)
+                        ])
+                    }
+                });
+                continue;
+            }
+            ew_chat.write(PlayerChat {
+                player    : *entity,
+                message   : message.to_string(),
+                formatted : Text::from(vec![
+                    TextComponent::of_literal(format!("<{}> ", player.username())),
+                    TextComponent::of_literal(message.to_string())
+                ]),
+                cancelled : state.muted
+            });
+        }
+    }
+}
+
+pub(crate) fn broadcast_chat_messages(
+        q_players : Query<(Entity, &PlayerChatState,), (With<ConnStatePlay>,)>,
+    mut er_chat   : EventReader<PlayerChat>,
+    mut ew_comms  : EventWriter<PlayerCommsActionEvent>
+) {
+    for chat in er_chat.read() {
+        if (chat.cancelled) { continue; }
+        for (entity, state,) in &q_players {
+            // Players always see their own messages.
+            if (entity == chat.player || state.shows_chat()) {
+                ew_comms.write(PlayerCommsActionEvent {
+                    entity,
+                    action : PlayerCommsAction::Chat { message : chat.formatted.clone() }
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use crate::conn::Connection;
+use crate::conn::packet::{ PacketReadEvent, Packet };
+use crate::conn::play::ConnStatePlay;
+use crate::player::PlayerJoined;
+use crate::player::comms::{ PlayerCommsActionEvent, PlayerCommsAction };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Identifier, Text, TextComponent, TextColour };
+use protocol::packet::c2s::play::{
+    C2SPlayPackets,
+    ChatCommandC2SPlayPacket,
+    ChatCommandSignedC2SPlayPacket,
+    CommandSuggestionC2SPlayPacket
+};
+use protocol::packet::s2c::play::{
+    CommandsS2CPlayPacket,
+    CommandSuggestionsS2CPlayPacket,
+    CommandSuggestionMatch,
+    CommandNode as RawCommandNode,
+    CommandNodeKind as RawCommandNodeKind,
+    CommandArgParser,
+    CommandStringKind
+};
+
+
+/// Decides whether a player may see and run a command node.
+pub type CommandRequirement = fn(EntityRef) -> bool;
+
+/// Returns suggestions for a partially typed argument.
+///
+/// Suggestions which do not start with the partial argument are discarded.
+pub type SuggestionProvider = fn(EntityRef, &str) -> Vec<String>;
+
+
+/// The type of a command argument.
+#[derive(Clone, Copy, Debug)]
+pub enum ArgumentKind {
+    Bool,
+    Integer { min : Option<i32>, max : Option<i32> },
+    Double { min : Option<f64>, max : Option<f64> },
+    /// A single word, without spaces.
+    Word,
+    /// A single word, or a quoted string.
+    Phrase,
+    /// The rest of the command.
+    Greedy
+}
+
+impl ArgumentKind {
+
+    /// Reads an argument from the start of `input`, returning the value and the unread input.
+    fn read<'l>(&self, input : &'l str) -> Option<(CommandArg, &'l str,)> {
+        let (word, rest,) = input.split_at(input.find(' ').unwrap_or(input.len()));
+        match (self) {
+            Self::Bool => match (word) {
+                "true"  => Some((CommandArg::Bool(true), rest,)),
+                "false" => Some((CommandArg::Bool(false), rest,)),
+                _       => None
+            },
+            Self::Integer { min, max } => {
+                let value = word.parse::<i32>().ok()?;
+                if (min.is_some_and(|min| value < min) || max.is_some_and(|max| value > max)) { return None; }
+                Some((CommandArg::Integer(value), rest,))
+            },
+            Self::Double { min, max } => {
+                let value = word.parse::<f64>().ok().filter(|value| value.is_finite())?;
+                if (min.is_some_and(|min| value < min) || max.is_some_and(|max| value > max)) { return None; }
+                Some((CommandArg::Double(value), rest,))
+            },
+            Self::Word => {
+                if (word.is_empty()) { return None; }
+                Some((CommandArg::String(word.to_string()), rest,))
+            },
+            Self::Phrase => {
+                let Some(quoted) = input.strip_prefix('"') else {
+                    return Self::Word.read(input);
+                };
+                let mut value   = String::new();
+                let mut escaped = false;
+                for (i, ch,) in quoted.char_indices() {
+                    match (ch) {
+                        _ if (escaped) => { value.push(ch); escaped = false; },
+                        '\\'           => { escaped = true; },
+                        '"'            => { return Some((CommandArg::String(value), &quoted[(i + 1)..],)); },
+                        _              => { value.push(ch); }
+                    }
+                }
+                None
+            },
+            Self::Greedy => {
+                if (input.is_empty()) { return None; }
+                Some((CommandArg::String(input.to_string()), "",))
+            }
+        }
+    }
+
+    fn parser(&self) -> CommandArgParser {
+        match (*self) {
+            Self::Bool                 => CommandArgParser::Bool,
+            Self::Integer { min, max } => CommandArgParser::Integer { min, max },
+            Self::Double { min, max }  => CommandArgParser::Double { min, max },
+            Self::Word                 => CommandArgParser::String(CommandStringKind::SingleWord),
+            Self::Phrase               => CommandArgParser::String(CommandStringKind::QuotablePhrase),
+            Self::Greedy               => CommandArgParser::String(CommandStringKind::GreedyPhrase)
+        }
+    }
+
+}
+
+
+/// The value of a parsed command argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandArg {
+    Bool(bool),
+    Integer(i32),
+    Double(f64),
+    String(String)
+}
+
+
+enum CommandNodeKind {
+    Literal(String),
+    Argument {
+        name     : String,
+        kind     : ArgumentKind,
+        suggests : Option<SuggestionProvider>
+    }
+}
+
+/// A node in the command tree.
+///
+/// ```ignore
+/// CommandNode::literal("give")
+///     .then(CommandNode::argument("item", ArgumentKind::Word)
+///         .suggests(suggest_items)
+///         .executes()
+///         .then(CommandNode::argument("count", ArgumentKind::Integer { min : Some(1), max : Some(64) })
+///             .executes()))
+/// ```
+pub struct CommandNode {
+    kind       : CommandNodeKind,
+    children   : Vec<CommandNode>,
+    executable : bool,
+    requires   : Option<CommandRequirement>
+}
+
+impl CommandNode {
+
+    pub fn literal(name : impl Into<String>) -> Self { Self {
+        kind       : CommandNodeKind::Literal(name.into()),
+        children   : Vec::new(),
+        executable : false,
+        requires   : None
+    } }
+
+    pub fn argument(name : impl Into<String>, kind : ArgumentKind) -> Self { Self {
+        kind       : CommandNodeKind::Argument { name : name.into(), kind, suggests : None },
+        children   : Vec::new(),
+        executable : false,
+        requires   : None
+    } }
+
+    /// Adds a child node.
+    pub fn then(mut self, child : CommandNode) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Marks this node as a complete command.
+    ///
+    /// Running it writes a [`PlayerCommand`] event.
+    pub fn executes(mut self) -> Self {
+        self.executable = true;
+        self
+    }
+
+    /// Hides this node and its children from players who do not meet `requirement`.
+    pub fn requires(mut self, requirement : CommandRequirement) -> Self {
+        self.requires = Some(requirement);
+        self
+    }
+
+    /// Asks the server for suggestions when completing this argument. Ignored on literals.
+    pub fn suggests(mut self, provider : SuggestionProvider) -> Self {
+        if let CommandNodeKind::Argument { suggests, .. } = &mut self.kind {
+            *suggests = Some(provider);
+        }
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        match (&self.kind) {
+            CommandNodeKind::Literal(name)         => name,
+            CommandNodeKind::Argument { name, .. } => name
+        }
+    }
+
+    #[inline]
+    fn permits(&self, player : EntityRef) -> bool {
+        self.requires.is_none_or(|requirement| requirement(player))
+    }
+
+    /// Reads this node from the start of `input`, returning the argument value if any, and the unread input.
+    fn read<'l>(&self, input : &'l str) -> Option<(Option<CommandArg>, &'l str,)> {
+        match (&self.kind) {
+            CommandNodeKind::Literal(name) => {
+                let rest = input.strip_prefix(name.as_str())?;
+                if (! (rest.is_empty() || rest.starts_with(' '))) { return None; }
+                Some((None, rest,))
+            },
+            CommandNodeKind::Argument { kind, .. } => {
+                let (value, rest,) = kind.read(input)?;
+                if (! (rest.is_empty() || rest.starts_with(' '))) { return None; }
+                Some((Some(value), rest,))
+            }
+        }
+    }
+
+}
+
+
+/// Every registered command.
+///
+/// Changing it resends the command tree to every player.
+#[derive(Resource, Default)]
+pub struct CommandTree {
+    roots : Vec<CommandNode>
+}
+
+impl CommandTree {
+
+    /// Registers a command, replacing any command with the same name.
+    ///
+    /// `node` should be a literal.
+    pub fn register(&mut self, node : CommandNode) {
+        self.roots.retain(|root| root.name() != node.name());
+        self.roots.push(node);
+    }
+
+    pub fn unregister(&mut self, name : &str) {
+        self.roots.retain(|root| root.name() != name);
+    }
+
+    /// Flattens the nodes `player` may see into the commands packet.
+    fn to_packet(&self, player : EntityRef) -> CommandsS2CPlayPacket {
+        let mut nodes = vec![RawCommandNode {
+            kind       : RawCommandNodeKind::Root,
+            executable : false,
+            children   : Vec::new().into(),
+            redirect   : None
+        }];
+        let children = Self::flatten(&self.roots, player, &mut nodes);
+        nodes[0].children = children.into_iter().map(|index| (index as i32).into()).collect::<Vec<_>>().into();
+        CommandsS2CPlayPacket { nodes : nodes.into(), root : 0.into() }
+    }
+
+    /// Appends the nodes `player` may see, returning the indices of `children`.
+    fn flatten(children : &[CommandNode], player : EntityRef, nodes : &mut Vec<RawCommandNode>) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(children.len());
+        for child in children {
+            if (! child.permits(player)) { continue; }
+            let index = nodes.len();
+            nodes.push(RawCommandNode {
+                kind       : match (&child.kind) {
+                    CommandNodeKind::Literal(name) => RawCommandNodeKind::Literal { name : name.clone() },
+                    CommandNodeKind::Argument { name, kind, suggests } => RawCommandNodeKind::Argument {
+                        name        : name.clone(),
+                        parser      : kind.parser(),
+                        suggestions : suggests.map(|_| Identifier::vanilla_const("ask_server"))
+                    }
+                },
+                executable : child.executable,
+                children   : Vec::new().into(),
+                redirect   : None
+            });
+            let grandchildren = Self::flatten(&child.children, player, nodes);
+            nodes[index].children = grandchildren.into_iter().map(|index| (index as i32).into()).collect::<Vec<_>>().into();
+            indices.push(index);
+        }
+        indices
+    }
+
+    /// Parses `input` (without the leading `/`), returning the command path and arguments.
+    fn parse(&self, player : EntityRef, input : &str) -> Option<(Vec<String>, BTreeMap<String, CommandArg>,)> {
+        let mut path = Vec::new();
+        let mut args = BTreeMap::new();
+        Self::parse_children(&self.roots, player, input, &mut path, &mut args).then_some((path, args,))
+    }
+
+    fn parse_children(
+        children : &[CommandNode],
+        player   : EntityRef,
+        input    : &str,
+        path     : &mut Vec<String>,
+        args     : &mut BTreeMap<String, CommandArg>
+    ) -> bool {
+        for child in children {
+            if (! child.permits(player)) { continue; }
+            let Some((value, rest,)) = child.read(input)
+                else { continue; };
+            path.push(child.name().to_string());
+            if let Some(value) = value {
+                args.insert(child.name().to_string(), value);
+            }
+            let complete = match (rest.strip_prefix(' ')) {
+                None       => child.executable,
+                Some(rest) => Self::parse_children(&child.children, player, rest, path, args)
+            };
+            if (complete) { return true; }
+            path.pop();
+            args.remove(child.name());
+        }
+        false
+    }
+
+    /// Collects suggestions for the last argument of `input`, which starts at byte `offset` of the full text.
+    fn suggest(
+        children : &[CommandNode],
+        player   : EntityRef,
+        input    : &str,
+        offset   : usize,
+        out      : &mut Vec<(usize, String,)>
+    ) {
+        for child in children {
+            if (! child.permits(player)) { continue; }
+            if let CommandNodeKind::Argument { kind, suggests : Some(provider), .. } = &child.kind
+                && (matches!(kind, ArgumentKind::Greedy) || (! input.contains(' ')))
+            {
+                for suggestion in provider(player, input) {
+                    if (suggestion.starts_with(input)) {
+                        out.push((offset, suggestion,));
+                    }
+                }
+            }
+            if let Some((_, rest,)) = child.read(input)
+                && let Some(rest) = rest.strip_prefix(' ')
+            { Self::suggest(&child.children, player, rest, offset + (input.len() - rest.len()), out); }
+        }
+    }
+
+}
+
+
+/// A player ran a command.
+///
+/// `path` holds the name of every node on the way to the executed node,
+///  starting with the command name. Handler systems should match on it.
+#[derive(Event)]
+#[non_exhaustive]
+pub struct PlayerCommand {
+    pub player : Entity,
+    pub input  : String,
+    pub path   : Vec<String>,
+    pub args   : BTreeMap<String, CommandArg>
+}
+
+impl PlayerCommand {
+
+    /// Returns the name of the command.
+    #[inline]
+    pub fn command(&self) -> &str { &self.path[0] }
+
+    /// Returns `true` if the nodes on the way to the executed node are `path`.
+    pub fn is(&self, path : &[&str]) -> bool {
+        self.path.iter().map(String::as_str).eq(path.iter().copied())
+    }
+
+    pub fn get_bool(&self, name : &str) -> Option<bool> {
+        if let Some(CommandArg::Bool(value)) = self.args.get(name) { Some(*value) } else { None }
+    }
+
+    pub fn get_int(&self, name : &str) -> Option<i32> {
+        if let Some(CommandArg::Integer(value)) = self.args.get(name) { Some(*value) } else { None }
+    }
+
+    pub fn get_double(&self, name : &str) -> Option<f64> {
+        if let Some(CommandArg::Double(value)) = self.args.get(name) { Some(*value) } else { None }
+    }
+
+    pub fn get_str(&self, name : &str) -> Option<&str> {
+        if let Some(CommandArg::String(value)) = self.args.get(name) { Some(value) } else { None }
+    }
+
+}
+
+
+/// Resends the command tree to a player, for example after their permissions changed.
+#[derive(Event)]
+pub struct RefreshCommands {
+    pub entity : Entity
+}
+
+
+pub(crate) fn read_commands(
+    mut q_players  : ParamSet<(Query<EntityRef, (With<ConnStatePlay>,)>, Query<(&mut Connection,)>,)>,
+    mut er_packet  : EventReader<PacketReadEvent>,
+    mut ew_command : EventWriter<PlayerCommand>,
+    mut ew_comms   : EventWriter<PlayerCommsActionEvent>,
+        r_tree     : Res<CommandTree>
+) {
+    let mut responses = Vec::new();
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        match (packet) {
+
+            Packet::Play(C2SPlayPackets::ChatCommand(ChatCommandC2SPlayPacket { command }))
+            | Packet::Play(C2SPlayPackets::ChatCommandSigned(ChatCommandSignedC2SPlayPacket { command, .. })) => {
+                let q_views = q_players.p0();
+                let Ok(player) = q_views.get(*entity)
+                    else { continue; };
+                if let Some((path, args,)) = r_tree.parse(player, command) {
+                    ew_command.write(PlayerCommand {
+                        player : *entity,
+                        input  : command.to_string(),
+                        path,
+                        args
+                    });
+                } else {
+                    ew_comms.write(PlayerCommsActionEvent {
+                        entity : *entity,
+                        action : PlayerCommsAction::Chat {
+                            message : Text::from(vec![
+                                TextComponent::of_literal(format!("Unknown or incomplete command: /{command}")).colour(TextColour::Red)
+                            ])
+                        }
+                    });
+                }
+            },
+
+            Packet::Play(C2SPlayPackets::CommandSuggestion(CommandSuggestionC2SPlayPacket { id, text })) => {
+                let q_views = q_players.p0();
+                let Ok(player) = q_views.get(*entity)
+                    else { continue; };
+                let (input, offset,) = match (text.strip_prefix('/')) {
+                    Some(input) => (input, 1,),
+                    None        => (text.as_str(), 0,)
+                };
+                let mut suggestions = Vec::new();
+                CommandTree::suggest(&r_tree.roots, player, input, offset, &mut suggestions);
+                // Suggestions replace the text from the start of the last argument.
+                //  The client waits for a response, so one is sent even with no suggestions.
+                let start = suggestions.first().map_or(text.len(), |&(start, _,)| start);
+                suggestions.retain(|&(other, _,)| other == start);
+                // The client counts in UTF-16 code units.
+                let (before, replaced,) = text.split_at(start);
+                responses.push((*entity, CommandSuggestionsS2CPlayPacket {
+                    id      : *id,
+                    start   : (before.encode_utf16().count() as i32).into(),
+                    length  : (replaced.encode_utf16().count() as i32).into(),
+                    matches : suggestions.into_iter().map(|(_, text,)| CommandSuggestionMatch { text, tooltip : None }).collect::<Vec<_>>().into()
+                },));
+            },
+
+            _ => { }
+
+        }
+    }
+    let mut q_conns = q_players.p1();
+    for (entity, packet,) in responses {
+        if let Ok((mut conn,)) = q_conns.get_mut(entity) {
+            let _ = conn.send_packet_play(packet);
+        }
+    }
+}
+
+pub(crate) fn send_command_trees(
+    mut q_players  : ParamSet<(Query<EntityRef, (With<ConnStatePlay>,)>, Query<(&mut Connection,)>,)>,
+    mut er_joined  : EventReader<PlayerJoined>,
+    mut er_refresh : EventReader<RefreshCommands>,
+        r_tree     : Res<CommandTree>
+) {
+    let targets = if (r_tree.is_changed()) {
+        er_joined.clear();
+        er_refresh.clear();
+        q_players.p0().iter().map(|player| player.id()).collect::<BTreeSet<_>>()
+    } else {
+        er_joined.read().map(|joined| joined.entity).chain(er_refresh.read().map(|refresh| refresh.entity)).collect::<BTreeSet<_>>()
+    };
+    if (targets.is_empty()) { return; }
+
+    let q_views = q_players.p0();
+    let packets = targets.into_iter()
+        .filter_map(|entity| Some((entity, r_tree.to_packet(q_views.get(entity).ok()?),)))
+        .collect::<Vec<_>>();
+    let mut q_conns = q_players.p1();
+    for (entity, packet,) in packets {
+        if let Ok((mut conn,)) = q_conns.get_mut(entity) {
+            let _ = conn.send_packet_play(packet);
+        }
+    }
+}
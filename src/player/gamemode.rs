@@ -0,0 +1,78 @@
+use crate::conn::Connection;
+use crate::conn::play::ConnStatePlay;
+use crate::player::Player;
+use crate::player::abilities::{ PlayerAbilities, Abilities };
+use flywheelmc_common::prelude::*;
+use protocol::packet::s2c::play::{
+    GameEventS2CPlayPacket,
+    PlayerInfoUpdateS2CPlayPacket,
+    Gamemode,
+    GameEvent,
+    PlayerActionEntry
+};
+
+
+/// The gamemode of a player.
+///
+/// Players join in the default gamemode set on [`FlywheelMcPlayersPlugin`](crate::FlywheelMcPlayersPlugin).
+///  Changing it is sent to the player, and to the player list of every other player.
+#[derive(Component)]
+pub struct PlayerGamemode(pub(crate) Dirty<Gamemode>);
+
+impl PlayerGamemode {
+
+    #[inline]
+    pub fn get(&self) -> Gamemode { *self.0 }
+
+    #[inline]
+    pub fn set(&mut self, gamemode : Gamemode) {
+        if (*self.0 != gamemode) {
+            *self.0 = gamemode;
+        }
+    }
+
+}
+
+
+/// Returns the value of the change-gamemode game event for `gamemode`.
+fn game_event_value(gamemode : Gamemode) -> f32 {
+    match (gamemode) {
+        Gamemode::None | Gamemode::Survival => 0.0,
+        Gamemode::Creative                  => 1.0,
+        Gamemode::Adventure                 => 2.0,
+        Gamemode::Spectator                 => 3.0
+    }
+}
+
+
+pub(crate) fn update_gamemodes(
+    mut q_players : Query<(&Player, &mut Connection, &mut PlayerGamemode, &mut PlayerAbilities,), (With<ConnStatePlay>,)>
+) {
+    let mut changed = Vec::new();
+    for (player, mut conn, mut gamemode, mut abilities,) in &mut q_players {
+        if (Dirty::take_dirty(&mut gamemode.0)) {
+            trace!("Updating gamemode of peer {} to {:?}", conn.peer_addr(), *gamemode.0);
+            let _ = conn.send_packet_play(GameEventS2CPlayPacket {
+                event : GameEvent::ChangeGameMode,
+                value : game_event_value(*gamemode.0)
+            });
+            // The client resets its abilities when its gamemode changes.
+            let current = *abilities.get();
+            abilities.set(Abilities {
+                fly_speed    : current.fly_speed,
+                fov_modifier : current.fov_modifier,
+                ..Abilities::for_gamemode(*gamemode.0)
+            });
+            changed.push((player.uuid, *gamemode.0,));
+        }
+    }
+    if (changed.is_empty()) { return; }
+
+    for (_, mut conn, _, _,) in &mut q_players {
+        let _ = conn.send_packet_play(PlayerInfoUpdateS2CPlayPacket {
+            actions : changed.iter().map(|&(uuid, gamemode,)| (uuid, vec![
+                PlayerActionEntry::UpdateGamemode(gamemode)
+            ],)).collect::<Vec<_>>()
+        });
+    }
+}
@@ -0,0 +1,319 @@
+use crate::conn::Connection;
+use crate::conn::packet::{ PacketReadEvent, Packet };
+use crate::conn::play::ConnStatePlay;
+use crate::player::gamemode::PlayerGamemode;
+use crate::player::inventory::{ PlayerInventory, ItemStack, HOTBAR_START, OFFHAND_SLOT };
+use crate::player::movement::PlayerPosition;
+use crate::world::{ World, WorldOverlay, PlayerInWorld, ChunkView, BlockProps, BlockChangeCause, BLOCK_AIR, state_entry };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Identifier, BlockPos, BlockState, Direction, Hand };
+use protocol::packet::c2s::play::{
+    C2SPlayPackets,
+    PlayerActionC2SPlayPacket,
+    PlayerActionStatus,
+    UseItemOnC2SPlayPacket
+};
+use protocol::packet::s2c::play::{
+    BlockUpdateS2CPlayPacket,
+    BlockChangedAckS2CPlayPacket,
+    Gamemode
+};
+use protocol::registry::RegEntry;
+
+
+/// The furthest a player can reach to break or place a block, from their eyes to the centre of the block.
+const MAX_REACH : f64 = 6.0;
+
+/// Items which place a block with a different name. Every other block item
+///  places the block with the same name.
+const ITEM_BLOCKS : &[(&str, &str,)] = &[
+    ("minecraft:bamboo",             "minecraft:bamboo_sapling",),
+    ("minecraft:beetroot_seeds",     "minecraft:beetroots",),
+    ("minecraft:carrot",             "minecraft:carrots",),
+    ("minecraft:cocoa_beans",        "minecraft:cocoa",),
+    ("minecraft:glow_berries",       "minecraft:cave_vines",),
+    ("minecraft:melon_seeds",        "minecraft:melon_stem",),
+    ("minecraft:pitcher_pod",        "minecraft:pitcher_crop",),
+    ("minecraft:potato",             "minecraft:potatoes",),
+    ("minecraft:powder_snow_bucket", "minecraft:powder_snow",),
+    ("minecraft:pumpkin_seeds",      "minecraft:pumpkin_stem",),
+    ("minecraft:redstone",           "minecraft:redstone_wire",),
+    ("minecraft:string",             "minecraft:tripwire",),
+    ("minecraft:sweet_berries",      "minecraft:sweet_berry_bush",),
+    ("minecraft:torchflower_seeds",  "minecraft:torchflower_crop",),
+    ("minecraft:wheat_seeds",        "minecraft:wheat",)
+];
+
+
+/// A player is trying to break a block.
+///
+/// Survival and adventure players break a block when they finish digging it,
+///  or as soon as they start if it breaks instantly.
+///
+/// Decoded in [`PreUpdate`] and applied in [`PostUpdate`]. Systems in [`Update`]
+///  can deny the attempt through an [`EventMutator`], in which case the client
+///  is sent the block back. Attempts by players in adventure or spectator mode,
+///  out of reach, or in chunks the player has not loaded arrive already denied.
+#[derive(Event)]
+#[non_exhaustive]
+pub struct BlockBreakAttempt {
+    pub player           : Entity,
+    pub world            : Entity,
+    pub pos              : BlockPos,
+    pub face             : Direction,
+    pub(crate) sequence  : i32,
+    pub(crate) cancelled : bool
+}
+
+impl BlockBreakAttempt {
+
+    #[inline]
+    pub fn cancel(&mut self) { self.cancelled = true; }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool { self.cancelled }
+
+}
+
+
+/// A player is trying to place a block.
+///
+/// `pos` is the position the block would be placed at, next to `against`.
+///  `block` starts as the block form of the item in `hand`, if it has one.
+///  Nothing is placed unless `block` is set. Survival players use up one item
+///  per block placed.
+///
+/// Decoded in [`PreUpdate`] and applied in [`PostUpdate`]. Systems in [`Update`]
+///  can deny the attempt, or choose the block to place, through an
+///  [`EventMutator`]. Denied attempts are sent the blocks back. Attempts by
+///  players in adventure or spectator mode, out of reach, or in chunks the
+///  player has not loaded arrive already denied.
+#[derive(Event)]
+#[non_exhaustive]
+pub struct BlockPlaceAttempt {
+    pub player           : Entity,
+    pub world            : Entity,
+    pub pos              : BlockPos,
+    pub against          : BlockPos,
+    pub face             : Direction,
+    pub hand             : Hand,
+    pub block            : Option<RegEntry<BlockState>>,
+    pub(crate) sequence  : i32,
+    pub(crate) cancelled : bool
+}
+
+impl BlockPlaceAttempt {
+
+    #[inline]
+    pub fn cancel(&mut self) { self.cancelled = true; }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool { self.cancelled }
+
+}
+
+
+#[expect(clippy::type_complexity)]
+pub(crate) fn read_block_interactions(
+    mut q_players : Query<(&mut Connection, Option<&PlayerInWorld>, Option<&WorldOverlay>, &PlayerGamemode, &PlayerInventory, &PlayerPosition, &ChunkView,), (With<ConnStatePlay>,)>,
+        q_worlds  : Query<(&World,)>,
+    mut er_packet : EventReader<PacketReadEvent>,
+    mut ew_break  : EventWriter<BlockBreakAttempt>,
+    mut ew_place  : EventWriter<BlockPlaceAttempt>,
+    mut l_digging : Local<BTreeMap<Entity, BlockPos>>
+) {
+    l_digging.retain(|&entity, _| q_players.contains(entity));
+
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        match (packet) {
+
+            Packet::Play(C2SPlayPackets::PlayerAction(PlayerActionC2SPlayPacket { status, pos, face, sequence })) => {
+                // Other statuses are about items, and are not acknowledged.
+                if (! matches!(status, PlayerActionStatus::StartDigging | PlayerActionStatus::CancelDigging | PlayerActionStatus::FinishDigging)) { continue; }
+                let sequence : i32 = (*sequence).into();
+                let Ok((mut conn, in_world, overlay, gamemode, _, position, view,)) = q_players.get_mut(*entity)
+                    else { continue; };
+                if (matches!(status, PlayerActionStatus::CancelDigging)) {
+                    l_digging.remove(entity);
+                }
+                let world = in_world.and_then(|in_world| q_worlds.get(in_world.0).ok())
+                    .map(|(world,)| target_world(world, overlay, *pos));
+                let digging = matches!(status, PlayerActionStatus::StartDigging | PlayerActionStatus::FinishDigging);
+                let started = if (matches!(status, PlayerActionStatus::StartDigging)) {
+                    l_digging.insert(*entity, *pos);
+                    true
+                } else { false };
+                let finished = digging && (! started) && (l_digging.remove(entity) == Some(*pos));
+                let breaks = match (gamemode.get()) {
+                    // Creative players break blocks as soon as they start digging.
+                    Gamemode::Creative  => started,
+                    Gamemode::Spectator => false,
+                    _                   => finished || (started && world.and_then(|world| world.get_block_entry(*pos))
+                        .is_some_and(|block| block != BLOCK_AIR && BlockProps::of(block).breaks_instantly))
+                };
+                if (breaks)
+                    && let Some(in_world) = in_world
+                {
+                    trace!("Peer {} is breaking <{}, {}, {}>", conn.peer_addr(), pos.x, pos.y, pos.z);
+                    l_digging.remove(entity);
+                    ew_break.write(BlockBreakAttempt {
+                        player    : *entity,
+                        world     : in_world.0,
+                        pos       : *pos,
+                        face      : *face,
+                        sequence,
+                        cancelled : ! (can_build(gamemode) && can_reach(position, view, *pos))
+                    });
+                } else {
+                    // The client may have predicted a break.
+                    if (digging)
+                        && let Some(world) = world
+                    { resync_block(&mut conn, world, *pos); }
+                    let _ = conn.send_packet_play(BlockChangedAckS2CPlayPacket { sequence : sequence.into() });
+                }
+            },
+
+            Packet::Play(C2SPlayPackets::UseItemOn(UseItemOnC2SPlayPacket { hand, pos, face, sequence, .. })) => {
+                let sequence : i32 = (*sequence).into();
+                let Ok((mut conn, in_world, _, gamemode, inventory, position, view,)) = q_players.get_mut(*entity)
+                    else { continue; };
+                let Some(in_world) = in_world else {
+                    let _ = conn.send_packet_play(BlockChangedAckS2CPlayPacket { sequence : sequence.into() });
+                    continue;
+                };
+                let place_pos = offset(*pos, *face);
+                ew_place.write(BlockPlaceAttempt {
+                    player    : *entity,
+                    world     : in_world.0,
+                    pos       : place_pos,
+                    against   : *pos,
+                    face      : *face,
+                    hand      : *hand,
+                    block     : held_stack(inventory, *hand).and_then(|stack| block_of(&stack.item)),
+                    sequence,
+                    cancelled : ! (can_build(gamemode) && can_reach(position, view, *pos) && can_reach(position, view, place_pos))
+                });
+            },
+
+            _ => { }
+
+        }
+    }
+}
+
+
+pub(crate) fn apply_block_interactions(
+    mut q_players : Query<(&mut Connection, Option<&mut WorldOverlay>, &PlayerGamemode, &mut PlayerInventory,), (With<ConnStatePlay>,)>,
+    mut q_worlds  : Query<(&mut World,)>,
+    mut er_break  : EventReader<BlockBreakAttempt>,
+    mut er_place  : EventReader<BlockPlaceAttempt>
+) {
+    let mut acks = BTreeMap::<Entity, i32>::new();
+
+    for attempt in er_break.read() {
+        let Ok((mut conn, mut overlay, _, _,)) = q_players.get_mut(attempt.player)
+            else { continue; };
+        let Ok((mut world,)) = q_worlds.get_mut(attempt.world)
+            else { continue; };
+        let world = target_world_mut(&mut *world, overlay.as_deref_mut(), attempt.pos);
+        if (attempt.cancelled || world.caused_by(BlockChangeCause::Player(attempt.player), |world| world.set_block(attempt.pos, BLOCK_AIR)).is_err()) {
+            resync_block(&mut conn, world, attempt.pos);
+        }
+        acks.entry(attempt.player).and_modify(|s| *s = (*s).max(attempt.sequence)).or_insert(attempt.sequence);
+    }
+
+    for attempt in er_place.read() {
+        let Ok((mut conn, mut overlay, gamemode, mut inventory,)) = q_players.get_mut(attempt.player)
+            else { continue; };
+        let Ok((mut world,)) = q_worlds.get_mut(attempt.world)
+            else { continue; };
+        let world  = target_world_mut(&mut *world, overlay.as_deref_mut(), attempt.pos);
+        let placed = if (! attempt.cancelled)
+            && let Some(block) = attempt.block
+            && world.get_block_entry(attempt.pos) == Some(BLOCK_AIR)
+        { world.caused_by(BlockChangeCause::Player(attempt.player), |world| world.set_block(attempt.pos, block)).is_ok() }
+        else { false };
+        if (! placed) {
+            resync_block(&mut conn, world, attempt.pos);
+            resync_block(&mut conn, world, attempt.against);
+        } else if (gamemode.get() == Gamemode::Survival) {
+            let slot = held_slot(&inventory, attempt.hand);
+            if let Some(mut stack) = inventory.get(slot).cloned() {
+                stack.count = stack.count.saturating_sub(1);
+                inventory.set(slot, Some(stack));
+            }
+        }
+        acks.entry(attempt.player).and_modify(|s| *s = (*s).max(attempt.sequence)).or_insert(attempt.sequence);
+    }
+
+    // Acknowledge after the block updates, so the client keeps them.
+    for (player, sequence,) in acks {
+        if let Ok((mut conn, _, _, _,)) = q_players.get_mut(player) {
+            let _ = conn.send_packet_play(BlockChangedAckS2CPlayPacket { sequence : sequence.into() });
+        }
+    }
+}
+
+fn held_slot(inventory : &PlayerInventory, hand : Hand) -> usize {
+    match (hand) {
+        Hand::MainHand => HOTBAR_START + (inventory.selected_hotbar() as usize),
+        Hand::OffHand  => OFFHAND_SLOT
+    }
+}
+
+fn held_stack(inventory : &PlayerInventory, hand : Hand) -> Option<&ItemStack> {
+    inventory.get(held_slot(inventory, hand))
+}
+
+/// Returns the default state of the block which `item` places.
+fn block_of(item : &Identifier) -> Option<RegEntry<BlockState>> {
+    let item  = item.to_string();
+    let block = ITEM_BLOCKS.iter().find(|(other, _,)| *other == item).map_or(item.as_str(), |(_, block,)| block);
+    state_entry(&BlockState::default_for(&Identifier::from(block))?).filter(|&block| block != BLOCK_AIR)
+}
+
+fn can_build(gamemode : &PlayerGamemode) -> bool {
+    matches!(gamemode.get(), Gamemode::Survival | Gamemode::Creative)
+}
+
+/// Returns `true` if `pos` is within reach of the player, in a chunk the player has loaded.
+fn can_reach(position : &PlayerPosition, view : &ChunkView, pos : BlockPos) -> bool {
+    position.eye_distance_sq(pos) <= (MAX_REACH * MAX_REACH)
+        && view.has_loaded(Vec2::new(pos.x.div_floor(16), pos.z.div_floor(16)))
+}
+
+/// Returns the player's overlay if it overrides the chunk containing `pos`, or else the shared world.
+fn target_world_mut<'l>(world : &'l mut World, overlay : Option<&'l mut WorldOverlay>, pos : BlockPos) -> &'l mut World {
+    let cpos = Vec2::new(pos.x.div_floor(16), pos.z.div_floor(16));
+    match (overlay.and_then(|overlay| overlay.world.as_mut()).filter(|overlay| overlay.chunks.contains_key(&cpos))) {
+        Some(overlay) => overlay,
+        None          => world
+    }
+}
+
+fn target_world<'l>(world : &'l World, overlay : Option<&'l WorldOverlay>, pos : BlockPos) -> &'l World {
+    let cpos = Vec2::new(pos.x.div_floor(16), pos.z.div_floor(16));
+    match (overlay.and_then(|overlay| overlay.world.as_ref()).filter(|overlay| overlay.chunks.contains_key(&cpos))) {
+        Some(overlay) => overlay,
+        None          => world
+    }
+}
+
+/// Sends the server's version of the block at `pos`, undoing the client's prediction.
+fn resync_block(conn : &mut Connection, world : &World, pos : BlockPos) {
+    if let Some(block) = world.get_block_entry(pos) {
+        let _ = conn.send_packet_play(BlockUpdateS2CPlayPacket { pos, block });
+    }
+}
+
+fn offset(pos : BlockPos, face : Direction) -> BlockPos {
+    let (dx, dy, dz,) = match (face) {
+        Direction::Down  => ( 0, -1,  0,),
+        Direction::Up    => ( 0,  1,  0,),
+        Direction::North => ( 0,  0, -1,),
+        Direction::South => ( 0,  0,  1,),
+        Direction::West  => (-1,  0,  0,),
+        Direction::East  => ( 1,  0,  0,)
+    };
+    BlockPos { x : pos.x + dx, y : pos.y + dy, z : pos.z + dz }
+}
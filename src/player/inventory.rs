@@ -0,0 +1,399 @@
+use crate::Registries;
+use crate::conn::Connection;
+use crate::conn::packet::{ PacketReadEvent, Packet };
+use crate::conn::play::ConnStatePlay;
+use crate::player::gamemode::PlayerGamemode;
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Identifier, Item, ItemComponent, Slot };
+use protocol::packet::c2s::play::{
+    C2SPlayPackets,
+    ContainerClickC2SPlayPacket,
+    ContainerCloseC2SPlayPacket,
+    PlayerActionC2SPlayPacket,
+    PlayerActionStatus,
+    SetCarriedItemC2SPlayPacket,
+    SetCreativeModeSlotC2SPlayPacket,
+    ClickMode
+};
+use protocol::packet::s2c::play::{
+    ContainerSetContentS2CPlayPacket,
+    ContainerSetSlotS2CPlayPacket,
+    SetHeldSlotS2CPlayPacket,
+    Gamemode
+};
+use protocol::registry::Registry;
+
+
+/// The number of slots in a player's inventory.
+pub const INVENTORY_SIZE : usize = 46;
+
+/// The slot index of the first hotbar slot.
+pub const HOTBAR_START : usize = 36;
+
+/// The slot index of the offhand slot.
+pub const OFFHAND_SLOT : usize = 45;
+
+/// The slot clicked when a player clicks outside of an inventory or menu.
+const OUTSIDE_SLOT : i16 = -999;
+
+/// The number of changed slots above which the whole inventory is resent.
+const FULL_RESEND_SLOTS : usize = 8;
+
+
+/// A stack of items.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemStack {
+    pub item       : Identifier,
+    pub count      : u8,
+    /// Data components added to or removed from the item's defaults.
+    pub components : Vec<ItemComponent>
+}
+
+impl ItemStack {
+
+    /// Creates a stack of `count` items, or `None` if `count` is `0`.
+    pub fn new(item : Identifier, count : u8) -> Option<Self> {
+        (count > 0).then(|| Self { item, count, components : Vec::new() })
+    }
+
+    pub(crate) fn to_slot(stack : Option<&Self>, items : &Registry<Item>) -> Slot {
+        match (stack.and_then(|stack| Some((stack, items.get_entry(&stack.item)?,)))) {
+            Some((stack, entry,)) if (stack.count > 0) => Slot::Filled {
+                item       : entry,
+                count      : (stack.count as i32).into(),
+                components : stack.components.clone().into()
+            },
+            _ => Slot::Empty
+        }
+    }
+
+    pub(crate) fn from_slot(slot : &Slot, items : &Registry<Item>) -> Option<Self> {
+        match (slot) {
+            Slot::Filled { item, count, components } => Some(Self {
+                item       : items.get_key(*item)?.clone(),
+                count      : u8::try_from(i32::from(*count)).ok().filter(|&count| count > 0)?,
+                components : components.to_vec()
+            }),
+            Slot::Empty => None
+        }
+    }
+
+}
+
+
+/// The inventory of a player.
+///
+/// Slots are numbered as in vanilla: `0` is the crafting output, `1..=4` the
+///  crafting grid, `5..=8` the armour slots, `9..=35` the main inventory,
+///  `36..=44` the hotbar, and `45` the offhand. Changed slots are sent to the
+///  player at the end of the tick.
+///
+/// Players can move items around their own inventory. A click is only
+///  accepted if it keeps the same items, otherwise the whole inventory is
+///  sent back. Items which are dropped are removed.
+#[derive(Component)]
+pub struct PlayerInventory {
+    slots    : Box<[Option<ItemStack>; INVENTORY_SIZE]>,
+    carried  : Option<ItemStack>,
+    selected : Dirty<u8>,
+    dirty    : BTreeSet<u8>,
+    state_id : i32
+}
+
+impl Default for PlayerInventory {
+    fn default() -> Self { Self {
+        slots    : Box::new([const { None }; INVENTORY_SIZE]),
+        carried  : None,
+        selected : Dirty::new(0),
+        dirty    : BTreeSet::new(),
+        state_id : 0
+    } }
+}
+
+impl PlayerInventory {
+
+    #[inline]
+    pub fn get(&self, slot : usize) -> Option<&ItemStack> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    /// Sets the contents of `slot`. Slots out of range are ignored, and empty
+    ///  stacks are stored as `None`.
+    pub fn set(&mut self, slot : usize, stack : Option<ItemStack>) {
+        let stack = stack.filter(|stack| stack.count > 0);
+        if let Some(current) = self.slots.get_mut(slot)
+            && (*current != stack)
+        {
+            *current = stack;
+            self.dirty.insert(slot as u8);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for slot in 0..INVENTORY_SIZE {
+            self.set(slot, None);
+        }
+    }
+
+    /// Returns the selected hotbar slot, from `0` to `8`.
+    #[inline]
+    pub fn selected_hotbar(&self) -> u8 { *self.selected }
+
+    /// Selects a hotbar slot, from `0` to `8`.
+    pub fn select_hotbar(&mut self, hotbar : u8) {
+        if (hotbar < 9 && *self.selected != hotbar) {
+            *self.selected = hotbar;
+        }
+    }
+
+    /// Returns the item stack in the selected hotbar slot.
+    #[inline]
+    pub fn main_hand(&self) -> Option<&ItemStack> {
+        self.get(HOTBAR_START + (*self.selected as usize))
+    }
+
+    #[inline]
+    pub fn off_hand(&self) -> Option<&ItemStack> {
+        self.get(OFFHAND_SLOT)
+    }
+
+    /// Returns the item stack held on the cursor.
+    #[inline]
+    pub fn carried(&self) -> Option<&ItemStack> {
+        self.carried.as_ref()
+    }
+
+    /// Marks `slot` to be sent again, undoing any change the client predicted.
+    pub(crate) fn resync(&mut self, slot : usize) {
+        if (slot < INVENTORY_SIZE) {
+            self.dirty.insert(slot as u8);
+        }
+    }
+
+    /// Stores a change to `slot` which the client already predicted, without sending it back.
+    pub(crate) fn set_predicted(&mut self, slot : usize, stack : Option<ItemStack>) {
+        if let Some(current) = self.slots.get_mut(slot) {
+            *current = stack.filter(|stack| stack.count > 0);
+        }
+    }
+
+    /// Stores a cursor stack which the client already predicted.
+    pub(crate) fn set_carried_predicted(&mut self, stack : Option<ItemStack>) {
+        self.carried = stack.filter(|stack| stack.count > 0);
+    }
+
+    /// Marks every slot and the cursor to be sent again.
+    pub(crate) fn resync_all(&mut self) {
+        self.dirty.extend(0..(INVENTORY_SIZE as u8));
+    }
+
+    /// Puts the stack on the cursor into the first empty hotbar or main
+    ///  inventory slot. If there is no room, the stack is removed.
+    fn return_carried(&mut self) {
+        let Some(stack) = self.carried.take()
+            else { return; };
+        if let Some(slot) = (HOTBAR_START..OFFHAND_SLOT).chain(9..HOTBAR_START).find(|&slot| self.slots[slot].is_none()) {
+            self.set(slot, Some(stack));
+        }
+    }
+
+    fn next_state_id(&mut self) -> i32 {
+        self.state_id = self.state_id.wrapping_add(1) & 0x7FFF;
+        self.state_id
+    }
+
+}
+
+
+/// Decides which creative-mode inventory changes are accepted.
+///
+/// Changes by players who are not in creative mode are always rejected, and
+///  rejected slots are sent back to the player.
+#[derive(Resource, Clone, Copy)]
+pub enum CreativeInventoryPolicy {
+    /// Accept every change.
+    Allow,
+    /// Reject every change.
+    Deny,
+    /// Accept changes for which the function returns `true`.
+    Filter(fn(Entity, usize, Option<&ItemStack>) -> bool)
+}
+
+impl CreativeInventoryPolicy {
+
+    fn accepts(&self, player : Entity, slot : usize, stack : Option<&ItemStack>) -> bool {
+        match (self) {
+            Self::Allow          => true,
+            Self::Deny           => false,
+            Self::Filter(filter) => filter(player, slot, stack)
+        }
+    }
+
+}
+
+
+/// Returns `true` if a click in `slot` with `mode` drops items.
+pub(crate) fn click_drops_items(slot : i16, mode : ClickMode) -> bool {
+    (slot == OUTSIDE_SLOT) || matches!(mode, ClickMode::Throw)
+}
+
+/// Checks that a click only moved items around.
+///
+/// Every kind of item must be counted the same in `before` and `after`, except
+///  that clicks which `drop` items may have fewer items after.
+pub(crate) fn moves_items_only<'l>(
+    before : impl IntoIterator<Item = Option<&'l ItemStack>>,
+    after  : impl IntoIterator<Item = Option<&'l ItemStack>>,
+    drop   : bool
+) -> bool {
+    let mut counts = Vec::<(&ItemStack, i64,)>::new();
+    let     stacks = before.into_iter().map(|stack| (stack, 1,))
+        .chain(after.into_iter().map(|stack| (stack, -1,)));
+    for (stack, sign,) in stacks {
+        let Some(stack) = stack
+            else { continue; };
+        let count = sign * (stack.count as i64);
+        match (counts.iter_mut().find(|(other, _,)| other.item == stack.item && other.components == stack.components)) {
+            Some((_, total,)) => { *total += count; },
+            None              => { counts.push((stack, count,)); }
+        }
+    }
+    counts.iter().all(|(_, total,)| if (drop) { *total >= 0 } else { *total == 0 })
+}
+
+
+/// A player selected another hotbar slot.
+#[derive(Event)]
+#[non_exhaustive]
+pub struct HeldSlotChanged {
+    pub player : Entity,
+    pub old    : u8,
+    pub new    : u8
+}
+
+
+pub(crate) fn read_inventory_packets(
+    mut q_players : Query<(&mut PlayerInventory, &PlayerGamemode,), (With<ConnStatePlay>,)>,
+    mut er_packet : EventReader<PacketReadEvent>,
+    mut ew_held   : EventWriter<HeldSlotChanged>,
+        r_policy  : Res<CreativeInventoryPolicy>,
+        r_regs    : Res<Registries>
+) {
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        let Ok((mut inventory, gamemode,)) = q_players.get_mut(*entity)
+            else { continue; };
+        match (packet) {
+
+            Packet::Play(C2SPlayPackets::SetCarriedItem(SetCarriedItemC2SPlayPacket { slot })) => {
+                let Ok(new) = u8::try_from(*slot)
+                    else { continue; };
+                if (new >= 9) { continue; }
+                let old = *inventory.selected;
+                if (old != new) {
+                    // The client already knows.
+                    *inventory.selected = new;
+                    Dirty::take_dirty(&mut inventory.selected);
+                    ew_held.write(HeldSlotChanged { player : *entity, old, new });
+                }
+            },
+
+            Packet::Play(C2SPlayPackets::SetCreativeModeSlot(SetCreativeModeSlotC2SPlayPacket { slot, item })) => {
+                let Ok(slot) = usize::try_from(*slot)
+                    else { continue; };
+                if (slot >= INVENTORY_SIZE) { continue; }
+                let stack = ItemStack::from_slot(item, &r_regs.item);
+                if (gamemode.get() == Gamemode::Creative && r_policy.accepts(*entity, slot, stack.as_ref())) {
+                    // The client already knows.
+                    inventory.slots[slot] = stack;
+                } else {
+                    inventory.resync(slot);
+                }
+            },
+
+            Packet::Play(C2SPlayPackets::ContainerClick(ContainerClickC2SPlayPacket { window_id, slot, mode, changed_slots, carried_item, .. })) => {
+                // Other windows are menus.
+                if (i32::from(*window_id) != 0) { continue; }
+                let mut changed = BTreeMap::new();
+                let     valid   = changed_slots.iter().all(|(changed_slot, item,)| {
+                    usize::try_from(*changed_slot).is_ok_and(|changed_slot| (changed_slot < INVENTORY_SIZE)
+                        && changed.insert(changed_slot, ItemStack::from_slot(item, &r_regs.item)).is_none()
+                    )
+                });
+                let carried = ItemStack::from_slot(carried_item, &r_regs.item);
+                if (valid && moves_items_only(
+                    changed.keys().map(|&changed_slot| inventory.get(changed_slot)).chain([inventory.carried()]),
+                    changed.values().map(Option::as_ref).chain([carried.as_ref()]),
+                    click_drops_items(*slot, *mode)
+                )) {
+                    for (changed_slot, stack,) in changed {
+                        inventory.set_predicted(changed_slot, stack);
+                    }
+                    inventory.set_carried_predicted(carried);
+                } else {
+                    inventory.resync_all();
+                }
+            },
+
+            Packet::Play(C2SPlayPackets::ContainerClose(ContainerCloseC2SPlayPacket { .. })) => {
+                inventory.return_carried();
+            },
+
+            Packet::Play(C2SPlayPackets::PlayerAction(PlayerActionC2SPlayPacket { status, .. })) => {
+                let hand = HOTBAR_START + (*inventory.selected as usize);
+                match (status) {
+                    PlayerActionStatus::DropItem => {
+                        if let Some(mut stack) = inventory.get(hand).cloned() {
+                            stack.count = stack.count.saturating_sub(1);
+                            inventory.set(hand, Some(stack));
+                        }
+                    },
+                    PlayerActionStatus::DropItemStack => {
+                        inventory.set(hand, None);
+                    },
+                    PlayerActionStatus::SwapItemInHand => {
+                        inventory.slots.swap(hand, OFFHAND_SLOT);
+                        inventory.resync(hand);
+                        inventory.resync(OFFHAND_SLOT);
+                    },
+                    _ => { }
+                }
+            },
+
+            _ => { }
+
+        }
+    }
+}
+
+pub(crate) fn update_inventories(
+    mut q_players : Query<(&mut Connection, &mut PlayerInventory,), (With<ConnStatePlay>,)>,
+        r_regs    : Res<Registries>
+) {
+    for (mut conn, mut inventory,) in &mut q_players {
+        if (Dirty::take_dirty(&mut inventory.selected)) {
+            let _ = conn.send_packet_play(SetHeldSlotS2CPlayPacket {
+                slot : (*inventory.selected as i32).into()
+            });
+        }
+        if (inventory.dirty.is_empty()) { continue; }
+        let dirty    = mem::take(&mut inventory.dirty);
+        let state_id = inventory.next_state_id();
+        if (dirty.len() > FULL_RESEND_SLOTS) {
+            let _ = conn.send_packet_play(ContainerSetContentS2CPlayPacket {
+                window_id : 0.into(),
+                state_id  : state_id.into(),
+                slots     : inventory.slots.iter().map(|stack| ItemStack::to_slot(stack.as_ref(), &r_regs.item)).collect::<Vec<_>>().into(),
+                carried   : ItemStack::to_slot(inventory.carried(), &r_regs.item)
+            });
+        } else {
+            for slot in dirty {
+                let _ = conn.send_packet_play(ContainerSetSlotS2CPlayPacket {
+                    window_id : 0.into(),
+                    state_id  : state_id.into(),
+                    slot      : slot as i16,
+                    item      : ItemStack::to_slot(inventory.get(slot as usize), &r_regs.item)
+                });
+            }
+        }
+    }
+}
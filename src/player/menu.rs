@@ -0,0 +1,304 @@
+use crate::Registries;
+use crate::conn::Connection;
+use crate::conn::packet::{ PacketReadEvent, Packet };
+use crate::conn::play::ConnStatePlay;
+use crate::player::inventory::{ PlayerInventory, ItemStack, OFFHAND_SLOT, click_drops_items, moves_items_only };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Identifier, Text };
+use protocol::packet::c2s::play::{
+    C2SPlayPackets,
+    ContainerClickC2SPlayPacket,
+    ContainerCloseC2SPlayPacket,
+    ClickMode
+};
+use protocol::packet::s2c::play::{
+    OpenScreenS2CPlayPacket,
+    ContainerSetContentS2CPlayPacket,
+    ContainerSetSlotS2CPlayPacket,
+    ContainerCloseS2CPlayPacket
+};
+
+
+/// The first and last player inventory slots shown below a menu.
+const MENU_INVENTORY_SLOTS : (usize, usize,) = (9, 44,);
+
+
+/// A container menu, such as a chest GUI.
+///
+/// Clicks are sent as [`MenuClicked`] events. Menus are read-only unless made
+///  [`editable`](Menu::editable), and whatever the client predicted is undone.
+#[derive(Clone)]
+pub struct Menu {
+    kind      : Identifier,
+    title     : Text,
+    slots     : Vec<Option<ItemStack>>,
+    read_only : bool
+}
+
+impl Menu {
+
+    /// Creates an empty menu of type `kind`, with `size` slots.
+    ///
+    /// `size` must match the menu type, for example `27` for `minecraft:generic_9x3`.
+    pub fn new(kind : Identifier, size : usize, title : Text) -> Self { Self {
+        kind,
+        title,
+        slots     : vec![None; size],
+        read_only : true
+    } }
+
+    /// Creates an empty chest menu with 1 to 6 rows.
+    pub fn chest(rows : u8, title : Text) -> Self {
+        let rows = rows.clamp(1, 6);
+        Self::new(Identifier::from(format!("minecraft:generic_9x{rows}").as_str()), (rows as usize) * 9, title)
+    }
+
+    /// Lets players move items in and out of this menu.
+    ///
+    /// A click is only accepted if it keeps the same items between the menu,
+    ///  the player's inventory and their cursor. Other clicks are undone.
+    pub fn editable(mut self) -> Self {
+        self.read_only = false;
+        self
+    }
+
+    /// Sets the contents of `slot`. Slots out of range are ignored.
+    pub fn with(mut self, slot : usize, stack : Option<ItemStack>) -> Self {
+        self.set(slot, stack);
+        self
+    }
+
+    #[inline]
+    pub fn get(&self, slot : usize) -> Option<&ItemStack> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    pub fn set(&mut self, slot : usize, stack : Option<ItemStack>) {
+        if let Some(current) = self.slots.get_mut(slot) {
+            *current = stack;
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize { self.slots.len() }
+
+}
+
+
+/// The menu a player has open.
+#[derive(Component, Default)]
+pub struct PlayerMenu {
+    open           : Option<(u8, Menu,)>,
+    last_window_id : u8,
+    state_id       : i32
+}
+
+impl PlayerMenu {
+
+    #[inline]
+    pub fn menu(&self) -> Option<&Menu> {
+        self.open.as_ref().map(|(_, menu,)| menu)
+    }
+
+    #[inline]
+    pub fn is_open(&self) -> bool { self.open.is_some() }
+
+}
+
+
+#[derive(Event)]
+pub struct PlayerMenuActionEvent {
+    pub entity : Entity,
+    pub action : PlayerMenuAction
+}
+
+pub enum PlayerMenuAction {
+
+    /// Opens a menu, closing any menu which is already open.
+    Open {
+        menu : Menu
+    },
+
+    /// Sets a slot of the open menu.
+    SetSlot {
+        slot  : usize,
+        stack : Option<ItemStack>
+    },
+
+    /// Closes the open menu.
+    Close
+
+}
+
+
+/// A player clicked a slot of their open menu.
+///
+/// `slot` counts menu slots first, then the player's inventory. It is `-999`
+///  for clicks outside of the menu.
+#[derive(Event)]
+#[non_exhaustive]
+pub struct MenuClicked {
+    pub player : Entity,
+    pub slot   : i16,
+    pub button : i8,
+    pub mode   : ClickMode
+}
+
+/// A player closed their open menu.
+#[derive(Event)]
+#[non_exhaustive]
+pub struct MenuClosed {
+    pub player : Entity
+}
+
+
+pub(crate) fn handle_actions(
+    mut q_players : Query<(&mut Connection, &mut PlayerMenu, &PlayerInventory,), (With<ConnStatePlay>,)>,
+    mut er_action : EventReader<PlayerMenuActionEvent>,
+        r_regs    : Res<Registries>
+) {
+    for PlayerMenuActionEvent { entity, action } in er_action.read() {
+        let Ok((mut conn, mut menus, inventory,)) = q_players.get_mut(*entity)
+            else { continue; };
+        match (action) {
+
+            PlayerMenuAction::Open { menu } => {
+                let Some(kind) = r_regs.menu_type.get_entry(&menu.kind) else {
+                    warn!("Not opening menu for peer {}: unknown menu type {}", conn.peer_addr(), menu.kind);
+                    continue;
+                };
+                // Window 0 is the player's inventory.
+                let window_id = (menus.last_window_id % 100) + 1;
+                menus.last_window_id = window_id;
+                let _ = conn.send_packet_play(OpenScreenS2CPlayPacket {
+                    window_id : (window_id as i32).into(),
+                    kind,
+                    title     : menu.title.to_nbt()
+                });
+                menus.open = Some((window_id, menu.clone(),));
+                send_contents(&mut conn, &mut menus, inventory, &r_regs);
+            },
+
+            PlayerMenuAction::SetSlot { slot, stack } => {
+                let Some((window_id, menu,)) = &mut menus.open
+                    else { continue; };
+                if (*slot >= menu.size()) { continue; }
+                menu.set(*slot, stack.clone());
+                let window_id = *window_id;
+                menus.state_id = menus.state_id.wrapping_add(1) & 0x7FFF;
+                let _ = conn.send_packet_play(ContainerSetSlotS2CPlayPacket {
+                    window_id : (window_id as i32).into(),
+                    state_id  : menus.state_id.into(),
+                    slot      : *slot as i16,
+                    item      : ItemStack::to_slot(stack.as_ref(), &r_regs.item)
+                });
+            },
+
+            PlayerMenuAction::Close => {
+                if let Some((window_id, _,)) = menus.open.take() {
+                    let _ = conn.send_packet_play(ContainerCloseS2CPlayPacket {
+                        window_id : (window_id as i32).into()
+                    });
+                }
+            }
+
+        }
+    }
+}
+
+pub(crate) fn read_menu_packets(
+    mut q_players : Query<(&mut Connection, &mut PlayerMenu, &mut PlayerInventory,), (With<ConnStatePlay>,)>,
+    mut er_packet : EventReader<PacketReadEvent>,
+    mut ew_click  : EventWriter<MenuClicked>,
+    mut ew_close  : EventWriter<MenuClosed>,
+        r_regs    : Res<Registries>
+) {
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        let Ok((mut conn, mut menus, mut inventory,)) = q_players.get_mut(*entity)
+            else { continue; };
+        match (packet) {
+
+            Packet::Play(C2SPlayPackets::ContainerClick(ContainerClickC2SPlayPacket { window_id, slot, button, mode, changed_slots, carried_item, .. })) => {
+                let Some((open_id, menu,)) = &mut menus.open
+                    else { continue; };
+                if (i32::from(*window_id) != (*open_id as i32)) { continue; }
+                let accepted = (! menu.read_only) && {
+                    let mut changed = BTreeMap::new();
+                    changed_slots.iter().all(|(changed_slot, item,)| {
+                        usize::try_from(*changed_slot).is_ok_and(|changed_slot|
+                            changed.insert(changed_slot, ItemStack::from_slot(item, &r_regs.item)).is_none()
+                        )
+                    }) && apply_click(menu, &mut inventory, changed, ItemStack::from_slot(carried_item, &r_regs.item), click_drops_items(*slot, *mode))
+                };
+                ew_click.write(MenuClicked {
+                    player : *entity,
+                    slot   : *slot,
+                    button : *button,
+                    mode   : *mode
+                });
+                if (! accepted) {
+                    // Undo whatever the client predicted.
+                    send_contents(&mut conn, &mut menus, &inventory, &r_regs);
+                    // The offhand slot is not shown in the menu, but can be swapped into it.
+                    inventory.resync(OFFHAND_SLOT);
+                }
+            },
+
+            Packet::Play(C2SPlayPackets::ContainerClose(ContainerCloseC2SPlayPacket { window_id })) => {
+                if let Some((open_id, _,)) = &menus.open
+                    && (i32::from(*window_id) == (*open_id as i32))
+                {
+                    menus.open = None;
+                    ew_close.write(MenuClosed { player : *entity });
+                }
+            },
+
+            _ => { }
+
+        }
+    }
+}
+
+/// Stores the changes the client predicted for a click in an editable menu,
+///  if they only move items around. `changed` is keyed by window slot.
+fn apply_click(
+    menu      : &mut Menu,
+    inventory : &mut PlayerInventory,
+    changed   : BTreeMap<usize, Option<ItemStack>>,
+    carried   : Option<ItemStack>,
+    drop      : bool
+) -> bool {
+    let size           = menu.size();
+    let window_size    = size + (MENU_INVENTORY_SLOTS.1 - MENU_INVENTORY_SLOTS.0) + 1;
+    let inventory_slot = |window_slot : usize| MENU_INVENTORY_SLOTS.0 + (window_slot - size);
+    if (changed.keys().any(|&window_slot| window_slot >= window_size)) { return false; }
+    let get = |window_slot : usize| if (window_slot < size) { menu.get(window_slot) } else { inventory.get(inventory_slot(window_slot)) };
+    if (! moves_items_only(
+        changed.keys().map(|&window_slot| get(window_slot)).chain([inventory.carried()]),
+        changed.values().map(Option::as_ref).chain([carried.as_ref()]),
+        drop
+    )) { return false; }
+    for (window_slot, stack,) in changed {
+        if (window_slot < size) { menu.set(window_slot, stack); }
+        else { inventory.set_predicted(inventory_slot(window_slot), stack); }
+    }
+    inventory.set_carried_predicted(carried);
+    true
+}
+
+/// Sends every slot of the open menu, the player's inventory below it, and the cursor.
+fn send_contents(conn : &mut Connection, menus : &mut PlayerMenu, inventory : &PlayerInventory, regs : &Registries) {
+    let Some((window_id, menu,)) = &menus.open
+        else { return; };
+    let window_id = *window_id;
+    let slots = menu.slots.iter().map(|stack| ItemStack::to_slot(stack.as_ref(), &regs.item))
+        .chain((MENU_INVENTORY_SLOTS.0..=MENU_INVENTORY_SLOTS.1).map(|slot| ItemStack::to_slot(inventory.get(slot), &regs.item)))
+        .collect::<Vec<_>>();
+    menus.state_id = menus.state_id.wrapping_add(1) & 0x7FFF;
+    let _ = conn.send_packet_play(ContainerSetContentS2CPlayPacket {
+        window_id : (window_id as i32).into(),
+        state_id  : menus.state_id.into(),
+        slots     : slots.into(),
+        carried   : ItemStack::to_slot(inventory.carried(), &regs.item)
+    });
+}
@@ -5,6 +5,28 @@ use protocol::mojang::auth_verify::MojAuthProperty;
 
 pub mod comms;
 
+pub mod gamemode;
+
+pub mod abilities;
+
+pub mod inventory;
+
+pub mod menu;
+
+pub mod chat;
+
+pub mod scoreboard;
+
+pub mod bossbar;
+
+pub mod command;
+
+pub mod permission;
+
+pub mod movement;
+
+pub mod interact;
+
 
 #[derive(Component)]
 pub struct Player {
@@ -0,0 +1,59 @@
+use crate::conn::packet::{ PacketReadEvent, Packet };
+use flywheelmc_common::prelude::*;
+use protocol::value::BlockPos;
+use protocol::packet::c2s::play::{
+    C2SPlayPackets,
+    MovePlayerPosC2SPlayPacket,
+    MovePlayerPosRotC2SPlayPacket
+};
+
+
+/// The height of a standing player's eyes above their feet.
+const EYE_HEIGHT : f64 = 1.62;
+
+
+/// The position of a player's feet, as last reported by their client.
+#[derive(Component, Clone, Copy)]
+pub struct PlayerPosition {
+    x : f64,
+    y : f64,
+    z : f64
+}
+
+impl PlayerPosition {
+
+    #[inline]
+    pub(crate) fn new(x : f64, y : f64, z : f64) -> Self { Self { x, y, z } }
+
+    #[inline]
+    pub fn x(&self) -> f64 { self.x }
+
+    #[inline]
+    pub fn y(&self) -> f64 { self.y }
+
+    #[inline]
+    pub fn z(&self) -> f64 { self.z }
+
+    /// Returns the squared distance from the player's eyes to the centre of the block at `pos`.
+    pub fn eye_distance_sq(&self, pos : BlockPos) -> f64 {
+        let dx = ((pos.x as f64) + 0.5) - self.x;
+        let dy = ((pos.y as f64) + 0.5) - (self.y + EYE_HEIGHT);
+        let dz = ((pos.z as f64) + 0.5) - self.z;
+        (dx * dx) + (dy * dy) + (dz * dz)
+    }
+
+}
+
+
+pub(crate) fn read_movement(
+    mut q_players : Query<(&mut PlayerPosition,)>,
+    mut er_packet : EventReader<PacketReadEvent>
+) {
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        if let Ok((mut position,)) = q_players.get_mut(*entity)
+            && let    Packet::Play(C2SPlayPackets::MovePlayerPos(MovePlayerPosC2SPlayPacket { x, y, z, .. }))
+                    | Packet::Play(C2SPlayPackets::MovePlayerPosRot(MovePlayerPosRotC2SPlayPacket { x, y, z, .. })) = packet
+            && x.is_finite() && y.is_finite() && z.is_finite()
+        { *position = PlayerPosition::new(*x, *y, *z); }
+    }
+}
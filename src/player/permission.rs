@@ -0,0 +1,305 @@
+use crate::conn::Connection;
+use crate::conn::play::ConnStatePlay;
+use crate::player::command::RefreshCommands;
+use flywheelmc_common::prelude::*;
+use protocol::packet::s2c::play::EntityEventS2CPlayPacket;
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+
+/// The permission which lets a player join when the server is full.
+pub const BYPASS_FULL : &str = "server.bypass_full";
+
+/// The group inherited by players who have no entry in [`Permissions`].
+pub const DEFAULT_GROUP : &str = "default";
+
+/// The entity event which sets the operator level of the player to `0`.
+const OP_LEVEL_EVENT_BASE : u8 = 24;
+
+/// The highest operator level.
+pub const MAX_OP_LEVEL : u8 = 4;
+
+
+/// Permission nodes, inherited groups, and an operator level.
+///
+/// Nodes are dot-separated. A node ending in `*` matches every node below it,
+///  and `*` alone matches everything. The most specific node wins, so
+///  `chat.*` can be granted while `chat.colour` is denied.
+#[derive(Clone, Debug, Default)]
+pub struct PermissionSet {
+    nodes    : BTreeMap<String, bool>,
+    groups   : Vec<String>,
+    op_level : Option<u8>
+}
+
+impl PermissionSet {
+
+    pub fn grant(mut self, node : impl Into<String>) -> Self {
+        self.set(node, true);
+        self
+    }
+
+    pub fn deny(mut self, node : impl Into<String>) -> Self {
+        self.set(node, false);
+        self
+    }
+
+    /// Inherits the permissions of `group`. Later groups override earlier ones.
+    pub fn inherit(mut self, group : impl Into<String>) -> Self {
+        self.add_group(group);
+        self
+    }
+
+    pub fn op_level(mut self, level : u8) -> Self {
+        self.set_op_level(level);
+        self
+    }
+
+    #[inline]
+    pub fn set(&mut self, node : impl Into<String>, value : bool) {
+        self.nodes.insert(node.into(), value);
+    }
+
+    #[inline]
+    pub fn unset(&mut self, node : &str) {
+        self.nodes.remove(node);
+    }
+
+    /// Inherits the permissions of `group`, after every group already inherited.
+    pub fn add_group(&mut self, group : impl Into<String>) {
+        let group = group.into();
+        self.groups.retain(|inherited| *inherited != group);
+        self.groups.push(group);
+    }
+
+    pub fn remove_group(&mut self, group : &str) {
+        self.groups.retain(|inherited| inherited != group);
+    }
+
+    #[inline]
+    pub fn groups(&self) -> &[String] { &self.groups }
+
+    /// Sets the operator level, clamped to [`MAX_OP_LEVEL`].
+    #[inline]
+    pub fn set_op_level(&mut self, level : u8) {
+        self.op_level = Some(level.min(MAX_OP_LEVEL));
+    }
+
+    /// Removes the operator level, leaving it to inherited groups.
+    #[inline]
+    pub fn unset_op_level(&mut self) {
+        self.op_level = None;
+    }
+
+    /// Parses the entries of one line of a permissions file.
+    fn parse_entries(&mut self, entries : &str) -> Result<(), String> {
+        for entry in entries.split(|ch : char| ch == ',' || ch.is_whitespace()).filter(|entry| ! entry.is_empty()) {
+            if let Some(group) = entry.strip_prefix('@') {
+                self.add_group(group);
+            } else if let Some(level) = entry.strip_prefix("op:") {
+                let level = level.parse::<u8>().map_err(|_| format!("invalid operator level {level:?}"))?;
+                self.op_level = Some(level.min(MAX_OP_LEVEL));
+            } else if let Some(node) = entry.strip_prefix('-') {
+                self.nodes.insert(node.to_string(), false);
+            } else {
+                self.nodes.insert(entry.to_string(), true);
+            }
+        }
+        Ok(())
+    }
+
+}
+
+
+/// Permission groups, and the permissions of players by UUID.
+///
+/// Loaded from the file set on [`FlywheelMcPlayersPlugin`](crate::FlywheelMcPlayersPlugin)
+///  on startup. Each line of the file assigns entries to a group or a player:
+///
+/// ```text
+/// # Comments start with a hash.
+/// group.default = chat.*, commands.help
+/// group.admin   = @default, *, op:4
+/// 069a79f4-44e9-4726-a5be-fca90e38aaf5 = @admin, -server.stop
+/// ```
+///
+/// Entries starting with `@` inherit a group, entries starting with `-` deny a
+///  node, and `op:` sets the operator level. Players without an entry inherit
+///  the [`DEFAULT_GROUP`].
+#[derive(Resource, Default)]
+pub struct Permissions {
+    groups  : BTreeMap<String, PermissionSet>,
+    players : BTreeMap<Uuid, PermissionSet>
+}
+
+impl Permissions {
+
+    pub fn load(path : &Path) -> io::Result<Self> {
+        let mut permissions = Self::default();
+        for (i, line,) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _,)| line).trim();
+            if (line.is_empty()) { continue; }
+            let invalid = |reason : String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {reason}", i + 1));
+            let Some((key, entries,)) = line.split_once('=')
+                else { return Err(invalid("expected `key = entries`".to_string())); };
+            let key = key.trim();
+            let set = if let Some(group) = key.strip_prefix("group.") {
+                permissions.groups.entry(group.to_string()).or_default()
+            } else {
+                let uuid = key.parse::<Uuid>().map_err(|_| invalid(format!("invalid UUID {key:?}")))?;
+                permissions.players.entry(uuid).or_default()
+            };
+            set.parse_entries(entries).map_err(invalid)?;
+        }
+        Ok(permissions)
+    }
+
+    #[inline]
+    pub fn group(&self, name : &str) -> Option<&PermissionSet> { self.groups.get(name) }
+
+    /// Sets the permissions of a group, updating every player in it.
+    #[inline]
+    pub fn set_group(&mut self, name : impl Into<String>, set : PermissionSet) {
+        self.groups.insert(name.into(), set);
+    }
+
+    #[inline]
+    pub fn player(&self, uuid : Uuid) -> Option<&PermissionSet> { self.players.get(&uuid) }
+
+    /// Returns the permissions the player with `uuid` joins with, inheriting the
+    ///  [`DEFAULT_GROUP`] if they have no entry.
+    pub fn player_or_default(&self, uuid : Uuid) -> PermissionSet {
+        self.players.get(&uuid).cloned().unwrap_or_else(|| PermissionSet::default().inherit(DEFAULT_GROUP))
+    }
+
+    /// Sets the permissions a player joins with.
+    #[inline]
+    pub fn set_player(&mut self, uuid : Uuid, set : PermissionSet) {
+        self.players.insert(uuid, set);
+    }
+
+    /// Returns `true` if the player with `uuid` is granted `node` in the loaded permissions.
+    pub fn player_has(&self, uuid : Uuid, node : &str) -> bool {
+        lookup(&self.resolve(&self.player_or_default(uuid)).0, node)
+    }
+
+    /// Flattens `set` and every group it inherits, returning its nodes and operator level.
+    fn resolve(&self, set : &PermissionSet) -> (BTreeMap<String, bool>, u8,) {
+        let mut nodes    = BTreeMap::new();
+        let mut op_level = 0;
+        let mut visited  = BTreeSet::new();
+        self.resolve_into(set, &mut nodes, &mut op_level, &mut visited);
+        (nodes, op_level,)
+    }
+
+    fn resolve_into<'l>(&'l self, set : &'l PermissionSet, nodes : &mut BTreeMap<String, bool>, op_level : &mut u8, visited : &mut BTreeSet<&'l str>) {
+        for group in &set.groups {
+            // Groups which inherit each other are only applied once.
+            if (! visited.insert(group.as_str())) { continue; }
+            match (self.groups.get(group)) {
+                Some(inherited) => self.resolve_into(inherited, nodes, op_level, visited),
+                // Servers need not define a default group.
+                None if (group == DEFAULT_GROUP) => { },
+                None => warn!("Unknown permission group {group:?}")
+            }
+        }
+        nodes.extend(set.nodes.iter().map(|(node, &value,)| (node.clone(), value,)));
+        if let Some(level) = set.op_level {
+            *op_level = level;
+        }
+    }
+
+}
+
+
+/// Finds the most specific entry in `nodes` matching `node`.
+fn lookup(nodes : &BTreeMap<String, bool>, node : &str) -> bool {
+    if let Some(&value) = nodes.get(node) { return value; }
+    let mut prefix = node;
+    while let Some((parent, _,)) = prefix.rsplit_once('.') {
+        if let Some(&value) = nodes.get(&format!("{parent}.*")) { return value; }
+        prefix = parent;
+    }
+    nodes.get("*").copied().unwrap_or(false)
+}
+
+
+/// The permissions of a player.
+///
+/// Players join with their entry in [`Permissions`]. Changes, including to
+///  inherited groups, are applied at the end of the tick, and the player is sent
+///  their operator level and command tree again.
+#[derive(Component)]
+pub struct PlayerPermissions {
+    set      : PermissionSet,
+    resolved : BTreeMap<String, bool>,
+    op_level : Dirty<u8>
+}
+
+impl PlayerPermissions {
+
+    pub(crate) fn new(set : PermissionSet) -> Self { Self {
+        set,
+        resolved : BTreeMap::new(),
+        op_level : Dirty::new_dirty(0)
+    } }
+
+    #[inline]
+    pub fn has(&self, node : &str) -> bool { lookup(&self.resolved, node) }
+
+    /// Returns the operator level, from `0` to [`MAX_OP_LEVEL`].
+    #[inline]
+    pub fn op_level(&self) -> u8 { *self.op_level }
+
+    #[inline]
+    pub fn get(&self) -> &PermissionSet { &self.set }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut PermissionSet { &mut self.set }
+
+}
+
+
+pub(crate) fn update_permissions(
+    mut q_players  : Query<(Entity, &mut Connection, &mut PlayerPermissions, Ref<ConnStatePlay>,)>,
+    mut ew_refresh : EventWriter<RefreshCommands>,
+        r_perms    : Res<Permissions>
+) {
+    for (entity, mut conn, mut perms, state,) in &mut q_players {
+        if (! (r_perms.is_changed() || perms.is_changed() || state.is_added())) { continue; }
+        // Resolving is not a change of its own.
+        let perms = perms.bypass_change_detection();
+        let (resolved, op_level,) = r_perms.resolve(&perms.set);
+        if (*perms.op_level != op_level) {
+            *perms.op_level = op_level;
+        }
+        if (perms.resolved != resolved) {
+            perms.resolved = resolved;
+            ew_refresh.write(RefreshCommands { entity });
+        }
+        if (Dirty::take_dirty(&mut perms.op_level)) {
+            trace!("Updating operator level of peer {} to {}", conn.peer_addr(), *perms.op_level);
+            let _ = conn.send_packet_play(EntityEventS2CPlayPacket {
+                entity : 1,
+                event  : OP_LEVEL_EVENT_BASE + *perms.op_level
+            });
+            ew_refresh.write(RefreshCommands { entity });
+        }
+    }
+}
+
+pub(crate) fn load_permissions(
+    mut r_perms : ResMut<Permissions>,
+        r_file  : Res<PermissionsFile>
+) {
+    let Some(path) = &r_file.0
+        else { return; };
+    match (Permissions::load(path)) {
+        Ok(permissions) => { *r_perms = permissions; },
+        Err(err)        => { error!("Failed to load permissions from {}: {err}", path.display()); }
+    }
+}
+
+
+#[derive(Resource)]
+pub(crate) struct PermissionsFile(pub(crate) Option<PathBuf>);
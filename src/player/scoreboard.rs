@@ -0,0 +1,327 @@
+use crate::conn::Connection;
+use crate::conn::play::ConnStatePlay;
+use crate::player::PlayerJoined;
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Text, TextColour };
+use protocol::packet::s2c::play::{
+    SetObjectiveS2CPlayPacket,
+    SetDisplayObjectiveS2CPlayPacket,
+    SetScoreS2CPlayPacket,
+    ResetScoreS2CPlayPacket,
+    SetPlayerTeamS2CPlayPacket,
+    ObjectiveAction,
+    ObjectiveRender,
+    DisplaySlot,
+    NumberFormat,
+    TeamAction,
+    TeamInfo
+};
+pub use protocol::packet::s2c::play::{ NameTagVisibility, CollisionRule };
+
+
+/// The most lines a sidebar can show.
+pub const MAX_SIDEBAR_LINES : usize = 15;
+
+/// The name of the objective used for sidebars.
+const SIDEBAR_OBJECTIVE : &str = "sidebar";
+
+
+/// The sidebar of a player.
+///
+/// Only lines which changed are sent, so updating a line does not redraw the
+///  whole sidebar. The sidebar is hidden until it has a title or a line.
+#[derive(Component, Default)]
+pub struct PlayerSidebar {
+    title      : Dirty<Option<Text>>,
+    lines      : Vec<Text>,
+    sent_lines : Vec<Text>,
+    shown      : bool
+}
+
+impl PlayerSidebar {
+
+    #[inline]
+    pub fn title(&self) -> Option<&Text> { (*self.title).as_ref() }
+
+    #[inline]
+    pub fn set_title(&mut self, title : Text) {
+        *self.title = Some(title);
+    }
+
+    #[inline]
+    pub fn lines(&self) -> &[Text] { &self.lines }
+
+    /// Sets the line at `index`, adding empty lines before it if needed. Lines past [`MAX_SIDEBAR_LINES`] are ignored.
+    pub fn set_line(&mut self, index : usize, line : Text) {
+        if (index >= MAX_SIDEBAR_LINES) { return; }
+        if (index >= self.lines.len()) {
+            self.lines.resize_with(index + 1, Text::new);
+        }
+        self.lines[index] = line;
+    }
+
+    /// Replaces every line. Lines past [`MAX_SIDEBAR_LINES`] are ignored.
+    pub fn set_lines(&mut self, lines : impl IntoIterator<Item = Text>) {
+        self.lines = lines.into_iter().take(MAX_SIDEBAR_LINES).collect();
+    }
+
+    /// Removes the title and every line, hiding the sidebar.
+    pub fn clear(&mut self) {
+        *self.title = None;
+        self.lines.clear();
+    }
+
+}
+
+
+/// Settings of a [`Team`].
+#[derive(Clone)]
+pub struct TeamSettings {
+    pub display_name        : Text,
+    pub prefix              : Text,
+    pub suffix              : Text,
+    /// The colour of member names.
+    pub colour              : Option<TextColour>,
+    pub name_tag_visibility : NameTagVisibility,
+    pub collision_rule      : CollisionRule,
+    pub friendly_fire       : bool,
+    /// Lets members see invisible members.
+    pub see_invisible       : bool
+}
+
+impl Default for TeamSettings {
+    fn default() -> Self { Self {
+        display_name        : Text::new(),
+        prefix              : Text::new(),
+        suffix              : Text::new(),
+        colour              : None,
+        name_tag_visibility : NameTagVisibility::Always,
+        collision_rule      : CollisionRule::Always,
+        friendly_fire       : true,
+        see_invisible       : false
+    } }
+}
+
+impl TeamSettings {
+
+    fn to_info(&self) -> TeamInfo {
+        TeamInfo {
+            display_name        : self.display_name.to_nbt(),
+            friendly_fire       : self.friendly_fire,
+            see_invisible       : self.see_invisible,
+            name_tag_visibility : self.name_tag_visibility,
+            collision_rule      : self.collision_rule,
+            colour              : self.colour,
+            prefix              : self.prefix.to_nbt(),
+            suffix              : self.suffix.to_nbt()
+        }
+    }
+
+}
+
+
+/// A team, shown to every player.
+///
+/// Spawn an entity with this component to create the team, and despawn it to
+///  remove the team. Members are player usernames, or entity UUIDs. Changes
+///  are sent at the end of the tick.
+#[derive(Component)]
+pub struct Team {
+    name     : String,
+    settings : Dirty<TeamSettings>,
+    members  : BTreeSet<String>,
+    added    : BTreeSet<String>,
+    removed  : BTreeSet<String>
+}
+
+impl Team {
+
+    /// Creates a team. `name` must be unique.
+    pub fn new(name : impl Into<String>, settings : TeamSettings) -> Self { Self {
+        name     : name.into(),
+        settings : Dirty::new(settings),
+        members  : BTreeSet::new(),
+        added    : BTreeSet::new(),
+        removed  : BTreeSet::new()
+    } }
+
+    #[inline]
+    pub fn name(&self) -> &str { &self.name }
+
+    #[inline]
+    pub fn settings(&self) -> &TeamSettings { &self.settings }
+
+    #[inline]
+    pub fn settings_mut(&mut self) -> &mut TeamSettings { &mut self.settings }
+
+    #[inline]
+    pub fn members(&self) -> &BTreeSet<String> { &self.members }
+
+    pub fn add_member(&mut self, member : impl Into<String>) {
+        let member = member.into();
+        if (self.members.insert(member.clone()) && (! self.removed.remove(&member))) {
+            self.added.insert(member);
+        }
+    }
+
+    pub fn remove_member(&mut self, member : &str) {
+        if (self.members.remove(member) && (! self.added.remove(member))) {
+            self.removed.insert(member.to_string());
+        }
+    }
+
+    fn create_change(&self) -> TeamChange {
+        TeamChange::Create((*self.settings).clone(), self.members.iter().cloned().collect())
+    }
+
+}
+
+
+/// A change to a team, built into a packet for each player.
+enum TeamChange {
+    Create(TeamSettings, Vec<String>),
+    Update(TeamSettings),
+    AddMembers(Vec<String>),
+    RemoveMembers(Vec<String>),
+    Remove
+}
+
+impl TeamChange {
+
+    fn to_packet(&self, name : &str) -> SetPlayerTeamS2CPlayPacket {
+        SetPlayerTeamS2CPlayPacket {
+            name   : name.to_string(),
+            action : match (self) {
+                Self::Create(settings, members) => TeamAction::Create {
+                    info     : settings.to_info(),
+                    entities : members.clone().into()
+                },
+                Self::Update(settings)          => TeamAction::Update { info : settings.to_info() },
+                Self::AddMembers(members)       => TeamAction::AddEntities { entities : members.clone().into() },
+                Self::RemoveMembers(members)    => TeamAction::RemoveEntities { entities : members.clone().into() },
+                Self::Remove                    => TeamAction::Remove
+            }
+        }
+    }
+
+}
+
+
+pub(crate) fn update_sidebars(
+    mut q_players : Query<(&mut Connection, &mut PlayerSidebar,), (With<ConnStatePlay>,)>
+) {
+    for (mut conn, mut sidebar,) in &mut q_players {
+        let sidebar       = &mut *sidebar;
+        let title_changed = Dirty::take_dirty(&mut sidebar.title);
+        let visible       = (*sidebar.title).is_some() || (! sidebar.lines.is_empty());
+
+        if (! visible) {
+            if (sidebar.shown) {
+                let _ = conn.send_packet_play(SetObjectiveS2CPlayPacket {
+                    name   : SIDEBAR_OBJECTIVE.to_string(),
+                    action : ObjectiveAction::Remove
+                });
+                sidebar.shown = false;
+                sidebar.sent_lines.clear();
+            }
+            continue;
+        }
+
+        let title = (*sidebar.title).as_ref().map_or_else(|| Text::new().to_nbt(), Text::to_nbt);
+        if (! sidebar.shown) {
+            let _ = conn.send_packet_play(SetObjectiveS2CPlayPacket {
+                name   : SIDEBAR_OBJECTIVE.to_string(),
+                action : ObjectiveAction::Create {
+                    title,
+                    render        : ObjectiveRender::Integer,
+                    number_format : Some(NumberFormat::Blank)
+                }
+            });
+            let _ = conn.send_packet_play(SetDisplayObjectiveS2CPlayPacket {
+                slot : DisplaySlot::Sidebar,
+                name : SIDEBAR_OBJECTIVE.to_string()
+            });
+            sidebar.shown = true;
+        } else if (title_changed) {
+            let _ = conn.send_packet_play(SetObjectiveS2CPlayPacket {
+                name   : SIDEBAR_OBJECTIVE.to_string(),
+                action : ObjectiveAction::Update {
+                    title,
+                    render        : ObjectiveRender::Integer,
+                    number_format : Some(NumberFormat::Blank)
+                }
+            });
+        }
+
+        // Lines are sorted by score, so the first line has the highest.
+        for (i, line,) in sidebar.lines.iter().enumerate() {
+            if (sidebar.sent_lines.get(i) == Some(line)) { continue; }
+            let _ = conn.send_packet_play(SetScoreS2CPlayPacket {
+                holder        : format!("line{i}"),
+                objective     : SIDEBAR_OBJECTIVE.to_string(),
+                value         : ((MAX_SIDEBAR_LINES - i) as i32).into(),
+                display_name  : Some(line.to_nbt()),
+                number_format : None
+            });
+        }
+        for i in sidebar.lines.len()..sidebar.sent_lines.len() {
+            let _ = conn.send_packet_play(ResetScoreS2CPlayPacket {
+                holder    : format!("line{i}"),
+                objective : Some(SIDEBAR_OBJECTIVE.to_string())
+            });
+        }
+        sidebar.sent_lines.clone_from(&sidebar.lines);
+    }
+}
+
+pub(crate) fn update_teams(
+    mut q_players : Query<(Entity, &mut Connection,), (With<ConnStatePlay>,)>,
+    mut q_teams   : Query<(Entity, &mut Team,)>,
+    mut er_joined : EventReader<PlayerJoined>,
+    mut rc_teams  : RemovedComponents<Team>,
+    mut l_sent    : Local<BTreeMap<Entity, String>>
+) {
+    let mut changes = Vec::new();
+
+    for entity in rc_teams.read() {
+        if let Some(name) = l_sent.remove(&entity) {
+            changes.push((name, TeamChange::Remove,));
+        }
+    }
+
+    for (entity, mut team,) in &mut q_teams {
+        let team = &mut *team;
+        if (! l_sent.contains_key(&entity)) {
+            // New teams are created with their current settings and members.
+            Dirty::take_dirty(&mut team.settings);
+            team.added.clear();
+            team.removed.clear();
+            l_sent.insert(entity, team.name.clone());
+            changes.push((team.name.clone(), team.create_change(),));
+            continue;
+        }
+        if (Dirty::take_dirty(&mut team.settings)) {
+            changes.push((team.name.clone(), TeamChange::Update((*team.settings).clone()),));
+        }
+        if (! team.added.is_empty()) {
+            changes.push((team.name.clone(), TeamChange::AddMembers(mem::take(&mut team.added).into_iter().collect()),));
+        }
+        if (! team.removed.is_empty()) {
+            changes.push((team.name.clone(), TeamChange::RemoveMembers(mem::take(&mut team.removed).into_iter().collect()),));
+        }
+    }
+
+    let joined = er_joined.read().map(|joined| joined.entity).collect::<BTreeSet<_>>();
+    for (entity, mut conn,) in &mut q_players {
+        if (joined.contains(&entity)) {
+            // Joining players are sent every team instead of this tick's changes.
+            for (_, team,) in &q_teams {
+                let _ = conn.send_packet_play(team.create_change().to_packet(&team.name));
+            }
+            continue;
+        }
+        for (name, change,) in &changes {
+            let _ = conn.send_packet_play(change.to_packet(name));
+        }
+    }
+}
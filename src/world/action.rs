@@ -1,17 +1,27 @@
-use crate::world::{ World, SetBlockBatch };
+use crate::Registries;
+use crate::world::{ World, WorldOverlay, PlayerInWorld, ChunkView, Block, BlockShape, BlockEntity, PlaceError, BlockChangeCause, MAX_SHAPE_VOLUME };
 use flywheelmc_common::prelude::*;
-use protocol::value::{ Identifier, BlockState };
-use protocol::registry::RegEntry;
+use protocol::value::{ Identifier, BlockPos, Nbt };
 
 
+/// A chunk of a [`World`] is needed, but is neither stored nor generated.
+///
+/// Send [`WorldChunkAction::MarkReady`] to the world once the chunk is filled.
 #[derive(Event)]
 #[non_exhaustive]
 pub struct WorldChunkLoading {
-    pub entity : Entity,
-    pub pos    : Vec2<i32>
+    /// The world entity which needs the chunk.
+    pub world : Entity,
+    pub pos   : Vec2<i32>
 }
 
 
+/// An action applied to a [`World`].
+///
+/// `entity` is either a world entity, or a player entity with a
+///  [`WorldOverlay`], in which case the action only affects that player.
+///
+/// Rejected placements are logged. Use the methods on [`World`] directly to handle them.
 #[derive(Event)]
 pub struct WorldChunkActionEvent {
     pub entity : Entity,
@@ -25,16 +35,81 @@ pub enum WorldChunkAction {
     },
 
     Set {
-        #[expect(clippy::type_complexity)]
-        blocks : Vec<(Vec3<i64>, String, Vec<(String, String,)>)>
+        blocks : Vec<(BlockPos, Block,)>
+    },
+
+    Fill {
+        shape : BlockShape,
+        block : Block
+    },
+
+    /// Sets the biome of every 4x4x4 cell overlapping the cuboid between `a` and `b` (inclusive).
+    SetBiome {
+        a     : BlockPos,
+        b     : BlockPos,
+        biome : Identifier
+    },
+
+    /// Sets the block entity at `pos`. `kind` is looked up in the block entity type registry.
+    SetBlockEntity {
+        pos  : BlockPos,
+        kind : Identifier,
+        data : Nbt
+    },
+
+    RemoveBlockEntity {
+        pos : BlockPos
+    },
+
+    /// Discards a player's overlay of a chunk, showing the shared chunk again.
+    ClearOverlay {
+        chunk_pos : Vec2<i32>
+    }
+
+}
+
+impl WorldChunkAction {
+
+    /// Returns the positions of every chunk this action changes.
+    fn touched_chunks(&self) -> BTreeSet<Vec2<i32>> {
+        fn insert_cuboid(chunks : &mut BTreeSet<Vec2<i32>>, a : BlockPos, b : BlockPos) {
+            for cx in a.x.min(b.x).div_floor(16)..=a.x.max(b.x).div_floor(16) {
+                for cz in a.z.min(b.z).div_floor(16)..=a.z.max(b.z).div_floor(16) {
+                    chunks.insert(Vec2::new(cx, cz));
+                }
+            }
+        }
+        let mut chunks = BTreeSet::new();
+        match (self) {
+            Self::MarkReady { .. } | Self::ClearOverlay { .. } => { },
+            Self::Set { blocks } => {
+                for (pos, _,) in blocks {
+                    chunks.insert(Vec2::new(pos.x.div_floor(16), pos.z.div_floor(16)));
+                }
+            },
+            // Shapes which are too large are rejected without changing anything.
+            Self::Fill { shape, .. } => if (shape.volume() <= MAX_SHAPE_VOLUME) {
+                let (min, max,) = shape.bounds();
+                insert_cuboid(&mut chunks, min, max);
+            },
+            Self::SetBiome { a, b, .. } => {
+                insert_cuboid(&mut chunks, *a, *b);
+            },
+            Self::SetBlockEntity { pos, .. } | Self::RemoveBlockEntity { pos } => {
+                chunks.insert(Vec2::new(pos.x.div_floor(16), pos.z.div_floor(16)));
+            }
+        }
+        chunks
     }
 
 }
 
 
 pub(crate) fn handle_actions(
-    mut q_worlds  : Query<(&mut World,)>,
-    mut er_action : EventReader<WorldChunkActionEvent>
+    mut q_worlds   : Query<(&mut World,)>,
+    mut q_overlays : Query<(&PlayerInWorld, &mut WorldOverlay, &mut ChunkView,)>,
+    mut er_action  : EventReader<WorldChunkActionEvent>,
+        r_regs     : Res<Registries>
 ) {
     for WorldChunkActionEvent { entity, action } in er_action.read() {
         if let Ok((mut world,)) = q_worlds.get_mut(*entity) {
@@ -44,34 +119,93 @@ pub(crate) fn handle_actions(
                     if let Some(chunk) = world.chunks.get_mut(chunk_pos)
                         && (! chunk.ready)
                     {
-                        chunk.ready = true;
-                        world.ready_chunks.push_back(*chunk_pos);
+                        chunk.ready   = true;
+                        chunk.unsaved = true;
+                        chunk.compute_heightmaps();
+                        world.light_chunk(*chunk_pos);
                     }
                 },
 
-                WorldChunkAction::Set { blocks } => {
-                    let mut batch = SetBlockBatch::new(&mut world);
-
-                    for (block_pos, block_id, states,) in blocks {
-                        let chunk_pos = Vec2::new(block_pos.x.div_floor(16) as i32, block_pos.z.div_floor(16) as i32);
-                        if (batch.is_chunk_loaded(chunk_pos)) {
-                            let block_id = Identifier::from(block_id);
-                            if let Some(mut block_state) = BlockState::default_for(&block_id) {
-                                for (state, value,) in states {
-                                    if (block_state.properties.contains_key(state)) {
-                                        block_state.properties.insert(state.clone(), value.clone());
-                                    }
-                                }
-                                if let Some(block_id) = block_state.to_id() {
-                                    let block = unsafe { RegEntry::new_unchecked(block_id as u32) };
-                                    batch.set((block_pos.x, block_pos.y as u16, block_pos.z), block);
-                                }
-                            }
+                WorldChunkAction::ClearOverlay { .. } => {
+                    warn!("Ignored overlay clear sent to world {}: only players have overlays", entity);
+                },
+
+                _ => { apply_change(*entity, &mut world, &r_regs, action); }
+
+            }
+        }
+
+        else if let Ok((in_world, mut overlay, mut view,)) = q_overlays.get_mut(*entity)
+            && let Ok((base,)) = q_worlds.get(in_world.0)
+        {
+            match (action) {
+
+                WorldChunkAction::MarkReady { .. } => { },
+
+                WorldChunkAction::ClearOverlay { chunk_pos } => {
+                    if let Some(overlay) = &mut overlay.world
+                        && overlay.chunks.remove(chunk_pos).is_some()
+                    { view.resend(*chunk_pos); }
+                },
+
+                _ => {
+                    let overlay = overlay.world.get_or_insert_with(|| World::new(base.dim_id.clone(), base.dim_type.clone()));
+                    // Copy the touched chunks out of the shared world.
+                    for chunk_pos in action.touched_chunks() {
+                        if (! overlay.chunks.contains_key(&chunk_pos))
+                            && let Some(chunk) = base.chunks.get(&chunk_pos)
+                            && (chunk.ready)
+                        {
+                            let mut chunk = chunk.clone();
+                            chunk.viewers.clear();
+                            overlay.chunks.insert(chunk_pos, chunk);
                         }
                     }
+                    apply_change(*entity, overlay, &r_regs, action);
                 }
 
             }
         }
     }
 }
+
+fn apply_change(entity : Entity, world : &mut World, regs : &Registries, action : &WorldChunkAction) {
+    let result = match (action) {
+
+        WorldChunkAction::Set { blocks } => world.caused_by(BlockChangeCause::Action, |world| world.set_blocks(blocks.iter().cloned())),
+
+        WorldChunkAction::Fill { shape, block } => world.caused_by(BlockChangeCause::Action, |world| world.fill(shape, block.clone())),
+
+        WorldChunkAction::SetBiome { a, b, biome } => {
+            let Some(entry) = regs.biome.get_entry(biome) else {
+                warn!("Rejected biome change in {}: unknown biome {}", entity, biome);
+                return;
+            };
+            world.fill_biome(*a, *b, entry)
+        },
+
+        WorldChunkAction::SetBlockEntity { pos, kind, data } => {
+            let Some(kind) = regs.block_entity_type.get_entry(kind) else {
+                warn!("Rejected block entity in {}: unknown block entity type {}", entity, kind);
+                return;
+            };
+            world.set_block_entity(*pos, BlockEntity { kind, data : data.clone() })
+                .map_err(|reason| PlaceError { rejected : vec![(*pos, reason,)] })
+        },
+
+        WorldChunkAction::RemoveBlockEntity { pos } => {
+            world.remove_block_entity(*pos);
+            Ok(())
+        },
+
+        WorldChunkAction::MarkReady { .. }
+            | WorldChunkAction::ClearOverlay { .. }
+        => Ok(())
+
+    };
+    if let Err(PlaceError { rejected }) = result {
+        for (pos, reason,) in rejected {
+            debug!("Rejected change at <{}, {}, {}> in {}: {}", pos.x, pos.y, pos.z, entity, reason);
+        }
+    }
+}
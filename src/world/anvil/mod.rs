@@ -0,0 +1,519 @@
+use crate::Registries;
+use crate::world::{ World, WorldChunkLoading, Chunk, ChunkSection, BlockEntity, BLOCK_AIR, BIOME_DEFAULT, bits_for_len, state_entry };
+use crate::world::nbt::{ self, Tag };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Identifier, BlockState };
+use protocol::registry::RegEntry;
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+use std::thread;
+
+
+mod region;
+
+
+/// The data version written to saved chunks and schematics.
+pub(crate) const DATA_VERSION : i32 = 4189;
+
+
+/// Persists the chunks of a [`World`] to a directory of Anvil region (`.mca`) files.
+///
+/// Region files are read and written off the main thread. Chunks are read back
+///  before [`WorldChunkLoading`](crate::world::WorldChunkLoading) is fired, and
+///  changed chunks are written when they unload, on autosave, and when the world
+///  is despawned or the app exits.
+///
+/// Chunks holding blocks or biomes which are not in the registries load them as
+///  air or the default biome, and are never written back, so that the region
+///  files keep the original data. Changes to those chunks are lost on unload.
+pub struct AnvilStorage {
+    dir                : PathBuf,
+    autosave_interval  : Option<Duration>,
+    last_autosave      : Instant,
+    /// Chunks being read. `None` until no write to the same region file is in progress.
+    pub(super) reading : BTreeMap<Vec2<i32>, Option<ManuallyPoll<'static, io::Result<Option<Tag>>>>>,
+    /// Encoded chunks waiting to be written.
+    queued             : BTreeMap<Vec2<i32>, Arc<Vec<u8>>>,
+    /// Encoded chunks being written, one batch at a time.
+    writing            : Option<(BTreeMap<Vec2<i32>, Arc<Vec<u8>>>, ManuallyPoll<'static, Vec<(Vec2<i32>, io::Error,)>>,)>
+}
+
+impl AnvilStorage {
+
+    /// Creates a storage reading and writing region files in `dir`.
+    pub fn new(dir : impl Into<PathBuf>) -> Self { Self {
+        dir               : dir.into(),
+        autosave_interval : Some(Duration::from_secs(300)),
+        last_autosave     : Instant::now(),
+        reading           : BTreeMap::new(),
+        queued            : BTreeMap::new(),
+        writing           : None
+    } }
+
+    /// Sets how often changed chunks are saved while loaded. `None` disables autosave.
+    pub fn autosave_interval(mut self, interval : Option<Duration>) -> Self {
+        self.autosave_interval = interval;
+        self
+    }
+
+    /// Queues `chunk` to be written.
+    fn queue(&mut self, pos : Vec2<i32>, chunk : &Chunk, regs : &Registries) {
+        self.queued.insert(pos, Arc::new(nbt::write_root(&encode_chunk(chunk, pos, regs))));
+    }
+
+    /// Returns the encoded chunk at `pos` if it is queued or being written.
+    fn unwritten(&self, pos : Vec2<i32>) -> Option<Arc<Vec<u8>>> {
+        self.queued.get(&pos)
+            .or_else(|| self.writing.as_ref().and_then(|(batch, _,)| batch.get(&pos)))
+            .cloned()
+    }
+
+    /// Starts every waiting read whose region file is not being written.
+    fn start_reading(&mut self) {
+        let writing = self.writing.as_ref().map_or_else(BTreeSet::new, |(batch, _,)|
+            batch.keys().map(|&pos| region::region_of(pos)).collect()
+        );
+        for (&pos, read,) in &mut self.reading {
+            if (read.is_some() || writing.contains(&region::region_of(pos))) { continue; }
+            let dir = self.dir.clone();
+            *read = Some(ManuallyPoll::new(async move {
+                smol::unblock(move || {
+                    region::read_chunk(&dir, pos)?.map(|data| nbt::read_root(&data)).transpose()
+                }).await
+            }));
+        }
+    }
+
+    /// Starts writing the queued chunks whose region files are not being read.
+    fn start_writing(&mut self) {
+        if (self.writing.is_some() || self.queued.is_empty()) { return; }
+        let reading = self.reading.iter()
+            .filter(|(_, read,)| read.is_some())
+            .map(|(&pos, _,)| region::region_of(pos))
+            .collect::<BTreeSet<_>>();
+        let mut batch = BTreeMap::new();
+        self.queued.retain(|&pos, data| {
+            if (reading.contains(&region::region_of(pos))) { return true; }
+            batch.insert(pos, Arc::clone(data));
+            false
+        });
+        if (batch.is_empty()) { return; }
+        let dir  = self.dir.clone();
+        let jobs = batch.iter().map(|(&pos, data,)| (pos, Arc::clone(data),)).collect::<Vec<_>>();
+        self.writing = Some((batch, ManuallyPoll::new(async move {
+            smol::unblock(move || write_batch(&dir, jobs)).await
+        }),));
+    }
+
+    /// Finishes the batch being written, if it is done.
+    fn finish_writing(&mut self) -> bool {
+        let Some((_, fut,)) = &mut self.writing
+            else { return true; };
+        let Poll::Ready(errors) = fut.poll()
+            else { return false; };
+        self.writing = None;
+        log_write_errors(&self.dir, errors);
+        true
+    }
+
+    /// Writes every queued chunk, blocking until they are written.
+    fn flush(&mut self) {
+        // Reads would be discarded with the world.
+        self.reading.clear();
+        while (! self.finish_writing()) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let batch = mem::take(&mut self.queued).into_iter().collect::<Vec<_>>();
+        let count = batch.len();
+        log_write_errors(&self.dir, write_batch(&self.dir, batch));
+        debug!("Flushed {} chunks to {}", count, self.dir.display());
+    }
+
+}
+
+
+impl World {
+
+    /// Persists this world's chunks using `storage`.
+    pub fn with_storage(mut self, storage : AnvilStorage) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Starts loading the newly created chunk at `pos` from storage.
+    ///
+    /// Chunks waiting to be saved are read before the region files. Returns
+    ///  `false` if this world has no storage, or nothing could be loaded.
+    pub(crate) fn start_loading_stored(&mut self, pos : Vec2<i32>, regs : &Registries) -> bool {
+        let Some(storage) = &mut self.storage
+            else { return false; };
+
+        // Chunks which unloaded before they were saved are newer than anything stored.
+        if let Some(i) = self.unloaded.iter().position(|(unloaded, _,)| *unloaded == pos) {
+            let (_, chunk,) = self.unloaded.swap_remove(i);
+            self.restore_chunk(pos, chunk);
+            return true;
+        }
+
+        if let Some(data) = storage.unwritten(pos) {
+            return match (nbt::read_root(&data).and_then(|tag| self.restore_stored_chunk(pos, &tag, regs))) {
+                Ok(()) => true,
+                Err(err) => {
+                    error!("Failed to decode unsaved chunk <{}, {}>: {}", pos.x, pos.y, err);
+                    false
+                }
+            };
+        }
+
+        storage.reading.insert(pos, None);
+        true
+    }
+
+    /// Decodes a stored chunk into the newly created chunk at `pos`.
+    ///
+    /// The chunk is left unchanged if decoding fails.
+    fn restore_stored_chunk(&mut self, pos : Vec2<i32>, tag : &Tag, regs : &Registries) -> io::Result<()> {
+        let mut chunk = Chunk::empty(&self.dim_type);
+        decode_chunk(&mut chunk, pos, tag, regs)?;
+        chunk.ready = true;
+        chunk.compute_heightmaps();
+        self.restore_chunk(pos, chunk);
+        self.light_chunk(pos);
+        Ok(())
+    }
+
+    /// Replaces the newly created chunk at `pos` with `chunk`, keeping its
+    ///  viewers and anything set in it while it was loading.
+    fn restore_chunk(&mut self, pos : Vec2<i32>, mut chunk : Chunk) {
+        if let Some(created) = self.chunks.get_mut(&pos) {
+            chunk.viewers = mem::take(&mut created.viewers);
+            if (! (created.early_edits.is_empty() && created.block_entities.is_empty())) {
+                // Blocks set while loading replace the stored block entities in the same place.
+                let replaced = created.early_edits.block_positions().collect::<BTreeSet<_>>();
+                chunk.block_entities.retain(|local, _| ! replaced.contains(local));
+                chunk.block_entities.append(&mut created.block_entities);
+                chunk.early_edits = mem::take(&mut created.early_edits);
+                chunk.apply_early_edits();
+                chunk.compute_heightmaps();
+                chunk.unsaved = true;
+            }
+            *created = chunk;
+        }
+    }
+
+    /// Writes every unsaved chunk to storage, blocking until they are written.
+    fn flush_storage(&mut self, regs : &Registries) {
+        let World { storage, chunks, unloaded, .. } = self;
+        let Some(storage) = storage
+            else { return; };
+        for (pos, chunk,) in unloaded.drain(..) {
+            storage.queue(pos, &chunk, regs);
+        }
+        for (&pos, chunk,) in chunks.iter_mut() {
+            if (chunk.needs_saving()) {
+                storage.queue(pos, chunk, regs);
+                chunk.unsaved = false;
+            }
+        }
+        storage.flush();
+    }
+
+}
+
+
+pub(crate) fn update_storage(
+    mut q_worlds : Query<(Entity, &mut World,)>,
+    mut ew_load  : EventWriter<WorldChunkLoading>,
+        r_regs   : Res<Registries>
+) {
+    for (world_entity, mut world,) in &mut q_worlds {
+        let world = &mut *world;
+        let Some(storage) = &mut world.storage
+            else { continue; };
+
+        // Queue unloaded and autosaved chunks.
+        for (pos, chunk,) in world.unloaded.drain(..) {
+            storage.queue(pos, &chunk, &r_regs);
+        }
+        if let Some(interval) = storage.autosave_interval
+            && (storage.last_autosave.elapsed() >= interval)
+        {
+            storage.last_autosave = Instant::now();
+            let mut count = 0usize;
+            for (&pos, chunk,) in world.chunks.iter_mut() {
+                if (chunk.needs_saving()) {
+                    storage.queue(pos, chunk, &r_regs);
+                    chunk.unsaved = false;
+                    count += 1;
+                }
+            }
+            debug!("Autosaving {} chunks to {}", count, storage.dir.display());
+        }
+
+        storage.finish_writing();
+        storage.start_reading();
+        storage.start_writing();
+
+        let mut finished = Vec::new();
+        for (&pos, read,) in &mut storage.reading {
+            if let Some(fut) = read
+                && let Poll::Ready(result) = fut.poll()
+            { finished.push((pos, result,)); }
+        }
+        if (finished.is_empty()) { continue; }
+        let dir = storage.dir.clone();
+        for (pos, result,) in finished {
+            if let Some(storage) = &mut world.storage {
+                storage.reading.remove(&pos);
+            }
+            if (! world.chunks.get(&pos).is_some_and(|chunk| ! chunk.ready)) { continue; }
+            let stored = match (result) {
+                Ok(Some(tag)) => match (world.restore_stored_chunk(pos, &tag, &r_regs)) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        error!("Failed to decode chunk <{}, {}> from {}: {}", pos.x, pos.y, dir.display(), err);
+                        false
+                    }
+                },
+                Ok(None) => false,
+                Err(err) => {
+                    error!("Failed to read chunk <{}, {}> from {}: {}", pos.x, pos.y, dir.display(), err);
+                    false
+                }
+            };
+            if (! (stored || world.start_generating(pos))) {
+                ew_load.write(WorldChunkLoading { world : world_entity, pos });
+            }
+        }
+    }
+}
+
+pub(crate) fn flush_removed_world(
+        trigger  : Trigger<OnRemove, World>,
+    mut q_worlds : Query<(&mut World,)>,
+        r_regs   : Res<Registries>
+) {
+    if let Ok((mut world,)) = q_worlds.get_mut(trigger.target()) {
+        world.flush_storage(&r_regs);
+    }
+}
+
+pub(crate) fn flush_storage_on_exit(
+    mut q_worlds : Query<(&mut World,)>,
+    mut er_exit  : EventReader<AppExit>,
+        r_regs   : Res<Registries>
+) {
+    if (er_exit.read().last().is_none()) { return; }
+    for (mut world,) in &mut q_worlds {
+        world.flush_storage(&r_regs);
+    }
+}
+
+
+fn write_batch(dir : &Path, batch : Vec<(Vec2<i32>, Arc<Vec<u8>>,)>) -> Vec<(Vec2<i32>, io::Error,)> {
+    batch.into_iter()
+        .filter_map(|(pos, data,)| region::write_chunk(dir, pos, &data).err().map(|err| (pos, err,)))
+        .collect()
+}
+
+fn log_write_errors(dir : &Path, errors : Vec<(Vec2<i32>, io::Error,)>) {
+    for (pos, err,) in errors {
+        error!("Failed to save chunk <{}, {}> to {}: {}", pos.x, pos.y, dir.display(), err);
+    }
+}
+
+
+fn encode_chunk(chunk : &Chunk, pos : Vec2<i32>, regs : &Registries) -> Tag {
+    let mut sections = Vec::with_capacity(chunk.sections.len());
+    for (cy, section) in chunk.sections.iter().enumerate() {
+        let mut compound = BTreeMap::new();
+        compound.insert("Y".to_string(), Tag::Byte((chunk.min_section + (cy as i32)) as i8));
+
+        // Blocks
+        let mut palette = Vec::<RegEntry<BlockState>>::new();
+        let     indices = section.iter().map(|block| {
+            palette.iter().position(|&entry| entry == block).unwrap_or_else(|| {
+                palette.push(block);
+                palette.len() - 1
+            })
+        }).collect::<Vec<_>>();
+        let mut block_states = BTreeMap::new();
+        block_states.insert("palette".to_string(), Tag::List(palette.iter().map(|&entry| {
+            let mut compound = BTreeMap::new();
+            match (BlockState::from_id(entry.id())) {
+                Some(state) => {
+                    compound.insert("Name".to_string(), Tag::String(state.id.to_string()));
+                    if (! state.properties.is_empty()) {
+                        compound.insert("Properties".to_string(), Tag::Compound(state.properties.iter()
+                            .map(|(key, value,)| (key.to_string(), Tag::String(value.to_string()),))
+                            .collect()
+                        ));
+                    }
+                },
+                None => { compound.insert("Name".to_string(), Tag::String("minecraft:air".to_string())); }
+            }
+            Tag::Compound(compound)
+        }).collect()));
+        if (palette.len() > 1) {
+            block_states.insert("data".to_string(), Tag::LongArray(pack(&indices, bits_for_len(palette.len()).max(4))));
+        }
+        compound.insert("block_states".to_string(), Tag::Compound(block_states));
+
+        // Biomes
+        let mut palette = Vec::<RegEntry<_>>::new();
+        let     indices = (0..64u8).map(|i| {
+            let biome = section.get_biome_xyz((i & 0b11) << 2, (i >> 4) << 2, ((i >> 2) & 0b11) << 2);
+            palette.iter().position(|&entry| entry == biome).unwrap_or_else(|| {
+                palette.push(biome);
+                palette.len() - 1
+            })
+        }).collect::<Vec<_>>();
+        let mut biomes = BTreeMap::new();
+        biomes.insert("palette".to_string(), Tag::List(palette.iter().map(|&entry|
+            Tag::String(regs.biome.get_key(entry).map_or_else(|| "minecraft:plains".to_string(), |id| id.to_string()))
+        ).collect()));
+        if (palette.len() > 1) {
+            biomes.insert("data".to_string(), Tag::LongArray(pack(&indices, bits_for_len(palette.len()))));
+        }
+        compound.insert("biomes".to_string(), Tag::Compound(biomes));
+
+        sections.push(Tag::Compound(compound));
+    }
+
+    let mut root = BTreeMap::new();
+    root.insert("DataVersion".to_string(), Tag::Int(DATA_VERSION));
+    root.insert("xPos".to_string(), Tag::Int(pos.x));
+    root.insert("zPos".to_string(), Tag::Int(pos.y));
+    root.insert("yPos".to_string(), Tag::Int(chunk.min_section));
+    root.insert("Status".to_string(), Tag::String("minecraft:full".to_string()));
+    root.insert("sections".to_string(), Tag::List(sections));
+    root.insert("block_entities".to_string(), Tag::List(chunk.block_entities.iter().filter_map(|(&(x, y, z,), block_entity,)| {
+        let Some(id) = regs.block_entity_type.get_key(block_entity.kind)
+            else { return None; };
+        let Some(Tag::Compound(mut compound)) = block_entity.data_tag() else {
+            warn!("Not saving block entity {} in chunk <{}, {}>: data is not a compound", id, pos.x, pos.y);
+            return None;
+        };
+        compound.insert("id".to_string(), Tag::String(id.to_string()));
+        compound.insert("x".to_string(), Tag::Int((pos.x * 16) + (x as i32)));
+        compound.insert("y".to_string(), Tag::Int(chunk.min_y() + (y as i32)));
+        compound.insert("z".to_string(), Tag::Int((pos.y * 16) + (z as i32)));
+        Some(Tag::Compound(compound))
+    }).collect()));
+    Tag::Compound(root)
+}
+
+fn decode_chunk(chunk : &mut Chunk, pos : Vec2<i32>, tag : &Tag, regs : &Registries) -> io::Result<()> {
+    let invalid = |msg : &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad chunk: {msg}"));
+    let sections = tag.get("sections").and_then(Tag::as_list).ok_or_else(|| invalid("missing sections"))?;
+    for section_tag in sections {
+        let y = section_tag.get("Y").and_then(Tag::as_int).ok_or_else(|| invalid("missing section Y"))?;
+        // Section Y is absolute, so sections line up even if the dimension's height has changed.
+        let Ok(cy) = usize::try_from(y - (chunk.min_section as i64))
+            else { continue; };
+        let Some(section) = chunk.sections.get_mut(cy)
+            else { continue; };
+        let mut decoded = ChunkSection::empty();
+
+        // Blocks
+        if let Some(block_states) = section_tag.get("block_states") {
+            let palette = block_states.get("palette").and_then(Tag::as_list).unwrap_or(&[]).iter().map(|entry| {
+                let name  = entry.get("Name").and_then(Tag::as_str).unwrap_or("minecraft:air");
+                let block = BlockState::default_for(&Identifier::from(name)).and_then(|mut state| {
+                    if let Some(properties) = entry.get("Properties").and_then(Tag::as_compound) {
+                        for (key, value,) in properties {
+                            if let Some(value) = value.as_str()
+                                && state.properties.contains_key(key)
+                            { state.properties.insert(key.clone(), value.to_string()); }
+                        }
+                    }
+                    state_entry(&state)
+                });
+                block.unwrap_or_else(|| {
+                    warn!("Loading unknown block {} in section <{}, {}, {}> as air. The chunk will not be saved", name, pos.x, y, pos.y);
+                    chunk.lossy = true;
+                    BLOCK_AIR
+                })
+            }).collect::<Vec<_>>();
+            let data = block_states.get("data").and_then(Tag::as_long_array).unwrap_or(&[]);
+            let bits = bits_for_len(palette.len()).max(4);
+            for (linear_xyz, index) in unpack(data, bits, 4096).into_iter().enumerate() {
+                decoded.set(linear_xyz as u16, palette.get(index).cloned().unwrap_or(BLOCK_AIR));
+            }
+        }
+
+        // Biomes
+        if let Some(biomes) = section_tag.get("biomes") {
+            let palette = biomes.get("palette").and_then(Tag::as_list).unwrap_or(&[]).iter().map(|entry| {
+                let name = entry.as_str().unwrap_or("");
+                regs.biome.get_entry(&Identifier::from(name)).unwrap_or_else(|| {
+                    warn!("Loading unknown biome {} in section <{}, {}, {}> as the default biome. The chunk will not be saved", name, pos.x, y, pos.y);
+                    chunk.lossy = true;
+                    BIOME_DEFAULT
+                })
+            }).collect::<Vec<_>>();
+            let data = biomes.get("data").and_then(Tag::as_long_array).unwrap_or(&[]);
+            let bits = bits_for_len(palette.len());
+            for (i, index) in unpack(data, bits, 64).into_iter().enumerate() {
+                let i = i as u8;
+                decoded.set_biome_xyz((i & 0b11) << 2, (i >> 4) << 2, ((i >> 2) & 0b11) << 2, palette.get(index).cloned().unwrap_or(BIOME_DEFAULT));
+            }
+        }
+
+        decoded.collapse();
+        decoded.clear_dirty();
+        *section = decoded;
+    }
+
+    // Block entities
+    let height = (chunk.sections.len() * 16) as i64;
+    for entry in tag.get("block_entities").and_then(Tag::as_list).unwrap_or(&[]) {
+        let compound = entry.as_compound().ok_or_else(|| invalid("block entity is not a compound"))?;
+        let id = compound.get("id").and_then(Tag::as_str).ok_or_else(|| invalid("missing block entity id"))?;
+        let (Some(x), Some(y), Some(z),) = (
+            compound.get("x").and_then(Tag::as_int),
+            compound.get("y").and_then(Tag::as_int),
+            compound.get("z").and_then(Tag::as_int),
+        ) else { return Err(invalid("missing block entity position")); };
+        let local_y = y - (chunk.min_y() as i64);
+        if (x.div_euclid(16) != (pos.x as i64) || z.div_euclid(16) != (pos.y as i64) || local_y < 0 || local_y >= height) {
+            warn!("Skipping block entity {} at ({}, {}, {}) outside chunk <{}, {}>", id, x, y, z, pos.x, pos.y);
+            continue;
+        }
+        let Some(kind) = regs.block_entity_type.get_entry(&Identifier::from(id)) else {
+            warn!("Skipping block entity of unknown type {} in chunk <{}, {}>", id, pos.x, pos.y);
+            continue;
+        };
+        let mut data = compound.clone();
+        for key in ["id", "x", "y", "z", "keepPacked"] {
+            data.remove(key);
+        }
+        let block_entity = BlockEntity::from_data_tag(kind, &Tag::Compound(data)).ok_or_else(|| invalid("bad block entity data"))?;
+        chunk.block_entities.insert((x.rem_euclid(16) as u8, local_y as u16, z.rem_euclid(16) as u8,), block_entity);
+    }
+
+    Ok(())
+}
+
+
+/// Packs indices into longs, without entries spanning two longs.
+fn pack(indices : &[usize], bits : u8) -> Vec<i64> {
+    let per_long = 64 / (bits as usize);
+    let mut data = vec![0u64; indices.len().div_ceil(per_long)];
+    for (i, &index) in indices.iter().enumerate() {
+        data[i / per_long] |= (index as u64) << ((i % per_long) * (bits as usize));
+    }
+    data.into_iter().map(u64::cast_signed).collect()
+}
+
+/// Unpacks `count` indices from longs, without entries spanning two longs.
+///
+/// Missing data unpacks as index 0.
+fn unpack(data : &[i64], bits : u8, count : usize) -> Vec<usize> {
+    if (bits == 0 || data.is_empty()) { return vec![0; count]; }
+    let per_long = 64 / (bits as usize);
+    let mask     = (1u64 << bits) - 1;
+    (0..count).map(|i| {
+        data.get(i / per_long).map_or(0, |&long| ((long.cast_unsigned() >> ((i % per_long) * (bits as usize))) & mask) as usize)
+    }).collect()
+}
@@ -0,0 +1,111 @@
+use flywheelmc_common::prelude::*;
+use flate2::Compression;
+use flate2::read::{ GzDecoder, ZlibDecoder };
+use flate2::write::ZlibEncoder;
+use std::fs::{ self, File, OpenOptions };
+use std::io::{ Read, Write, Seek, SeekFrom };
+use std::path::{ Path, PathBuf };
+
+
+const SECTOR_SIZE : u64 = 4096;
+
+const COMPRESSION_GZIP : u8 = 1;
+const COMPRESSION_ZLIB : u8 = 2;
+const COMPRESSION_NONE : u8 = 3;
+
+
+/// Returns the position of the region containing the chunk at `cpos`.
+pub(crate) fn region_of(cpos : Vec2<i32>) -> Vec2<i32> {
+    Vec2::new(cpos.x.div_floor(32), cpos.y.div_floor(32))
+}
+
+fn region_path(dir : &Path, cpos : Vec2<i32>) -> PathBuf {
+    let region = region_of(cpos);
+    dir.join(format!("r.{}.{}.mca", region.x, region.y))
+}
+
+fn header_index(cpos : Vec2<i32>) -> u64 {
+    ((cpos.x.rem_euclid(32) + (cpos.y.rem_euclid(32) * 32)) * 4) as u64
+}
+
+
+/// Reads the uncompressed NBT of a chunk from its region file in `dir`.
+///
+/// Returns `None` if the region file or chunk does not exist.
+pub(crate) fn read_chunk(dir : &Path, cpos : Vec2<i32>) -> io::Result<Option<Vec<u8>>> {
+    let mut file = match (File::open(region_path(dir, cpos))) {
+        Ok(file) => file,
+        Err(err) if (err.kind() == io::ErrorKind::NotFound) => { return Ok(None); },
+        Err(err) => { return Err(err); }
+    };
+    let mut location = [0u8; 4];
+    file.seek(SeekFrom::Start(header_index(cpos)))?;
+    if (file.read_exact(&mut location).is_err()) { return Ok(None); }
+    let offset = u32::from_be_bytes([0, location[0], location[1], location[2]]) as u64;
+    if (offset == 0) { return Ok(None); }
+
+    file.seek(SeekFrom::Start(offset * SECTOR_SIZE))?;
+    let mut header = [0u8; 5];
+    file.read_exact(&mut header)?;
+    let len = (u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize).saturating_sub(1);
+    let mut compressed = vec![0u8; len];
+    file.read_exact(&mut compressed)?;
+
+    let mut data = Vec::new();
+    match (header[4]) {
+        COMPRESSION_GZIP => { GzDecoder::new(&*compressed).read_to_end(&mut data)?; },
+        COMPRESSION_ZLIB => { ZlibDecoder::new(&*compressed).read_to_end(&mut data)?; },
+        COMPRESSION_NONE => { data = compressed; },
+        other => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported chunk compression {other}")));
+        }
+    }
+    Ok(Some(data))
+}
+
+/// Writes the uncompressed NBT of a chunk into its region file in `dir`.
+///
+/// The chunk is written in place if it fits in its existing sectors, and appended to the file otherwise.
+pub(crate) fn write_chunk(dir : &Path, cpos : Vec2<i32>, data : &[u8]) -> io::Result<()> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    let compressed = encoder.finish()?;
+
+    let mut payload = Vec::with_capacity(compressed.len() + 5);
+    payload.extend(((compressed.len() + 1) as u32).to_be_bytes());
+    payload.push(COMPRESSION_ZLIB);
+    payload.extend(compressed);
+    let sectors = (payload.len() as u64).div_ceil(SECTOR_SIZE);
+    if (sectors > u8::MAX as u64) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "chunk too large for region file"));
+    }
+    payload.resize((sectors * SECTOR_SIZE) as usize, 0);
+
+    fs::create_dir_all(dir)?;
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(region_path(dir, cpos))?;
+    let file_len = file.metadata()?.len();
+    if (file_len < (SECTOR_SIZE * 2)) {
+        file.set_len(SECTOR_SIZE * 2)?;
+    }
+
+    let mut location = [0u8; 4];
+    file.seek(SeekFrom::Start(header_index(cpos)))?;
+    file.read_exact(&mut location)?;
+    let old_offset  = u32::from_be_bytes([0, location[0], location[1], location[2]]) as u64;
+    let old_sectors = location[3] as u64;
+
+    let offset = if (old_offset >= 2 && sectors <= old_sectors) {
+        old_offset
+    } else {
+        file.metadata()?.len().div_ceil(SECTOR_SIZE)
+    };
+    file.seek(SeekFrom::Start(offset * SECTOR_SIZE))?;
+    file.write_all(&payload)?;
+
+    let offset = (offset as u32).to_be_bytes();
+    file.seek(SeekFrom::Start(header_index(cpos)))?;
+    file.write_all(&[offset[1], offset[2], offset[3], sectors as u8])?;
+    file.seek(SeekFrom::Start(SECTOR_SIZE + header_index(cpos)))?;
+    file.write_all(&(Utc::now().timestamp() as u32).to_be_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use crate::world::{ World, PlaceRejection, PlaceError };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ BlockPos, Biome };
+use protocol::registry::RegEntry;
+
+
+impl World {
+
+    /// Returns the biome at `pos`.
+    ///
+    /// Returns `None` if the chunk containing `pos` is not loaded and ready, or if `pos` is out of the world's height range.
+    pub fn get_biome(&self, pos : BlockPos) -> Option<RegEntry<Biome>> {
+        let (cpos, cy, _,) = self.locate_block(pos)?;
+        let chunk = self.chunks.get(&cpos)?;
+        if (! chunk.ready) { return None; }
+        let section = chunk.sections.get(cy)?;
+        Some(section.get_biome_xyz(pos.x.rem_euclid(16) as u8, pos.y.rem_euclid(16) as u8, pos.z.rem_euclid(16) as u8))
+    }
+
+    /// Sets the biome of the 4x4x4 cell containing `pos`.
+    pub fn set_biome(&mut self, pos : BlockPos, biome : RegEntry<Biome>) -> Result<(), PlaceRejection> {
+        let (cpos, cy, _,) = self.locate_block(pos).ok_or(PlaceRejection::OutOfHeight)?;
+        let chunk   = self.chunks.get_mut(&cpos).ok_or(PlaceRejection::ChunkNotLoaded)?;
+        let section = chunk.sections.get_mut(cy).ok_or(PlaceRejection::OutOfHeight)?;
+        let [dx, dy, dz] = [pos.x.rem_euclid(16) as u8, pos.y.rem_euclid(16) as u8, pos.z.rem_euclid(16) as u8];
+        // Kept over whatever the chunk loads as.
+        if (! chunk.ready) {
+            chunk.early_edits.biomes.insert((cy, [dx & !0b11, dy & !0b11, dz & !0b11],), biome);
+        }
+        if (section.set_biome_xyz(dx, dy, dz, biome)) {
+            chunk.biomes_dirty = true;
+            chunk.unsaved      = true;
+        }
+        Ok(())
+    }
+
+    /// Sets the biome of every 4x4x4 cell overlapping the cuboid between `a` and `b` (inclusive).
+    pub fn fill_biome(&mut self, a : BlockPos, b : BlockPos, biome : RegEntry<Biome>) -> Result<(), PlaceError> {
+        let mut rejected = Vec::new();
+        for y in a.y.min(b.y).div_floor(4)..=a.y.max(b.y).div_floor(4) {
+            for z in a.z.min(b.z).div_floor(4)..=a.z.max(b.z).div_floor(4) {
+                for x in a.x.min(b.x).div_floor(4)..=a.x.max(b.x).div_floor(4) {
+                    let pos = BlockPos { x : x * 4, y : y * 4, z : z * 4 };
+                    if let Err(reason) = self.set_biome(pos, biome) {
+                        rejected.push((pos, reason,));
+                    }
+                }
+            }
+        }
+        if (rejected.is_empty()) { Ok(()) }
+        else { Err(PlaceError { rejected }) }
+    }
+
+}
@@ -0,0 +1,122 @@
+use crate::world::{ World, Chunk, PlaceRejection };
+use crate::world::nbt::{ self, Tag };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ BlockPos, BlockEntityType, ChunkBlockEntity, Nbt };
+use protocol::packet::{ PacketReader, PacketWriter, PacketDecode };
+use protocol::packet::s2c::play::BlockEntityDataS2CPlayPacket;
+use protocol::registry::RegEntry;
+
+
+/// A block entity, such as a sign, chest, skull, or banner.
+#[derive(Clone)]
+pub struct BlockEntity {
+    pub kind : RegEntry<BlockEntityType>,
+    pub data : Nbt
+}
+
+
+impl BlockEntity {
+
+    /// Converts the data of this block entity to a file NBT tag.
+    pub(crate) fn data_tag(&self) -> Option<Tag> {
+        let mut buf = PacketWriter::new();
+        buf.encode_write(&self.data).ok()?;
+        nbt::read_nameless(&buf.into_inner()).ok()
+    }
+
+    /// Creates a block entity from a file NBT tag.
+    pub(crate) fn from_data_tag(kind : RegEntry<BlockEntityType>, tag : &Tag) -> Option<Self> {
+        let bytes = nbt::write_nameless(tag);
+        let data  = Nbt::decode(&mut PacketReader::new(&bytes)).ok()?;
+        Some(Self { kind, data })
+    }
+
+}
+
+
+/// Chunk-local block entity position, as `(x, y, z)`.
+pub(crate) type LocalBlockPos = (u8, u16, u8,);
+
+
+impl World {
+
+    fn locate_block_entity(&self, pos : BlockPos) -> Result<(Vec2<i32>, LocalBlockPos,), PlaceRejection> {
+        let (cpos, cy, _,) = self.locate_block(pos).ok_or(PlaceRejection::OutOfHeight)?;
+        let chunk = self.chunks.get(&cpos).ok_or(PlaceRejection::ChunkNotLoaded)?;
+        if (cy >= chunk.sections.len()) { return Err(PlaceRejection::OutOfHeight); }
+        Ok((cpos, (pos.x.rem_euclid(16) as u8, ((cy * 16) as u16) + (pos.y.rem_euclid(16) as u16), pos.z.rem_euclid(16) as u8,),))
+    }
+
+    /// Returns the block entity at `pos`.
+    pub fn get_block_entity(&self, pos : BlockPos) -> Option<&BlockEntity> {
+        let (cpos, local,) = self.locate_block_entity(pos).ok()?;
+        self.chunks.get(&cpos)?.block_entities.get(&local)
+    }
+
+    /// Sets the block entity at `pos`, replacing any existing one.
+    ///
+    /// The block at `pos` should already be of a type that has this kind of block entity.
+    pub fn set_block_entity(&mut self, pos : BlockPos, block_entity : BlockEntity) -> Result<(), PlaceRejection> {
+        let (cpos, local,) = self.locate_block_entity(pos)?;
+        let chunk = self.chunks.get_mut(&cpos).ok_or(PlaceRejection::ChunkNotLoaded)?;
+        chunk.block_entities_dirty.insert(local, block_entity.kind);
+        chunk.block_entities.insert(local, block_entity);
+        chunk.unsaved = true;
+        Ok(())
+    }
+
+    /// Removes the block entity at `pos`, returning it if there was one.
+    pub fn remove_block_entity(&mut self, pos : BlockPos) -> Option<BlockEntity> {
+        let (cpos, local,) = self.locate_block_entity(pos).ok()?;
+        let chunk = self.chunks.get_mut(&cpos)?;
+        let block_entity = chunk.block_entities.remove(&local)?;
+        chunk.block_entities_dirty.insert(local, block_entity.kind);
+        chunk.unsaved = true;
+        Some(block_entity)
+    }
+
+}
+
+
+impl Chunk {
+
+    pub(crate) fn ptc_block_entities(&self) -> Vec<ChunkBlockEntity> {
+        self.block_entities.iter().map(|(&(x, y, z,), block_entity)| ChunkBlockEntity {
+            packed_xz : (x << 4) | z,
+            y         : (self.min_y() + (y as i32)) as i16,
+            kind      : block_entity.kind,
+            data      : block_entity.data.clone()
+        }).collect()
+    }
+
+    /// Returns block entity data packets for every changed block entity whose section has no unsent block changes.
+    ///
+    /// Returned positions should be removed from `block_entities_dirty` once sent.
+    pub(crate) fn ptc_update_block_entities(&self, cpos : Vec2<i32>) -> Vec<(LocalBlockPos, BlockEntityDataS2CPlayPacket,)> {
+        let mut packets = Vec::new();
+        for (&local, &kind) in &self.block_entities_dirty {
+            let (x, y, z,) = local;
+            if (self.sections[(y / 16) as usize].is_dirty()) { continue; }
+            let pos = BlockPos {
+                x : (cpos.x * 16) + (x as i32),
+                y : self.min_y() + (y as i32),
+                z : (cpos.y * 16) + (z as i32)
+            };
+            packets.push((local, match (self.block_entities.get(&local)) {
+                Some(block_entity) => BlockEntityDataS2CPlayPacket {
+                    pos,
+                    kind : block_entity.kind,
+                    data : block_entity.data.clone()
+                },
+                // Removed block entities are cleared by sending empty data.
+                None => BlockEntityDataS2CPlayPacket {
+                    pos,
+                    kind,
+                    data : Nbt::new()
+                }
+            },));
+        }
+        packets
+    }
+
+}
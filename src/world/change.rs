@@ -0,0 +1,129 @@
+use crate::world::{ World, WorldOverlay };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ BlockState, BlockPos };
+use protocol::registry::RegEntry;
+
+
+/// What changed a block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockChangeCause {
+    /// A method on [`World`] called directly.
+    Direct,
+    /// A [`WorldChunkAction`](crate::world::WorldChunkAction).
+    Action,
+    /// A player breaking or placing a block.
+    Player(Entity)
+}
+
+
+/// A block in a ready chunk changed.
+///
+/// `world` is either a world entity, or a player entity with a [`WorldOverlay`]
+///  if the change only affects that player.
+#[derive(Event, Clone)]
+pub struct BlockChanged {
+    pub world : Entity,
+    pub pos   : BlockPos,
+    pub old   : RegEntry<BlockState>,
+    pub new   : RegEntry<BlockState>,
+    pub cause : BlockChangeCause
+}
+
+impl BlockChanged {
+
+    /// Returns `true` if this change is within the cuboid between `a` and `b` (inclusive).
+    pub fn is_within(&self, a : BlockPos, b : BlockPos) -> bool {
+        (a.x.min(b.x)..=a.x.max(b.x)).contains(&self.pos.x)
+        && (a.y.min(b.y)..=a.y.max(b.y)).contains(&self.pos.y)
+        && (a.z.min(b.z)..=a.z.max(b.z)).contains(&self.pos.z)
+    }
+
+    /// Returns `true` if `block` was placed or removed by this change.
+    pub fn involves(&self, block : RegEntry<BlockState>) -> bool {
+        self.old == block || self.new == block
+    }
+
+}
+
+
+/// Subscribes an entity to [`BlockChanged`] events matching every given filter.
+///
+/// Matching changes are delivered as [`SubscribedBlockChanged`] events.
+#[derive(Component, Default)]
+pub struct BlockChangeSubscription {
+    world  : Option<Entity>,
+    region : Option<(BlockPos, BlockPos,)>,
+    blocks : Option<Vec<RegEntry<BlockState>>>
+}
+
+impl BlockChangeSubscription {
+
+    /// Only matches changes in `world`.
+    pub fn in_world(mut self, world : Entity) -> Self {
+        self.world = Some(world);
+        self
+    }
+
+    /// Only matches changes in the cuboid between `a` and `b` (inclusive).
+    pub fn in_region(mut self, a : BlockPos, b : BlockPos) -> Self {
+        self.region = Some((a, b,));
+        self
+    }
+
+    /// Only matches changes which place or remove `block`. May be called more than once.
+    pub fn of_block(mut self, block : RegEntry<BlockState>) -> Self {
+        self.blocks.get_or_insert_with(Vec::new).push(block);
+        self
+    }
+
+    pub fn matches(&self, change : &BlockChanged) -> bool {
+        self.world.is_none_or(|world| world == change.world)
+        && self.region.is_none_or(|(a, b,)| change.is_within(a, b))
+        && self.blocks.as_ref().is_none_or(|blocks| blocks.iter().any(|&block| change.involves(block)))
+    }
+
+}
+
+
+/// A [`BlockChanged`] event matching the [`BlockChangeSubscription`] on `subscriber`.
+#[derive(Event)]
+pub struct SubscribedBlockChanged {
+    pub subscriber : Entity,
+    pub change     : BlockChanged
+}
+
+
+impl World {
+
+    /// Runs `f`, attributing every block change it makes to `cause`.
+    pub(crate) fn caused_by<R>(&mut self, cause : BlockChangeCause, f : impl FnOnce(&mut Self) -> R) -> R {
+        let previous = mem::replace(&mut self.change_cause, cause);
+        let result   = f(self);
+        self.change_cause = previous;
+        result
+    }
+
+}
+
+
+pub(crate) fn emit_block_changes(
+    mut q_worlds        : Query<(Entity, &mut World,)>,
+    mut q_overlays      : Query<(Entity, &mut WorldOverlay,)>,
+        q_subscriptions : Query<(Entity, &BlockChangeSubscription,)>,
+    mut ew_changed      : EventWriter<BlockChanged>,
+    mut ew_subscribed   : EventWriter<SubscribedBlockChanged>
+) {
+    let worlds   = q_worlds.iter_mut().map(|(entity, world,)| (entity, world.into_inner(),));
+    let overlays = q_overlays.iter_mut().filter_map(|(entity, overlay,)| Some((entity, overlay.into_inner().world.as_mut()?,)));
+    for (entity, world,) in worlds.chain(overlays) {
+        for (pos, old, new, cause,) in mem::take(&mut world.block_changes) {
+            let change = BlockChanged { world : entity, pos, old, new, cause };
+            for (subscriber, subscription,) in &q_subscriptions {
+                if (subscription.matches(&change)) {
+                    ew_subscribed.write(SubscribedBlockChanged { subscriber, change : change.clone() });
+                }
+            }
+            ew_changed.write(change);
+        }
+    }
+}
@@ -1,23 +1,144 @@
-use crate::world::ChunkSection;
+use crate::world::{ ChunkSection, ChunkLight, Heightmaps, BlockEntity, LocalBlockPos, in_section_block_delinearise };
 use flywheelmc_common::prelude::*;
+use protocol::value::{ DimType, BlockState, Biome, BlockEntityType };
 use protocol::value::ChunkSectionData as PtcChunkSectionData;
+use protocol::packet::s2c::play::{
+    ChunksBiomesS2CPlayPacket,
+    ChunkBiomeData
+};
+use protocol::registry::RegEntry;
 
 
+#[derive(Clone)]
 pub struct Chunk {
-    pub(super) sections : Vec<ChunkSection>,
-    pub(super) ready    : bool,
-    pub(super) loaded   : bool
+    /// The section Y of the lowest section.
+    pub(super) min_section          : i32,
+    pub(super) sections             : Vec<ChunkSection>,
+    pub(super) ready                : bool,
+    pub(super) viewers              : BTreeSet<Entity>,
+    pub(super) biomes_dirty         : bool,
+    pub(super) light                : Option<ChunkLight>,
+    pub(super) light_dirty          : bool,
+    pub(super) heightmaps           : Heightmaps,
+    pub(super) block_entities       : BTreeMap<LocalBlockPos, BlockEntity>,
+    /// Changed block entities, with the kind of block entity that was there.
+    pub(super) block_entities_dirty : BTreeMap<LocalBlockPos, RegEntry<BlockEntityType>>,
+    /// Whether this chunk has changed since it was last saved.
+    pub(super) unsaved              : bool,
+    /// Whether this chunk was loaded with blocks or biomes which could not be
+    ///  decoded. Such chunks are never saved, so that the stored chunk is kept.
+    pub(super) lossy                : bool,
+    /// Blocks and biomes set while this chunk was loading.
+    pub(super) early_edits          : EarlyEdits
+}
+
+
+/// Blocks and biomes set in a chunk before it is ready, which are applied
+///  over whatever the chunk is loaded or generated as.
+#[derive(Clone, Default)]
+pub(super) struct EarlyEdits {
+    /// Blocks, by section index and in-section block index.
+    pub(super) blocks : BTreeMap<(usize, u16,), RegEntry<BlockState>>,
+    /// Biomes, by section index and the in-section position of the 4x4x4 cell.
+    pub(super) biomes : BTreeMap<(usize, [u8; 3],), RegEntry<Biome>>
+}
+
+impl EarlyEdits {
+
+    #[inline]
+    pub(super) fn is_empty(&self) -> bool { self.blocks.is_empty() && self.biomes.is_empty() }
+
+    /// Returns the chunk-local positions of every block set.
+    pub(super) fn block_positions(&self) -> impl Iterator<Item = LocalBlockPos> {
+        self.blocks.keys().map(|&(cy, linear_xyz,)| {
+            let [dx, dy, dz] = in_section_block_delinearise(linear_xyz);
+            (dx, ((cy * 16) + (dy as usize)) as u16, dz,)
+        })
+    }
+
 }
 
 
 impl Chunk {
 
-    pub(crate) fn ptc_chunk_section_data(&self) -> PtcChunkSectionData {
+    pub(super) fn empty(dim_type : &DimType) -> Self {
+        let     section  = ChunkSection::empty();
+        let     count    = (dim_type.height / 16).max(1);
+        let mut sections = Vec::with_capacity(count as usize);
+        for _ in 1..count {
+            sections.push(section.clone());
+        }
+        sections.push(section);
+        Self {
+            min_section          : dim_type.min_y.div_floor(16),
+            sections,
+            ready                : false,
+            viewers              : BTreeSet::new(),
+            biomes_dirty         : false,
+            light                : None,
+            light_dirty          : false,
+            heightmaps           : Heightmaps::EMPTY,
+            block_entities       : BTreeMap::new(),
+            block_entities_dirty : BTreeMap::new(),
+            unsaved              : false,
+            lossy                : false,
+            early_edits          : EarlyEdits::default()
+        }
+    }
+
+    /// Returns `true` if this chunk has changes which should be saved.
+    #[inline]
+    pub(super) fn needs_saving(&self) -> bool {
+        self.ready && self.unsaved && (! self.lossy)
+    }
+
+    /// Applies the blocks and biomes set while this chunk was loading over its current contents.
+    pub(super) fn apply_early_edits(&mut self) {
+        let EarlyEdits { blocks, biomes } = mem::take(&mut self.early_edits);
+        for ((cy, linear_xyz,), block,) in blocks {
+            if let Some(section) = self.sections.get_mut(cy) {
+                section.set(linear_xyz, block);
+            }
+        }
+        for ((cy, [dx, dy, dz],), biome,) in biomes {
+            if let Some(section) = self.sections.get_mut(cy) {
+                section.set_biome_xyz(dx, dy, dz, biome);
+            }
+        }
+        for section in &mut self.sections {
+            section.collapse();
+            section.clear_dirty();
+        }
+    }
+
+    /// Returns the world Y of the lowest block in this chunk.
+    #[inline]
+    pub fn min_y(&self) -> i32 { self.min_section * 16 }
+
+    /// Returns the number of players currently viewing this chunk.
+    #[inline]
+    pub fn viewer_count(&self) -> usize { self.viewers.len() }
+
+}
+
+impl Chunk {
+
+    pub(crate) fn ptc_chunk_section_data(&self, biome_direct_bits : u8) -> PtcChunkSectionData {
         let mut sections = Vec::with_capacity(self.sections.len());
         for section in &self.sections {
-            sections.push(section.ptc_chunk_section());
+            sections.push(section.ptc_chunk_section(biome_direct_bits));
         }
         PtcChunkSectionData { sections }
     }
 
+    pub(crate) fn ptc_update_biomes(&self, pos : Vec2<i32>, biome_direct_bits : u8) -> ChunksBiomesS2CPlayPacket {
+        ChunksBiomesS2CPlayPacket {
+            chunks : vec![ChunkBiomeData {
+                chunk_x : pos.x,
+                chunk_z : pos.y,
+                biomes  : self.sections.iter().map(|section| section.ptc_biomes(biome_direct_bits)).collect::<Vec<_>>().into()
+            }].into()
+        }
+    }
+
 }
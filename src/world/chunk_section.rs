@@ -1,6 +1,6 @@
-use crate::world::{ BLOCK_AIR, in_section_block_linearise, in_section_block_delinearise };
+use crate::world::{ BLOCK_AIR, BIOME_DEFAULT, in_section_block_linearise, in_section_block_delinearise };
 use flywheelmc_common::prelude::*;
-use protocol::value::{ Var32, Var64, BlockState, BlockPos };
+use protocol::value::{ Var64, BlockState, BlockPos, Biome };
 use protocol::value::{
     ChunkSection as PtcChunkSection,
     PalettedContainer,
@@ -15,27 +15,74 @@ use protocol::packet::s2c::play::{
 use protocol::registry::RegEntry;
 
 
+/// The smallest bits-per-entry the protocol allows for an indirect block palette.
+const MIN_INDIRECT_BITS       : u8 = 4;
+/// The largest bits-per-entry the protocol allows for an indirect block palette.
+const MAX_INDIRECT_BITS       : u8 = 8;
+/// The bits-per-entry of a direct block container.
+const DIRECT_BITS             : u8 = 15;
+/// The largest bits-per-entry the protocol allows for an indirect biome palette.
+const MAX_INDIRECT_BIOME_BITS : u8 = 3;
+
+
 #[derive(Clone)]
 pub struct ChunkSection {
-    runs  : Vec<(u16, RegEntry<BlockState>)>,
-    dirty : BTreeSet<u16>
+    storage : SectionStorage,
+    non_air : u16,
+    dirty   : BTreeSet<u16>,
+    biomes  : BiomeStorage
+}
+
+#[derive(Clone)]
+enum SectionStorage {
+
+    /// Every block in the section is the same.
+    Single(RegEntry<BlockState>),
+
+    /// Each block is an index into a palette of at most 256 entries.
+    ///
+    /// `counts` holds the number of blocks using each palette entry. Entries
+    ///  whose count reaches zero are reused before the palette grows.
+    Indirect {
+        palette : Vec<RegEntry<BlockState>>,
+        counts  : Vec<u16>,
+        indices : Box<[u8; 4096]>
+    },
+
+    /// Each block is stored directly.
+    Direct(Box<[RegEntry<BlockState>; 4096]>)
+
+}
+
+/// The biomes of a section, stored as a 4x4x4 grid.
+#[derive(Clone)]
+enum BiomeStorage {
+    Single(RegEntry<Biome>),
+    Grid(Box<[RegEntry<Biome>; 64]>)
 }
 
 impl ChunkSection {
 
     pub fn empty() -> Self {
         Self {
-            runs  : vec![(4096, BLOCK_AIR)],
-            dirty : BTreeSet::new()
+            storage : SectionStorage::Single(BLOCK_AIR),
+            non_air : 0,
+            dirty   : BTreeSet::new(),
+            biomes  : BiomeStorage::Single(BIOME_DEFAULT)
         }
     }
 
     pub fn iter(&self) -> impl Iterator<Item = RegEntry<BlockState>> {
-        self.runs.iter().flat_map(|run| iter::repeat_n(run.1, run.0 as usize))
+        (0..4096).map(|linear_xyz| self.get(linear_xyz))
     }
 
     pub fn checked_get(&self, linear_xyz : u16) -> Option<RegEntry<BlockState>> {
-        self.iter().nth(linear_xyz as usize)
+        if (linear_xyz >= 4096) { return None; }
+        Some(match (&self.storage) {
+            SectionStorage::Single(block)                     => *block,
+            SectionStorage::Indirect { palette, indices, .. } => palette[indices[linear_xyz as usize] as usize],
+            SectionStorage::Direct(blocks)                    => blocks[linear_xyz as usize]
+        })
     }
 
     #[inline]
@@ -51,6 +98,35 @@ impl ChunkSection {
         self.get(in_section_block_linearise(dx, dy, dz))
     }
 
+    /// Returns the biome of the 4x4x4 cell containing the given block.
+    pub fn get_biome_xyz(&self, dx : u8, dy : u8, dz : u8) -> RegEntry<Biome> {
+        match (&self.biomes) {
+            BiomeStorage::Single(biome) => *biome,
+            BiomeStorage::Grid(biomes)  => biomes[in_section_biome_linearise(dx, dy, dz) as usize]
+        }
+    }
+
+    /// Returns the number of non-air blocks in this section.
+    #[inline]
+    pub fn block_count(&self) -> u16 { self.non_air }
+
+    #[inline]
+    pub fn is_dirty(&self) -> bool { ! self.dirty.is_empty() }
+
+    /// Returns the number of blocks changed since the last update was sent.
+    #[inline]
+    pub fn dirty_count(&self) -> usize { self.dirty.len() }
+
+    /// Returns the approximate encoded size of the update packet for this section, in bytes.
+    pub(crate) fn ptc_update_section_size(&self) -> usize {
+        match (self.dirty.len()) {
+            0 => 0,
+            1 => 12,
+            // Section position, length prefix, and one varlong per block.
+            n => 8 + 3 + (n * 5)
+        }
+    }
+
     pub fn clear_dirty(&mut self) {
         self.dirty.clear();
     }
@@ -59,68 +135,204 @@ impl ChunkSection {
 
 impl ChunkSection {
 
-    // Sets the block state of the given run.
-    pub(crate) fn overwrite_run_state(&mut self, run_index : u16, block : RegEntry<BlockState>) {
-        self.runs[run_index as usize].1 = block;
-        self.dirty.insert(run_index);
+    /// Sets the block state at the given index, returning the previous block state.
+    pub(crate) fn set(&mut self, linear_xyz : u16, block : RegEntry<BlockState>) -> RegEntry<BlockState> {
+        let i   = linear_xyz as usize;
+        let old = match (&mut self.storage) {
+
+            SectionStorage::Single(current) => {
+                let current = *current;
+                if (current == block) { return current; }
+                let mut indices = Box::new([0u8; 4096]);
+                indices[i] = 1;
+                self.storage = SectionStorage::Indirect {
+                    palette : vec![current, block],
+                    counts  : vec![4095, 1],
+                    indices
+                };
+                current
+            },
+
+            SectionStorage::Indirect { palette, counts, indices } => {
+                let old_index = indices[i] as usize;
+                let old       = palette[old_index];
+                if (old == block) { return old; }
+                let new_index = if let Some(index) = palette.iter().position(|&entry| entry == block) {
+                    Some(index)
+                } else if let Some(index) = counts.iter().position(|&count| count == 0) {
+                    palette[index] = block;
+                    Some(index)
+                } else if (palette.len() < 256) {
+                    palette.push(block);
+                    counts.push(0);
+                    Some(palette.len() - 1)
+                } else { None };
+                if let Some(new_index) = new_index {
+                    counts[old_index] -= 1;
+                    counts[new_index] += 1;
+                    indices[i] = new_index as u8;
+                } else {
+                    // The palette is full. Switch to direct storage.
+                    let mut blocks = Box::new([BLOCK_AIR; 4096]);
+                    for (j, &index) in indices.iter().enumerate() {
+                        blocks[j] = palette[index as usize];
+                    }
+                    blocks[i] = block;
+                    self.storage = SectionStorage::Direct(blocks);
+                }
+                old
+            },
+
+            SectionStorage::Direct(blocks) => {
+                let old = blocks[i];
+                if (old == block) { return old; }
+                blocks[i] = block;
+                old
+            }
+
+        };
+        if (old.id() == 0) { self.non_air += 1; }
+        if (block.id() == 0) { self.non_air -= 1; }
+        self.dirty.insert(linear_xyz);
+        old
     }
 
-    // Splits out all of the runs in this section.
-    pub(super) fn split_out_all(&mut self) {
-        self.runs = self.runs.iter().flat_map(|run| iter::repeat_n((1, run.1), run.0 as usize)).collect();
-        debug_assert_eq!(self.runs.iter().map(|(len, _)| *len).sum::<u16>(), 4096);
+    /// Sets the biome of the 4x4x4 cell containing the given block, returning `true` if it changed.
+    pub(crate) fn set_biome_xyz(&mut self, dx : u8, dy : u8, dz : u8, biome : RegEntry<Biome>) -> bool {
+        let i = in_section_biome_linearise(dx, dy, dz) as usize;
+        match (&mut self.biomes) {
+            BiomeStorage::Single(current) => {
+                if (*current == biome) { return false; }
+                let mut biomes = Box::new([*current; 64]);
+                biomes[i] = biome;
+                self.biomes = BiomeStorage::Grid(biomes);
+            },
+            BiomeStorage::Grid(biomes) => {
+                if (biomes[i] == biome) { return false; }
+                biomes[i] = biome;
+                if (biomes.iter().all(|&b| b == biome)) {
+                    self.biomes = BiomeStorage::Single(biome);
+                }
+            }
+        }
+        true
     }
 
-    // Makes this section as small as possible.
+    /// Makes this section as small as possible.
     pub(super) fn collapse(&mut self) {
-        for i in (0..(self.runs.len() - 1)).rev() {
-            // Merge runs of the same block state.
-            if (self.runs[i].1 == self.runs[i + 1].1) {
-                self.runs[i].0 += self.runs[i + 1].0;
-                self.runs.remove(i + 1);
+        let mut counts = BTreeMap::<u32, u16>::new();
+        match (&self.storage) {
+            SectionStorage::Single(_) => { return; },
+            SectionStorage::Indirect { palette, counts : palette_counts, .. } => {
+                for (&entry, &count) in palette.iter().zip(palette_counts) {
+                    if (count > 0) { *counts.entry(entry.id()).or_default() += count; }
+                }
+            },
+            SectionStorage::Direct(blocks) => {
+                for &entry in blocks.iter() {
+                    *counts.entry(entry.id()).or_default() += 1;
+                }
             }
-            // Remove empty runs.
-            if (self.runs[i].0 == 0) {
-                self.runs.remove(i);
+        }
+        if (counts.len() == 1) {
+            let id = *counts.keys().next().unwrap();
+            self.storage = SectionStorage::Single(unsafe { RegEntry::new_unchecked(id) });
+        } else if (counts.len() <= 256) {
+            let     ids     = counts.keys().cloned().collect::<Vec<_>>();
+            let mut indices = Box::new([0u8; 4096]);
+            for (linear_xyz, index) in indices.iter_mut().enumerate() {
+                let block = self.get(linear_xyz as u16);
+                *index = ids.binary_search(&block.id()).unwrap() as u8;
             }
+            self.storage = SectionStorage::Indirect {
+                palette : ids.into_iter().map(|id| unsafe { RegEntry::new_unchecked(id) }).collect(),
+                counts  : counts.into_values().collect(),
+                indices
+            };
         }
-        self.runs.shrink_to_fit();
-        debug_assert_eq!(self.runs.iter().map(|(len, _)| *len).sum::<u16>(), 4096);
     }
 
 }
 
 impl ChunkSection {
 
-    pub(super) fn ptc_chunk_section(&self) -> PtcChunkSection {
-        let mut block_count = 0;
-        let block_states;
-        if (self.runs.len() == 1) {
-            block_count  = if (self.runs[0].1.id() != 0) { 4096 } else { 0 };
-            block_states = PalettedContainer {
+    pub(super) fn ptc_chunk_section(&self, biome_direct_bits : u8) -> PtcChunkSection {
+        let block_states = match (&self.storage) {
+
+            SectionStorage::Single(block) => PalettedContainer {
                 bits_per_entry : 0,
-                format         : PaletteFormat::SingleValued { entry : self.runs[0].1 }
-            };
-        } else {
-            block_states = PalettedContainer {
-                bits_per_entry : 15,
-                format         : PaletteFormat::Direct { data : {
-                    let mut data = [unsafe{ RegEntry::new_unchecked(0) }; 4096];
-                    for (i, block) in self.runs.iter().flat_map(|run| iter::repeat_n(run.1, run.0 as usize)).enumerate() {
-                        if (block.id() != 0) { block_count += 1; }
-                        data[i] = block;
+                format         : PaletteFormat::SingleValued { entry : *block }
+            },
+
+            SectionStorage::Indirect { palette, counts, indices } => {
+                // Drop unused palette entries before encoding.
+                let mut remap = [0u8; 256];
+                let mut used  = Vec::with_capacity(palette.len());
+                for (i, (&entry, &count)) in palette.iter().zip(counts).enumerate() {
+                    if (count > 0) {
+                        remap[i] = used.len() as u8;
+                        used.push(entry);
+                    }
+                }
+                if (used.len() == 1) {
+                    PalettedContainer {
+                        bits_per_entry : 0,
+                        format         : PaletteFormat::SingleValued { entry : used[0] }
+                    }
+                } else {
+                    // The palette never holds more than 256 entries.
+                    let bits_per_entry = indirect_bits_per_entry(used.len());
+                    debug_assert!(bits_per_entry <= MAX_INDIRECT_BITS);
+                    PalettedContainer {
+                        bits_per_entry,
+                        format         : PaletteFormat::Indirect {
+                            palette : used,
+                            data    : array::from_fn(|i| remap[indices[i] as usize])
+                        }
                     }
-                    data
-                } }
+                }
+            },
+
+            SectionStorage::Direct(blocks) => PalettedContainer {
+                bits_per_entry : DIRECT_BITS,
+                format         : PaletteFormat::Direct { data : **blocks }
             }
+
         };
         PtcChunkSection {
-            block_count,
+            block_count  : self.non_air,
             block_states,
-            biomes       : PalettedContainer {
+            biomes       : self.ptc_biomes(biome_direct_bits)
+        }
+    }
+
+    pub(super) fn ptc_biomes(&self, direct_bits : u8) -> PalettedContainer<Biome> {
+        match (&self.biomes) {
+            BiomeStorage::Single(biome) => PalettedContainer {
                 bits_per_entry : 0,
-                format         : PaletteFormat::SingleValued { entry : unsafe { RegEntry::new_unchecked(0) } }
+                format         : PaletteFormat::SingleValued { entry : *biome }
             },
+            BiomeStorage::Grid(biomes) => {
+                let mut palette = Vec::new();
+                for &biome in biomes.iter() {
+                    if (! palette.contains(&biome)) { palette.push(biome); }
+                }
+                let bits_per_entry = bits_for_len(palette.len()).max(1);
+                if (bits_per_entry > MAX_INDIRECT_BIOME_BITS) {
+                    PalettedContainer {
+                        bits_per_entry : direct_bits,
+                        format         : PaletteFormat::Direct { data : **biomes }
+                    }
+                } else {
+                    PalettedContainer {
+                        bits_per_entry,
+                        format         : PaletteFormat::Indirect {
+                            data    : array::from_fn(|i| palette.iter().position(|&b| b == biomes[i]).unwrap() as u8),
+                            palette
+                        }
+                    }
+                }
+            }
         }
     }
 
@@ -159,27 +371,151 @@ impl ChunkSection {
 }
 
 
-pub struct SectionIter<'l> {
-    iter          : iter::Cloned<slice::Iter<'l, u8>>,
-    current_run   : u16,
-    current_block : RegEntry<BlockState>
+/// Entry points for the benchmarks, which can only reach public items.
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+impl ChunkSection {
+
+    #[inline]
+    pub fn bench_set(&mut self, linear_xyz : u16, block : RegEntry<BlockState>) -> RegEntry<BlockState> {
+        self.set(linear_xyz, block)
+    }
+
+    #[inline]
+    pub fn bench_ptc_chunk_section(&self, biome_direct_bits : u8) -> PtcChunkSection {
+        self.ptc_chunk_section(biome_direct_bits)
+    }
+
 }
 
-impl<'l> Iterator for SectionIter<'l> {
 
-    type Item = RegEntry<BlockState>;
+/// Returns the smallest bits-per-entry able to index a block palette of `len` entries.
+fn indirect_bits_per_entry(len : usize) -> u8 {
+    bits_for_len(len).max(MIN_INDIRECT_BITS)
+}
+
+/// Returns the number of bits needed to index `len` distinct values.
+pub(crate) fn bits_for_len(len : usize) -> u8 {
+    (usize::BITS - (len.max(1) - 1).leading_zeros()) as u8
+}
+
+fn in_section_biome_linearise(dx : u8, dy : u8, dz : u8) -> u8 {
+    (((dy >> 2) & 0b11) << 4)
+    | (((dz >> 2) & 0b11) << 2)
+    | ((dx >> 2) & 0b11)
+}
+
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if (self.current_run > 0) {
-                self.current_run -= 1;
-                return Some(self.current_block);
-            } else {
-                self.current_run = u16::from_ne_bytes([self.iter.next()?, self.iter.next()?]);
-                let (entry, _,) = Var32::decode_iter(&mut self.iter).ok()?;
-                self.current_block = unsafe { RegEntry::new_unchecked(entry.as_i32().cast_unsigned()) };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::in_section_block_linearise;
+
+    fn block(id : u32) -> RegEntry<BlockState> {
+        unsafe { RegEntry::new_unchecked(id) }
+    }
+
+    /// A xorshift generator, so that failures can be reproduced.
+    fn random(state : &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    /// Decodes an update packet into `(x, y, z, block id)` entries.
+    fn decode_update(packet : S2CPlayPackets) -> Vec<(i32, i32, i32, u32,)> {
+        match (packet) {
+            S2CPlayPackets::BlockUpdate(BlockUpdateS2CPlayPacket { pos, block }) => vec![(pos.x, pos.y, pos.z, block.id(),)],
+            S2CPlayPackets::SectionBlocksUpdate(SectionBlocksUpdateS2CPlayPacket { chunk_section, blocks }) => {
+                blocks.iter().map(|&entry| {
+                    let entry = i64::from(entry) as u64;
+                    (
+                        (chunk_section.x * 16) + (((entry >> 8) & 0b1111) as i32),
+                        (chunk_section.y * 16) + ((entry & 0b1111) as i32),
+                        (chunk_section.z * 16) + (((entry >> 4) & 0b1111) as i32),
+                        (entry >> 12) as u32,
+                    )
+                }).collect()
+            },
+            _ => panic!("not a block update packet")
+        }
+    }
+
+    #[test]
+    fn update_single_block() {
+        let mut section = ChunkSection::empty();
+        section.set(in_section_block_linearise(3, 15, 0), block(7));
+        // The lowest section of a world with a `min_y` of `-64`.
+        let update = decode_update(section.ptc_update_section([-1, -4, -2]).unwrap());
+        assert_eq!(update, vec![(-13, -49, -32, 7,)]);
+    }
+
+    #[test]
+    fn update_many_blocks() {
+        let     changes = [((0, 0, 0,), 1,), ((15, 15, 15,), 2,), ((1, 2, 3,), 3,), ((15, 0, 9,), 4,)];
+        let mut section = ChunkSection::empty();
+        for &((dx, dy, dz,), id,) in &changes {
+            section.set(in_section_block_linearise(dx, dy, dz), block(id));
+        }
+        for [cx, cy, cz] in [[-1, -4, -2], [i32::MIN / 16, -127, 5], [0, 0, 0]] {
+            let mut update = decode_update(section.ptc_update_section([cx, cy, cz]).unwrap());
+            update.sort();
+            let mut expected = changes.iter().map(|&((dx, dy, dz,), id,)| (
+                (cx * 16) + (dx as i32),
+                (cy * 16) + (dy as i32),
+                (cz * 16) + (dz as i32),
+                id,
+            )).collect::<Vec<_>>();
+            expected.sort();
+            assert_eq!(update, expected);
+        }
+    }
+
+    #[test]
+    fn update_nothing_when_clean() {
+        let mut section = ChunkSection::empty();
+        section.set(0, block(1));
+        section.clear_dirty();
+        assert!(section.ptc_update_section([0, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn collapse_keeps_blocks() {
+        let mut state = 0x9E3779B97F4A7C15;
+        // Few enough kinds for an indirect palette, and enough for direct storage.
+        for kinds in [1, 2, 17, 256, 300] {
+            let mut section  = ChunkSection::empty();
+            let mut expected = [0u32; 4096];
+            for _ in 0..20000 {
+                let linear_xyz = (random(&mut state) % 4096) as u16;
+                let id         = (random(&mut state) % kinds) as u32;
+                section.set(linear_xyz, block(id));
+                expected[linear_xyz as usize] = id;
             }
+            section.collapse();
+            for (linear_xyz, &id) in expected.iter().enumerate() {
+                assert_eq!(section.get(linear_xyz as u16).id(), id);
+            }
+            assert_eq!(section.block_count() as usize, expected.iter().filter(|&&id| id != 0).count());
+            // Collapsing twice changes nothing.
+            section.collapse();
+            assert!(section.iter().map(|entry| entry.id()).eq(expected.iter().copied()));
+        }
+    }
+
+    #[test]
+    fn collapse_to_single() {
+        let mut section = ChunkSection::empty();
+        for linear_xyz in 0..4096 {
+            section.set(linear_xyz, block(((linear_xyz % 300) + 1) as u32));
+        }
+        for linear_xyz in 0..4096 {
+            section.set(linear_xyz, block(5));
         }
+        section.collapse();
+        assert!(matches!(section.storage, SectionStorage::Single(entry) if entry.id() == 5));
+        assert!(section.iter().all(|entry| entry.id() == 5));
     }
 
 }
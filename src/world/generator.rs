@@ -0,0 +1,236 @@
+use crate::world::{ World, Chunk, ChunkSection, Block, PlaceRejection, BLOCK_AIR, in_section_block_linearise, state_entry };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Identifier, BlockState, Biome };
+use protocol::registry::RegEntry;
+use std::sync::Arc;
+
+
+/// Fills new chunks of a [`World`].
+///
+/// Generators are called off the main thread, once for each chunk position
+///  which is not already loaded or stored. Worlds without a generator fire
+///  [`WorldChunkLoading`](crate::world::WorldChunkLoading) instead.
+pub trait ChunkGenerator : Send + Sync + 'static {
+
+    /// Fills the chunk at `pos`.
+    fn generate(&self, pos : Vec2<i32>, chunk : &mut ProtoChunk);
+
+}
+
+
+/// A chunk which is being generated.
+pub struct ProtoChunk {
+    sections : Vec<ChunkSection>
+}
+
+impl ProtoChunk {
+
+    fn new(section_count : usize) -> Self { Self {
+        sections : vec![ChunkSection::empty(); section_count]
+    } }
+
+    /// Returns the number of blocks from the bottom to the top of this chunk.
+    #[inline]
+    pub fn height(&self) -> u32 { (self.sections.len() as u32) * 16 }
+
+    /// Sets the block at chunk-local `x`, `y`, `z`. Blocks outside of the chunk are ignored.
+    ///
+    /// `y` counts up from the bottom of the world, not from Y 0.
+    pub fn set_block(&mut self, x : u8, y : u32, z : u8, block : RegEntry<BlockState>) {
+        if (x < 16 && z < 16)
+            && let Some(section) = self.sections.get_mut((y / 16) as usize)
+        {
+            section.set(in_section_block_linearise(x, y as u8, z), block);
+        }
+    }
+
+    /// Sets every block in the horizontal layer at chunk-local `y`.
+    pub fn fill_layer(&mut self, y : u32, block : RegEntry<BlockState>) {
+        for z in 0..16 {
+            for x in 0..16 {
+                self.set_block(x, y, z, block);
+            }
+        }
+    }
+
+    /// Sets the biome of the 4x4x4 cell containing chunk-local `x`, `y`, `z`.
+    pub fn set_biome(&mut self, x : u8, y : u32, z : u8, biome : RegEntry<Biome>) {
+        if let Some(section) = self.sections.get_mut((y / 16) as usize) {
+            section.set_biome_xyz(x & 0b1111, (y as u8) & 0b1111, z & 0b1111, biome);
+        }
+    }
+
+    /// Sets the biome of the whole chunk.
+    pub fn fill_biome(&mut self, biome : RegEntry<Biome>) {
+        for section in &mut self.sections {
+            for i in 0..64u8 {
+                section.set_biome_xyz((i & 0b11) << 2, (i >> 4) << 2, ((i >> 2) & 0b11) << 2, biome);
+            }
+        }
+    }
+
+}
+
+
+/// Leaves every chunk empty.
+pub struct VoidGenerator;
+
+impl ChunkGenerator for VoidGenerator {
+    fn generate(&self, _pos : Vec2<i32>, _chunk : &mut ProtoChunk) { }
+}
+
+
+/// Fills every chunk with a single block up to a fixed height.
+pub struct FlatGenerator {
+    block  : RegEntry<BlockState>,
+    height : u32
+}
+
+impl FlatGenerator {
+
+    /// Fills the bottom `height` layers of every chunk with `block`.
+    pub fn new(block : impl Into<Block>, height : u32) -> Result<Self, PlaceRejection> {
+        Ok(Self { block : block.into().to_entry()?, height })
+    }
+
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, _pos : Vec2<i32>, chunk : &mut ProtoChunk) {
+        for y in 0..self.height.min(chunk.height()) {
+            chunk.fill_layer(y, self.block);
+        }
+    }
+}
+
+
+/// Fills every chunk with layers of blocks, like a superflat world.
+pub struct LayeredGenerator {
+    /// Layers from the bottom up, with their thickness.
+    layers : Vec<(RegEntry<BlockState>, u32,)>,
+    biome  : Option<RegEntry<Biome>>
+}
+
+impl LayeredGenerator {
+
+    /// Stacks `layers` from the bottom of the world up. Each layer is given with its thickness.
+    pub fn new<I, B>(layers : I) -> Result<Self, PlaceRejection>
+    where
+        I : IntoIterator<Item = (B, u32,)>,
+        B : Into<Block>
+    {
+        let layers = layers.into_iter()
+            .map(|(block, thickness,)| Ok((block.into().to_entry()?, thickness,)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { layers, biome : None })
+    }
+
+    /// The classic superflat preset: bedrock, two layers of dirt, then grass.
+    pub fn classic() -> Self {
+        let block = |name : &str| BlockState::default_for(&Identifier::from(name))
+            .and_then(|state| state_entry(&state))
+            .unwrap_or(BLOCK_AIR);
+        Self {
+            layers : vec![
+                (block("minecraft:bedrock"),     1,),
+                (block("minecraft:dirt"),        2,),
+                (block("minecraft:grass_block"), 1,)
+            ],
+            biome  : None
+        }
+    }
+
+    /// Sets the biome of every generated chunk.
+    pub fn biome(mut self, biome : RegEntry<Biome>) -> Self {
+        self.biome = Some(biome);
+        self
+    }
+
+}
+
+impl ChunkGenerator for LayeredGenerator {
+    fn generate(&self, _pos : Vec2<i32>, chunk : &mut ProtoChunk) {
+        let mut y = 0;
+        for &(block, thickness,) in &self.layers {
+            for _ in 0..thickness {
+                if (y >= chunk.height()) { break; }
+                chunk.fill_layer(y, block);
+                y += 1;
+            }
+        }
+        if let Some(biome) = self.biome {
+            chunk.fill_biome(biome);
+        }
+    }
+}
+
+
+impl World {
+
+    /// Generates new chunks of this world with `generator`.
+    pub fn with_generator(mut self, generator : impl ChunkGenerator) -> Self {
+        self.generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Starts generating the chunk at `pos` off the main thread.
+    ///
+    /// Returns `false` if this world has no generator.
+    pub(crate) fn start_generating(&mut self, pos : Vec2<i32>) -> bool {
+        let Some(generator) = self.generator.clone()
+            else { return false; };
+        let Some(chunk) = self.chunks.get(&pos)
+            else { return false; };
+        let section_count = chunk.sections.len();
+        self.generating.insert(pos, ManuallyPoll::new(async move {
+            smol::unblock(move || {
+                let mut proto = ProtoChunk::new(section_count);
+                generator.generate(pos, &mut proto);
+                proto
+            }).await
+        }));
+        true
+    }
+
+}
+
+impl Chunk {
+
+    /// Replaces the blocks and biomes of this chunk with a generated chunk, and marks it ready.
+    ///
+    /// Blocks and biomes set while the chunk was generating are kept. The chunk
+    ///  should be lit with [`World::light_chunk`] afterwards.
+    fn apply_generated(&mut self, proto : ProtoChunk) {
+        self.sections = proto.sections;
+        self.apply_early_edits();
+        self.ready   = true;
+        self.unsaved = true;
+        self.compute_heightmaps();
+    }
+
+}
+
+
+pub(crate) fn finish_generated_chunks(
+    mut q_worlds : Query<(&mut World,)>
+) {
+    for (mut world,) in &mut q_worlds {
+        let world = &mut *world;
+        let mut finished = Vec::new();
+        for (&pos, fut,) in &mut world.generating {
+            if let Poll::Ready(proto) = fut.poll() {
+                finished.push((pos, proto,));
+            }
+        }
+        for (pos, proto,) in finished {
+            world.generating.remove(&pos);
+            if let Some(chunk) = world.chunks.get_mut(&pos)
+                && (! chunk.ready)
+            {
+                trace!("Generated chunk <{}, {}>", pos.x, pos.y);
+                chunk.apply_generated(proto);
+                world.light_chunk(pos);
+            }
+        }
+    }
+}
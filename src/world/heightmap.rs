@@ -0,0 +1,118 @@
+use crate::world::{ Chunk, BlockProps, bits_for_len };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ BlockState, Nbt, NbtElement };
+use protocol::registry::RegEntry;
+
+
+/// The heightmaps of a chunk.
+///
+/// Each entry is one more than the chunk-local Y of the highest matching
+///  block in its column, or 0 if the column has none.
+#[derive(Clone)]
+pub(crate) struct Heightmaps {
+    motion_blocking : [u16; 256],
+    world_surface   : [u16; 256]
+}
+
+impl Heightmaps {
+
+    pub(crate) const EMPTY : Self = Self {
+        motion_blocking : [0; 256],
+        world_surface   : [0; 256]
+    };
+
+}
+
+
+impl Chunk {
+
+    /// Returns the world Y of the lowest block above every motion-blocking block (solid or fluid) in a column.
+    ///
+    /// `x` and `z` are local to this chunk, wrapping at 16.
+    pub fn height_at(&self, x : u8, z : u8) -> i32 {
+        self.min_y() + (self.heightmaps.motion_blocking[column_index(x, z)] as i32)
+    }
+
+    /// Returns the world Y of the lowest block above every non-air block in a column.
+    ///
+    /// `x` and `z` are local to this chunk, wrapping at 16.
+    pub fn surface_height_at(&self, x : u8, z : u8) -> i32 {
+        self.min_y() + (self.heightmaps.world_surface[column_index(x, z)] as i32)
+    }
+
+    fn block_at_local_y(&self, x : u8, y : usize, z : u8) -> RegEntry<BlockState> {
+        self.sections[y / 16].get_xyz(x, (y % 16) as u8, z)
+    }
+
+    /// Recomputes every column of the heightmaps.
+    pub(crate) fn compute_heightmaps(&mut self) {
+        for z in 0..16 {
+            for x in 0..16 {
+                self.rescan_column(x, z, self.sections.len() * 16);
+            }
+        }
+    }
+
+    /// Updates the heightmaps after the block at chunk-local `(x, y, z)` changed to `block`.
+    pub(crate) fn update_heightmaps(&mut self, x : u8, y : usize, z : u8, block : RegEntry<BlockState>) {
+        let i     = column_index(x, z);
+        let props = BlockProps::of(block);
+        let above = (y + 1) as u16;
+        let motion_blocking = self.heightmaps.motion_blocking[i];
+        let world_surface   = self.heightmaps.world_surface[i];
+        if (props.blocks_motion) {
+            if (above > motion_blocking) { self.heightmaps.motion_blocking[i] = above; }
+        } else if (above == motion_blocking) {
+            self.heightmaps.motion_blocking[i] = self.scan_down(x, z, y, |props| props.blocks_motion);
+        }
+        if (! props.is_air) {
+            if (above > world_surface) { self.heightmaps.world_surface[i] = above; }
+        } else if (above == world_surface) {
+            self.heightmaps.world_surface[i] = self.scan_down(x, z, y, |props| ! props.is_air);
+        }
+    }
+
+    fn rescan_column(&mut self, x : u8, z : u8, top : usize) {
+        let i = column_index(x, z);
+        self.heightmaps.motion_blocking[i] = self.scan_down(x, z, top, |props| props.blocks_motion);
+        self.heightmaps.world_surface[i]   = self.scan_down(x, z, top, |props| ! props.is_air);
+    }
+
+    /// Returns one more than the highest chunk-local Y below `below` matching `f`, or 0.
+    fn scan_down<F>(&self, x : u8, z : u8, below : usize, f : F) -> u16
+    where
+        F : Fn(&BlockProps) -> bool
+    {
+        for y in (0..below).rev() {
+            if (f(&BlockProps::of(self.block_at_local_y(x, y, z)))) {
+                return (y + 1) as u16;
+            }
+        }
+        0
+    }
+
+    pub(crate) fn ptc_heightmaps(&self) -> Nbt {
+        let bits = bits_for_len(self.sections.len() * 16 + 1);
+        let mut nbt = Nbt::new();
+        nbt.insert("MOTION_BLOCKING", NbtElement::LongArray(pack_heightmap(&self.heightmaps.motion_blocking, bits)));
+        nbt.insert("WORLD_SURFACE", NbtElement::LongArray(pack_heightmap(&self.heightmaps.world_surface, bits)));
+        nbt
+    }
+
+}
+
+
+#[inline]
+fn column_index(x : u8, z : u8) -> usize {
+    (((z & 0b00001111) as usize) << 4) | ((x & 0b00001111) as usize)
+}
+
+/// Packs heightmap entries into longs, without entries spanning two longs.
+fn pack_heightmap(heights : &[u16; 256], bits : u8) -> Vec<i64> {
+    let per_long = 64 / (bits as usize);
+    let mut data = vec![0u64; heights.len().div_ceil(per_long)];
+    for (i, &height) in heights.iter().enumerate() {
+        data[i / per_long] |= (height as u64) << ((i % per_long) * (bits as usize));
+    }
+    data.into_iter().map(u64::cast_signed).collect()
+}
@@ -0,0 +1,398 @@
+use crate::world::{ World, Chunk, ChunkSection, BlockProps };
+use flywheelmc_common::prelude::*;
+use protocol::value::BlockPos;
+use protocol::packet::s2c::play::{
+    LightUpdateS2CPlayPacket,
+    LightMask
+};
+
+
+/// The light levels of one section, stored as nibbles.
+#[derive(Clone)]
+pub(crate) enum LightNibbles {
+    Uniform(u8),
+    Array(Box<[u8; 2048]>)
+}
+
+impl LightNibbles {
+
+    fn from_levels(levels : &[u8]) -> Self {
+        debug_assert_eq!(levels.len(), 4096);
+        let first = levels[0];
+        if (levels.iter().all(|&level| level == first)) {
+            return Self::Uniform(first);
+        }
+        let mut nibbles = Box::new([0u8; 2048]);
+        for (i, pair) in levels.chunks_exact(2).enumerate() {
+            nibbles[i] = (pair[0] & 0x0F) | ((pair[1] & 0x0F) << 4);
+        }
+        Self::Array(nibbles)
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool { matches!(self, Self::Uniform(0)) }
+
+    /// Returns the level at `i`, indexed like a section.
+    fn get(&self, i : usize) -> u8 {
+        match (self) {
+            Self::Uniform(level) => *level,
+            Self::Array(nibbles) => (nibbles[i / 2] >> ((i % 2) * 4)) & 0x0F
+        }
+    }
+
+    fn set(&mut self, i : usize, level : u8) {
+        if let Self::Uniform(uniform) = *self {
+            if (uniform == level) { return; }
+            *self = Self::Array(Box::new([(uniform & 0x0F) | ((uniform & 0x0F) << 4); 2048]));
+        }
+        if let Self::Array(nibbles) = self {
+            let shift = (i % 2) * 4;
+            nibbles[i / 2] = (nibbles[i / 2] & !(0x0F << shift)) | ((level & 0x0F) << shift);
+        }
+    }
+
+    fn ptc_light_mask(&self) -> LightMask {
+        LightMask { light_array : match (self) {
+            Self::Uniform(level) => vec![(level & 0x0F) | ((level & 0x0F) << 4); 2048],
+            Self::Array(nibbles) => nibbles.to_vec()
+        }.into() }
+    }
+
+}
+
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LightKind {
+    Sky,
+    Block
+}
+
+
+/// The sky and block light of a chunk.
+///
+/// There is one entry per section, plus one below and one above the world.
+#[derive(Clone)]
+pub(crate) struct ChunkLight {
+    sky   : Vec<LightNibbles>,
+    block : Vec<LightNibbles>
+}
+
+/// The light fields of the chunk and light-update packets.
+pub(crate) struct PtcLightData {
+    pub(crate) sky_light_mask         : Vec<u64>,
+    pub(crate) block_light_mask       : Vec<u64>,
+    pub(crate) empty_sky_light_mask   : Vec<u64>,
+    pub(crate) empty_block_light_mask : Vec<u64>,
+    pub(crate) sky_light_array        : Vec<LightMask>,
+    pub(crate) block_light_array      : Vec<LightMask>
+}
+
+impl ChunkLight {
+
+    /// Computes the light of a chunk from its blocks.
+    ///
+    /// Light does not spread into neighbouring chunks. Use [`World::light_chunk`] for that.
+    pub(crate) fn compute(sections : &[ChunkSection], has_skylight : bool) -> Self {
+        let height  = (sections.len() + 2) * 16;
+        let index   = |x : usize, y : usize, z : usize| (y << 8) | (z << 4) | x;
+        let mut opacity  = vec![0u8; 256 * height];
+        let mut emission = vec![0u8; 256 * height];
+        for (cy, section) in sections.iter().enumerate() {
+            let base = (cy + 1) * 4096;
+            for (linear_xyz, block) in section.iter().enumerate() {
+                let props = BlockProps::of(block);
+                opacity[base + linear_xyz]  = props.opacity;
+                emission[base + linear_xyz] = props.emission;
+            }
+        }
+
+        // Sky light falls straight down until blocked, then spreads.
+        let mut sky = vec![0u8; 256 * height];
+        if (has_skylight) {
+            let mut queue = VecDeque::new();
+            for z in 0..16 {
+                for x in 0..16 {
+                    let mut level = 15u8;
+                    for y in (0..height).rev() {
+                        let i = index(x, y, z);
+                        level = propagated(level, opacity[i], true);
+                        if (level == 0) { break; }
+                        sky[i] = level;
+                        queue.push_back(i);
+                    }
+                }
+            }
+            spread_light(&mut sky, &opacity, queue, height, true);
+        }
+
+        // Block light spreads out from emissive blocks.
+        let mut block = vec![0u8; 256 * height];
+        let mut queue = VecDeque::new();
+        for (i, &level) in emission.iter().enumerate() {
+            if (level > 0) {
+                block[i] = level;
+                queue.push_back(i);
+            }
+        }
+        spread_light(&mut block, &opacity, queue, height, false);
+
+        Self {
+            sky   : sky.chunks_exact(4096).map(LightNibbles::from_levels).collect(),
+            block : block.chunks_exact(4096).map(LightNibbles::from_levels).collect()
+        }
+    }
+
+    fn nibbles(&self, kind : LightKind) -> &[LightNibbles] {
+        match (kind) {
+            LightKind::Sky   => &self.sky,
+            LightKind::Block => &self.block
+        }
+    }
+
+    fn nibbles_mut(&mut self, kind : LightKind) -> &mut [LightNibbles] {
+        match (kind) {
+            LightKind::Sky   => &mut self.sky,
+            LightKind::Block => &mut self.block
+        }
+    }
+
+    pub(crate) fn ptc_light_data(&self) -> PtcLightData {
+        let mask_len = self.sky.len().div_ceil(64);
+        let mut data = PtcLightData {
+            sky_light_mask         : vec![0; mask_len],
+            block_light_mask       : vec![0; mask_len],
+            empty_sky_light_mask   : vec![0; mask_len],
+            empty_block_light_mask : vec![0; mask_len],
+            sky_light_array        : Vec::new(),
+            block_light_array      : Vec::new()
+        };
+        for (i, (sky, block,)) in self.sky.iter().zip(&self.block).enumerate() {
+            let bit = 1u64 << (i % 64);
+            if (sky.is_empty()) {
+                data.empty_sky_light_mask[i / 64] |= bit;
+            } else {
+                data.sky_light_mask[i / 64] |= bit;
+                data.sky_light_array.push(sky.ptc_light_mask());
+            }
+            if (block.is_empty()) {
+                data.empty_block_light_mask[i / 64] |= bit;
+            } else {
+                data.block_light_mask[i / 64] |= bit;
+                data.block_light_array.push(block.ptc_light_mask());
+            }
+        }
+        data
+    }
+
+}
+
+
+/// Returns the light level after passing from a block at `level` into a block with `opacity`.
+///
+/// Full sky light passes down through transparent blocks without dimming.
+fn propagated(level : u8, opacity : u8, sky_down : bool) -> u8 {
+    if (sky_down && level == 15 && opacity == 0) { 15 }
+    else { level.saturating_sub(opacity.max(1)) }
+}
+
+/// Spreads light outwards from every queued position, within a single chunk.
+fn spread_light(light : &mut [u8], opacity : &[u8], mut queue : VecDeque<usize>, height : usize, sky : bool) {
+    while let Some(i) = queue.pop_front() {
+        let level = light[i];
+        if (level == 0) { continue; }
+        let x = i & 0x0F;
+        let z = (i >> 4) & 0x0F;
+        let y = i >> 8;
+        let mut neighbours = [None; 6];
+        if (x > 0)          { neighbours[0] = Some(i - 1); }
+        if (x < 15)         { neighbours[1] = Some(i + 1); }
+        if (z > 0)          { neighbours[2] = Some(i - 16); }
+        if (z < 15)         { neighbours[3] = Some(i + 16); }
+        if (y > 0)          { neighbours[4] = Some(i - 256); }
+        if (y + 1 < height) { neighbours[5] = Some(i + 256); }
+        for (d, j) in neighbours.into_iter().enumerate() {
+            let Some(j) = j
+                else { continue; };
+            let next = propagated(level, opacity[j], sky && d == 4);
+            if (next > light[j]) {
+                light[j] = next;
+                queue.push_back(j);
+            }
+        }
+    }
+}
+
+/// The six neighbours of `pos`, and whether each is below it.
+fn neighbours(pos : BlockPos) -> [(BlockPos, bool,); 6] {
+    let BlockPos { x, y, z } = pos;
+    [
+        (BlockPos { x : x - 1, y, z }, false,),
+        (BlockPos { x : x + 1, y, z }, false,),
+        (BlockPos { x, y, z : z - 1 }, false,),
+        (BlockPos { x, y, z : z + 1 }, false,),
+        (BlockPos { x, y : y - 1, z }, true,),
+        (BlockPos { x, y : y + 1, z }, false,)
+    ]
+}
+
+
+impl World {
+
+    fn light_kinds(&self) -> &'static [LightKind] {
+        if (self.dim_type.has_skylight) { &[LightKind::Sky, LightKind::Block] }
+        else { &[LightKind::Block] }
+    }
+
+    /// Returns the chunk containing `pos`, and the position of `pos` in the chunk's light.
+    ///
+    /// The light of a chunk has a section of padding below and above the world.
+    fn locate_light(&self, pos : BlockPos) -> Option<(Vec2<i32>, usize, usize,)> {
+        let y = usize::try_from(pos.y - (self.min_y() - 16)).ok()?;
+        if (y >= (self.section_count() + 2) * 16) { return None; }
+        let i = ((y & 0x0F) << 8) | ((pos.z.rem_euclid(16) as usize) << 4) | (pos.x.rem_euclid(16) as usize);
+        Some((Vec2::new(pos.x.div_floor(16), pos.z.div_floor(16)), y >> 4, i,))
+    }
+
+    /// Returns the light at `pos`, or `None` if its chunk is not loaded and lit.
+    fn light_at(&self, kind : LightKind, pos : BlockPos) -> Option<u8> {
+        let (cpos, section, i,) = self.locate_light(pos)?;
+        let light = self.chunks.get(&cpos)?.light.as_ref()?;
+        Some(light.nibbles(kind)[section].get(i))
+    }
+
+    fn set_light_at(&mut self, kind : LightKind, pos : BlockPos, level : u8) {
+        let Some((cpos, section, i,)) = self.locate_light(pos)
+            else { return; };
+        if let Some(chunk) = self.chunks.get_mut(&cpos)
+            && let Some(light) = &mut chunk.light
+        {
+            light.nibbles_mut(kind)[section].set(i, level);
+            chunk.light_dirty = true;
+        }
+    }
+
+    /// Returns the properties of the block at `pos`. Blocks outside the world are air.
+    fn light_props_at(&self, pos : BlockPos) -> (u8, u8,) {
+        self.get_block_entry(pos).map_or((0, 0,), |block| {
+            let props = BlockProps::of(block);
+            (props.opacity, props.emission,)
+        })
+    }
+
+    /// Spreads light outwards from every queued position, across loaded chunks.
+    fn spread_world_light(&mut self, kind : LightKind, mut queue : VecDeque<BlockPos>) {
+        while let Some(pos) = queue.pop_front() {
+            let Some(level) = self.light_at(kind, pos)
+                else { continue; };
+            if (level == 0) { continue; }
+            for (next_pos, below,) in neighbours(pos) {
+                let Some(current) = self.light_at(kind, next_pos)
+                    else { continue; };
+                let next = propagated(level, self.light_props_at(next_pos).0, kind == LightKind::Sky && below);
+                if (next > current) {
+                    self.set_light_at(kind, next_pos, next);
+                    queue.push_back(next_pos);
+                }
+            }
+        }
+    }
+
+    /// Lights a chunk which has just become ready, and exchanges light with its loaded neighbours.
+    pub(crate) fn light_chunk(&mut self, cpos : Vec2<i32>) {
+        let has_skylight = self.dim_type.has_skylight;
+        let Some(chunk) = self.chunks.get_mut(&cpos)
+            else { return; };
+        chunk.light       = Some(ChunkLight::compute(&chunk.sections, has_skylight));
+        chunk.light_dirty = true;
+
+        // Spread from both sides of every edge of the chunk.
+        let min_y  = self.min_y() - 16;
+        let height = ((self.section_count() + 2) * 16) as i32;
+        let (x0, z0,) = (cpos.x * 16, cpos.y * 16,);
+        for &kind in self.light_kinds() {
+            let mut queue = VecDeque::new();
+            for y in min_y..(min_y + height) {
+                for i in 0..16 {
+                    for (x, z,) in [
+                        (x0, z0 + i,), (x0 - 1, z0 + i,), (x0 + 15, z0 + i,), (x0 + 16, z0 + i,),
+                        (x0 + i, z0,), (x0 + i, z0 - 1,), (x0 + i, z0 + 15,), (x0 + i, z0 + 16,)
+                    ] {
+                        let pos = BlockPos { x, y, z };
+                        if (self.light_at(kind, pos).is_some_and(|level| level > 0)) {
+                            queue.push_back(pos);
+                        }
+                    }
+                }
+            }
+            self.spread_world_light(kind, queue);
+        }
+    }
+
+    /// Updates light around blocks which changed, across loaded chunks.
+    ///
+    /// Light which came through the changed blocks is removed, then light is
+    ///  spread back in from what remains and from the new blocks.
+    pub(crate) fn relight_blocks(&mut self, changed : &[BlockPos]) {
+        for &kind in self.light_kinds() {
+            let mut decrease = VecDeque::new();
+            let mut increase = VecDeque::new();
+            for &pos in changed {
+                if let Some(level) = self.light_at(kind, pos) {
+                    self.set_light_at(kind, pos, 0);
+                    decrease.push_back((pos, level,));
+                }
+            }
+
+            while let Some((pos, level,)) = decrease.pop_front() {
+                for (next_pos, below,) in neighbours(pos) {
+                    let Some(current) = self.light_at(kind, next_pos)
+                        else { continue; };
+                    if (current == 0) { continue; }
+                    if (current < level || (kind == LightKind::Sky && below && level == 15 && current == 15)) {
+                        self.set_light_at(kind, next_pos, 0);
+                        decrease.push_back((next_pos, current,));
+                    } else {
+                        increase.push_back(next_pos);
+                    }
+                }
+            }
+
+            for &pos in changed {
+                if (kind == LightKind::Block) {
+                    let emission = self.light_props_at(pos).1;
+                    if (emission > 0) {
+                        self.set_light_at(kind, pos, emission);
+                        increase.push_back(pos);
+                    }
+                }
+                for (next_pos, _,) in neighbours(pos) {
+                    if (self.light_at(kind, next_pos).is_some_and(|level| level > 0)) {
+                        increase.push_back(next_pos);
+                    }
+                }
+            }
+
+            self.spread_world_light(kind, increase);
+        }
+    }
+
+}
+
+
+impl Chunk {
+
+    pub(crate) fn ptc_update_light(&self, pos : Vec2<i32>) -> Option<LightUpdateS2CPlayPacket> {
+        let light = self.light.as_ref()?.ptc_light_data();
+        Some(LightUpdateS2CPlayPacket {
+            chunk_x                : pos.x.into(),
+            chunk_z                : pos.y.into(),
+            sky_light_mask         : light.sky_light_mask.into(),
+            block_light_mask       : light.block_light_mask.into(),
+            empty_sky_light_mask   : light.empty_sky_light_mask.into(),
+            empty_block_light_mask : light.empty_block_light_mask.into(),
+            sky_light_array        : light.sky_light_array.into(),
+            block_light_array      : light.block_light_array.into()
+        })
+    }
+
+}
@@ -1,4 +1,4 @@
-use crate::MaxViewDistance;
+use crate::{ MaxViewDistance, ChunkUpdateBudget, DefaultDim, DefaultWorld, Registries };
 use crate::conn::Connection;
 use crate::conn::packet::{ PacketReadEvent, Packet };
 use crate::conn::play::ConnStatePlay;
@@ -9,16 +9,20 @@ use protocol::packet::c2s::config::{
 };
 use protocol::packet::c2s::play::{
     C2SPlayPackets,
-    ClientInformationC2SPlayPacket
+    ClientInformationC2SPlayPacket,
+    ChunkBatchReceivedC2SPlayPacket
 };
 use protocol::packet::s2c::play::{
     SetChunkCacheCenterS2CPlayPacket,
     SetChunkCacheRadiusS2CPlayPacket,
     LevelChunkWithLightS2CPlayPacket,
-    LightMask
+    ForgetLevelChunkS2CPlayPacket,
+    ChunkBatchStartS2CPlayPacket,
+    ChunkBatchFinishedS2CPlayPacket
 };
-use protocol::value::{ Identifier, BlockState, DimType, Nbt };
+use protocol::value::{ Identifier, BlockState, BlockPos, Biome, DimType };
 use protocol::registry::RegEntry;
+use std::sync::Arc;
 
 
 mod chunk;
@@ -27,6 +31,41 @@ pub use chunk::*;
 mod chunk_section;
 pub use chunk_section::*;
 
+mod query;
+
+mod biome;
+
+mod props;
+use props::*;
+pub(crate) use props::BlockProps;
+
+mod light;
+use light::*;
+
+mod heightmap;
+use heightmap::*;
+
+mod place;
+pub use place::*;
+
+mod block_entity;
+pub use block_entity::*;
+
+mod nbt;
+
+mod anvil;
+pub use anvil::AnvilStorage;
+pub(crate) use anvil::{ update_storage, flush_removed_world, flush_storage_on_exit };
+
+mod schematic;
+pub use schematic::*;
+
+mod generator;
+pub use generator::*;
+
+mod change;
+pub use change::*;
+
 mod setbatch;
 use setbatch::*;
 
@@ -34,7 +73,14 @@ mod action;
 pub use action::*;
 
 
-const BLOCK_AIR : RegEntry<BlockState> = unsafe { RegEntry::new_unchecked(0) };
+pub(crate) const BLOCK_AIR     : RegEntry<BlockState> = unsafe { RegEntry::new_unchecked(0) };
+pub(crate) const BIOME_DEFAULT : RegEntry<Biome>      = unsafe { RegEntry::new_unchecked(0) };
+
+/// Returns the registry entry of `state`, or `None` if it is not a known block state.
+pub(crate) fn state_entry(state : &BlockState) -> Option<RegEntry<BlockState>> {
+    // `to_id` only returns ids of the block state registry.
+    state.to_id().map(|id| unsafe { RegEntry::new_unchecked(id as u32) })
+}
 
 
 #[derive(Component)]
@@ -45,17 +91,249 @@ pub type ChunkCenter = ChunkCentre;
 pub struct ViewDistance(pub(crate) Ordered<NonZeroU8>);
 
 
+/// A shared world.
+///
+/// Worlds live on their own entities. Players join a world by having a
+///  [`PlayerInWorld`] component pointing at it. Chunks are shared between
+///  every player viewing them, and are unloaded once the last viewer leaves.
 #[derive(Component)]
 pub struct World {
-    #[expect(dead_code)]
-    pub(crate) dim_id       : Identifier,
-    pub(crate) dim_type     : DimType,
-    pub(crate) chunks       : BTreeMap<Vec2<i32>, Chunk>,
-    pub(crate) ready_chunks : VecDeque<Vec2<i32>>
+    pub(crate) dim_id     : Identifier,
+    pub(crate) dim_type   : DimType,
+    pub(crate) chunks     : BTreeMap<Vec2<i32>, Chunk>,
+    pub(crate) storage    : Option<AnvilStorage>,
+    /// Unloaded chunks waiting to be saved.
+    pub(crate) unloaded   : Vec<(Vec2<i32>, Chunk,)>,
+    pub(crate) generator  : Option<Arc<dyn ChunkGenerator>>,
+    /// Chunks being generated off the main thread.
+    pub(crate) generating : BTreeMap<Vec2<i32>, ManuallyPoll<'static, ProtoChunk>>,
+    /// The cause attributed to block changes currently being made.
+    change_cause          : BlockChangeCause,
+    /// Block changes in ready chunks waiting to be emitted as [`BlockChanged`] events.
+    block_changes         : Vec<(BlockPos, RegEntry<BlockState>, RegEntry<BlockState>, BlockChangeCause,)>,
+    /// The chunk at which sending block changes ran out of budget, and resumes from next tick.
+    update_cursor         : Vec2<i32>
+}
+
+impl World {
+
+    pub fn new(dim_id : Identifier, dim_type : DimType) -> Self { Self {
+        dim_id,
+        dim_type,
+        chunks        : BTreeMap::new(),
+        storage       : None,
+        unloaded      : Vec::new(),
+        generator     : None,
+        generating    : BTreeMap::new(),
+        change_cause  : BlockChangeCause::Direct,
+        block_changes : Vec::new(),
+        update_cursor : Vec2::new(0, 0)
+    } }
+
+    #[inline]
+    pub fn dim_id(&self) -> &Identifier { &self.dim_id }
+
+    #[inline]
+    pub fn dim_type(&self) -> &DimType { &self.dim_type }
+
+    /// Returns the world Y of the lowest block in this world.
+    #[inline]
+    pub fn min_y(&self) -> i32 { self.min_section() * 16 }
+
+    /// Returns the number of blocks from the bottom to the top of this world.
+    #[inline]
+    pub fn height(&self) -> u32 { (self.section_count() as u32) * 16 }
+
+    #[inline]
+    fn min_section(&self) -> i32 { self.dim_type.min_y.div_floor(16) }
+
+    #[inline]
+    fn section_count(&self) -> usize { (self.dim_type.height / 16).max(1) as usize }
+
+    /// Returns the chunk at `pos`, if it is loaded and ready.
+    pub fn chunk(&self, pos : Vec2<i32>) -> Option<&Chunk> {
+        self.chunks.get(&pos).filter(|chunk| chunk.ready)
+    }
+
 }
 
+impl World {
+
+    /// Adds `viewer` to the chunk at `pos`, creating the chunk if needed.
+    ///
+    /// Returns `true` if the chunk was newly created.
+    fn acquire_chunk(&mut self, pos : Vec2<i32>, viewer : Entity) -> bool {
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            chunk.viewers.insert(viewer);
+            false
+        } else {
+            let mut chunk = Chunk::empty(&self.dim_type);
+            chunk.viewers.insert(viewer);
+            self.chunks.insert(pos, chunk);
+            true
+        }
+    }
+
+    /// Removes `viewer` from the chunk at `pos`, unloading the chunk if it has no viewers left.
+    fn release_chunk(&mut self, pos : Vec2<i32>, viewer : Entity) {
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            chunk.viewers.remove(&viewer);
+            if (chunk.viewers.is_empty()) {
+                let chunk = self.chunks.remove(&pos).unwrap();
+                self.unload_chunk(pos, chunk);
+            }
+        }
+    }
+
+    /// Returns the position of every chunk, starting from where sending block changes last ran out of budget.
+    fn chunks_from_update_cursor(&self) -> Vec<Vec2<i32>> {
+        self.chunks.range(self.update_cursor..).chain(self.chunks.range(..self.update_cursor))
+            .map(|(&pos, _,)| pos)
+            .collect()
+    }
+
+    /// Removes `viewer` from every chunk in this world.
+    fn release_all_chunks(&mut self, viewer : Entity) {
+        let unused = self.chunks.iter_mut()
+            .filter_map(|(&pos, chunk,)| { chunk.viewers.remove(&viewer); chunk.viewers.is_empty().then_some(pos) })
+            .collect::<Vec<_>>();
+        for pos in unused {
+            let chunk = self.chunks.remove(&pos).unwrap();
+            self.unload_chunk(pos, chunk);
+        }
+    }
+
+    /// Queues an unloaded chunk to be saved, if it has unsaved changes, and
+    ///  stops loading or generating it.
+    fn unload_chunk(&mut self, pos : Vec2<i32>, chunk : Chunk) {
+        self.generating.remove(&pos);
+        if let Some(storage) = &mut self.storage {
+            storage.reading.remove(&pos);
+            if (chunk.needs_saving()) {
+                self.unloaded.push((pos, chunk,));
+            }
+        }
+    }
+
+}
+
+
+/// Places a player in a [`World`].
+///
+/// Removing this component, or pointing it at another world, unloads every
+///  chunk the player was viewing and discards their [`WorldOverlay`].
 #[derive(Component)]
-pub struct PlayerInWorld;
+pub struct PlayerInWorld(pub Entity);
+
+
+/// Per-player chunks which override the chunks of the shared [`World`].
+///
+/// [`WorldChunkAction`]s sent to a player with this component are applied to
+///  the player's own copy of the affected chunks. Chunks without an overlay
+///  are read from the shared world.
+///  Overlaid chunks are discarded once they leave the player's view distance.
+#[derive(Component, Default)]
+pub struct WorldOverlay {
+    pub(crate) world : Option<World>
+}
+
+impl WorldOverlay {
+
+    #[inline]
+    pub fn has_chunk(&self, pos : Vec2<i32>) -> bool {
+        self.world.as_ref().is_some_and(|world| world.chunks.contains_key(&pos))
+    }
+
+}
+
+
+/// The rate at which chunks are sent to a client before it first reports its own.
+const DEFAULT_CHUNKS_PER_TICK : f32 = 9.0;
+
+/// The length of a game tick, which the client's requested chunk rate is measured in.
+const TICK_DURATION : Duration = Duration::from_millis(50);
+
+/// The number of changed blocks in a chunk above which the whole chunk is
+///  resent, instead of sending its changed sections.
+const FULL_RESEND_DIRTY_BLOCKS : usize = 4096;
+
+/// The number of chunk batches which may be awaiting acknowledgement, once the
+///  client has acknowledged its first batch.
+const MAX_UNACKED_BATCHES : u32 = 10;
+
+
+/// Chunks which a player is currently viewing.
+#[derive(Component)]
+pub(crate) struct ChunkView {
+    world           : Option<Entity>,
+    viewing         : BTreeSet<Vec2<i32>>,
+    pending         : BTreeSet<Vec2<i32>>,
+    loaded          : BTreeSet<Vec2<i32>>,
+    /// The number of chunks per tick requested by the client.
+    chunks_per_tick : f32,
+    /// The number of chunks which may be sent in the next batch.
+    batch_quota     : f32,
+    /// When `batch_quota` was last topped up.
+    quota_updated   : Instant,
+    unacked_batches : u32,
+    max_unacked     : u32
+}
+
+impl Default for ChunkView {
+    fn default() -> Self { Self {
+        world           : None,
+        viewing         : BTreeSet::new(),
+        pending         : BTreeSet::new(),
+        loaded          : BTreeSet::new(),
+        chunks_per_tick : DEFAULT_CHUNKS_PER_TICK,
+        batch_quota     : 0.0,
+        quota_updated   : Instant::now(),
+        unacked_batches : 0,
+        max_unacked     : 1
+    } }
+}
+
+impl ChunkView {
+
+    /// Forgets every chunk, and discards the player's overlay along with the shared chunks.
+    fn release_all(&mut self, entity : Entity, conn : &mut Connection, q_worlds : &mut Query<(&mut World,)>, overlay : Option<&mut WorldOverlay>) {
+        if let Some(world) = self.world.take()
+            && let Ok((mut world,)) = q_worlds.get_mut(world)
+        { world.release_all_chunks(entity); }
+        if let Some(overlay) = overlay {
+            overlay.world = None;
+        }
+        for pos in mem::take(&mut self.loaded) {
+            let _ = conn.send_packet_play(ForgetLevelChunkS2CPlayPacket {
+                chunk_x : pos.x,
+                chunk_z : pos.y
+            });
+        }
+        self.viewing.clear();
+        self.pending.clear();
+    }
+
+    /// Returns `true` if the chunk at `pos` has been sent to the client.
+    #[inline]
+    pub(crate) fn has_loaded(&self, pos : Vec2<i32>) -> bool { self.loaded.contains(&pos) }
+
+    /// Marks an already sent chunk to be sent again in full.
+    pub(crate) fn resend(&mut self, pos : Vec2<i32>) {
+        if (self.loaded.remove(&pos)) {
+            self.pending.insert(pos);
+        }
+    }
+
+}
+
+
+pub(crate) fn spawn_default_world(
+    mut cmds          : Commands,
+        r_default_dim : Res<DefaultDim>
+) {
+    let entity = cmds.spawn(World::new(r_default_dim.0.clone(), r_default_dim.1.clone())).id();
+    cmds.insert_resource(DefaultWorld(entity));
+}
 
 
 pub(crate) fn read_settings_updates(
@@ -72,6 +350,21 @@ pub(crate) fn read_settings_updates(
     }
 }
 
+pub(crate) fn read_chunk_batch_acks(
+    mut q_views   : Query<(&mut ChunkView,),>,
+    mut er_packet : EventReader<PacketReadEvent>
+) {
+    for PacketReadEvent { entity, packet, .. } in er_packet.read() {
+        if let Ok((mut view,)) = q_views.get_mut(*entity)
+            && let Packet::Play(C2SPlayPackets::ChunkBatchReceived(ChunkBatchReceivedC2SPlayPacket { chunks_per_tick })) = packet
+        {
+            view.unacked_batches = view.unacked_batches.saturating_sub(1);
+            view.max_unacked     = MAX_UNACKED_BATCHES;
+            view.chunks_per_tick = if (chunks_per_tick.is_nan()) { 0.01 } else { (*chunks_per_tick).clamp(0.01, 64.0) };
+        }
+    }
+}
+
 pub(crate) fn update_chunk_view(
     mut q_conns : Query<(&mut Connection, &mut ChunkCentre, &mut ViewDistance,), (With<ConnStatePlay>,)>
 ) {
@@ -94,13 +387,28 @@ pub(crate) fn update_chunk_view(
 
 #[expect(clippy::type_complexity)]
 pub(crate) fn load_chunks(
-    mut q_conns : Query<(Entity, &mut Connection, &mut World, &ChunkCentre, &ViewDistance), (With<ConnStatePlay>, With<PlayerInWorld>,)>,
-    mut ew_load : EventWriter<WorldChunkLoading>
+    mut q_conns  : Query<(Entity, &mut Connection, &mut ChunkView, Option<&PlayerInWorld>, Option<&mut WorldOverlay>, &ChunkCentre, &ViewDistance), (With<ConnStatePlay>,)>,
+    mut q_worlds : Query<(&mut World,)>,
+    mut ew_load  : EventWriter<WorldChunkLoading>,
+        r_regs   : Res<Registries>
 ) {
-    for (entity, mut conn, mut world, chunk_centre, view_dist,) in &mut q_conns {
+    for (entity, mut conn, mut view, in_world, mut overlay, chunk_centre, view_dist,) in &mut q_conns {
+
+        // Leave the previous world.
+        let world_entity = in_world.map(|in_world| in_world.0);
+        if (view.world != world_entity) {
+            view.release_all(entity, &mut conn, &mut q_worlds, overlay.as_deref_mut());
+            view.world = world_entity;
+        }
+        let Some(world_entity) = world_entity
+            else { continue; };
+        let Ok((mut world,)) = q_worlds.get_mut(world_entity)
+            else { continue; };
 
         // Queue new chunks for load.
-        try_load_chunk(entity, &mut ew_load, &conn, &mut world, *chunk_centre.0);
+        let mut in_range = BTreeSet::new();
+        in_range.insert(*chunk_centre.0);
+        try_load_chunk(entity, world_entity, &mut ew_load, &conn, &mut world, &mut view, &r_regs, *chunk_centre.0);
         for radius in 1..=(view_dist.0.get() as i32) {
             let edge_len = 2 * radius;
             for corner_cx in [-1i32, 1] {
@@ -110,84 +418,282 @@ pub(crate) fn load_chunks(
                     for i in 0..edge_len {
                         let offset_cx = (radius * corner_cx) + (i * shift_cx);
                         let offset_cz = (radius * corner_cz) + (i * shift_cz);
-                        try_load_chunk(entity, &mut ew_load, &conn, &mut world, Vec2::new(
+                        let pos       = Vec2::new(
                             chunk_centre.0.x + offset_cx,
                             chunk_centre.0.y + offset_cz
-                        ));
+                        );
+                        in_range.insert(pos);
+                        try_load_chunk(entity, world_entity, &mut ew_load, &conn, &mut world, &mut view, &r_regs, pos);
                     }
                 }
             }
         }
 
-        // Load ready chunks.
-        if let Some(pos) = world.ready_chunks.pop_front()
-            && let Some(chunk) = world.chunks.get_mut(&pos)
-        {
-            let data = chunk.ptc_chunk_section_data();
-            for section in &mut chunk.sections {
+        // Unload out-of-range chunks.
+        let out_of_range = view.viewing.difference(&in_range).cloned().collect::<Vec<_>>();
+        for pos in out_of_range {
+            view.viewing.remove(&pos);
+            view.pending.remove(&pos);
+            world.release_chunk(pos, entity);
+            if let Some(overlay) = overlay.as_deref_mut()
+                && let Some(overlay) = &mut overlay.world
+            { overlay.chunks.remove(&pos); }
+            if (view.loaded.remove(&pos)) {
+                let _ = conn.send_packet_play(ForgetLevelChunkS2CPlayPacket {
+                    chunk_x : pos.x,
+                    chunk_z : pos.y
+                });
+            }
+        }
+
+        // Send ready chunks nearest-first, in a batch sized by the client's requested rate.
+        //  The quota grows with the time passed, however often this system runs.
+        let now     = Instant::now();
+        let elapsed = now.duration_since(view.quota_updated).as_secs_f32() / TICK_DURATION.as_secs_f32();
+        view.quota_updated = now;
+        view.batch_quota   = (view.batch_quota + (view.chunks_per_tick * elapsed)).min(view.chunks_per_tick.max(1.0));
+        if (view.unacked_batches >= view.max_unacked) { continue; }
+        if (view.batch_quota < 1.0) { continue; }
+        let overlay = overlay.as_deref().and_then(|overlay| overlay.world.as_ref());
+        let mut ready = view.pending.iter().cloned().filter(|pos|
+            overlay.is_some_and(|overlay| overlay.chunks.contains_key(pos))
+            || world.chunks.get(pos).is_some_and(|chunk| chunk.ready)
+        ).collect::<Vec<_>>();
+        if (ready.is_empty()) { continue; }
+        ready.sort_by_key(|pos| (pos.x - chunk_centre.0.x).pow(2) + (pos.y - chunk_centre.0.y).pow(2));
+        ready.truncate(view.batch_quota as usize);
+
+        let _ = conn.send_packet_play(ChunkBatchStartS2CPlayPacket);
+        for &pos in &ready {
+            let chunk = overlay.and_then(|overlay| overlay.chunks.get(&pos))
+                .or_else(|| world.chunks.get(&pos))
+                .unwrap();
+            send_chunk(&mut conn, pos, chunk, world.dim_type.has_skylight, &r_regs);
+            view.pending.remove(&pos);
+            view.loaded.insert(pos);
+        }
+        let _ = conn.send_packet_play(ChunkBatchFinishedS2CPlayPacket {
+            batch_size : (ready.len() as i32).into()
+        });
+        view.batch_quota     -= ready.len() as f32;
+        view.unacked_batches += 1;
+
+    }
+}
+fn send_chunk(conn : &mut Connection, pos : Vec2<i32>, chunk : &Chunk, has_skylight : bool, regs : &Registries) {
+    let light = chunk.light.as_ref()
+        .map_or_else(|| ChunkLight::compute(&chunk.sections, has_skylight).ptc_light_data(), |light| light.ptc_light_data());
+    let _ = conn.send_packet_play(LevelChunkWithLightS2CPlayPacket {
+        chunk_x                : pos.x,
+        chunk_z                : pos.y,
+        data                   : chunk.ptc_chunk_section_data(regs.biome_direct_bits()),
+        heightmaps             : chunk.ptc_heightmaps(),
+        block_entities         : chunk.ptc_block_entities().into(),
+        sky_light_mask         : light.sky_light_mask.into(),
+        block_light_mask       : light.block_light_mask.into(),
+        empty_sky_light_mask   : light.empty_sky_light_mask.into(),
+        empty_block_light_mask : light.empty_block_light_mask.into(),
+        sky_light_array        : light.sky_light_array.into(),
+        block_light_array      : light.block_light_array.into()
+    });
+}
+
+#[expect(clippy::too_many_arguments)]
+fn try_load_chunk(
+    entity       : Entity,
+    world_entity : Entity,
+    ew_load      : &mut EventWriter<WorldChunkLoading>,
+    conn         : &Connection,
+    world        : &mut World,
+    view         : &mut ChunkView,
+    regs         : &Registries,
+    pos          : Vec2<i32>
+) {
+    if (! view.viewing.insert(pos)) { return; }
+    view.pending.insert(pos);
+    if (world.acquire_chunk(pos, entity)) {
+        trace!("Loading chunk <{}, {}> for peer {}", pos.x, pos.y, conn.peer_addr());
+        if (! (world.start_loading_stored(pos, regs) || world.start_generating(pos))) {
+            ew_load.write(WorldChunkLoading { world : world_entity, pos });
+        }
+    }
+}
+
+pub(crate) fn update_chunks(
+    mut q_worlds : Query<(&mut World,)>,
+    mut q_conns  : Query<(&mut Connection, &mut ChunkView, Option<&mut WorldOverlay>,), (With<ConnStatePlay>,)>,
+        r_regs   : Res<Registries>,
+        r_budget : Res<ChunkUpdateBudget>
+) {
+    let biome_direct_bits = r_regs.biome_direct_bits();
+
+    // Send biome changes in shared chunks to every viewer.
+    for (mut world,) in &mut q_worlds {
+        for (cpos, chunk,) in &mut world.chunks {
+            if (! (chunk.ready && chunk.biomes_dirty)) { continue; }
+            for &viewer in &chunk.viewers {
+                if let Ok((mut conn, view, overlay,)) = q_conns.get_mut(viewer)
+                    && view.loaded.contains(cpos)
+                    && (! overlay.is_some_and(|overlay| overlay.has_chunk(*cpos)))
+                { let _ = conn.send_packet_play(chunk.ptc_update_biomes(*cpos, biome_direct_bits)); }
+            }
+            chunk.biomes_dirty = false;
+        }
+    }
+
+    // Send light changes in shared chunks to every viewer.
+    for (mut world,) in &mut q_worlds {
+        for (cpos, chunk,) in &mut world.chunks {
+            if (! (chunk.ready && chunk.light_dirty)) { continue; }
+            for &viewer in &chunk.viewers {
+                if let Ok((mut conn, view, overlay,)) = q_conns.get_mut(viewer)
+                    && view.loaded.contains(cpos)
+                    && (! overlay.is_some_and(|overlay| overlay.has_chunk(*cpos)))
+                    && let Some(packet) = chunk.ptc_update_light(*cpos)
+                { let _ = conn.send_packet_play(packet); }
+            }
+            chunk.light_dirty = false;
+        }
+    }
+
+    // Send changes in shared chunks to every viewer, and then changes in
+    //  overlay chunks, until the byte budget for the tick runs out. Each world
+    //  and overlay carries on from the chunk where the last tick ran out.
+    let mut spent = 0;
+    for (mut world,) in &mut q_worlds {
+        let world = &mut *world;
+        'update_chunks : for pos in world.chunks_from_update_cursor() {
+            let cpos = &pos;
+            let Some(chunk) = world.chunks.get_mut(cpos)
+                else { continue; };
+            if (! chunk.ready) { continue; }
+            let dirty_blocks = chunk.sections.iter().map(ChunkSection::dirty_count).sum::<usize>();
+            if (dirty_blocks == 0) { continue; }
+
+            // Too many changes. Resend the whole chunk.
+            if (dirty_blocks > FULL_RESEND_DIRTY_BLOCKS) {
+                for &viewer in &chunk.viewers {
+                    if let Ok((_, mut view, overlay,)) = q_conns.get_mut(viewer)
+                        && (! overlay.is_some_and(|overlay| overlay.has_chunk(*cpos)))
+                    { view.resend(*cpos); }
+                }
+                for section in &mut chunk.sections {
+                    section.clear_dirty();
+                }
+                continue;
+            }
+
+            let min_section = chunk.min_section;
+            for (cy, section) in chunk.sections.iter_mut().enumerate() {
+                if (! section.is_dirty()) { continue; }
+                let size = section.ptc_update_section_size();
+                if (spent > 0 && spent + size > r_budget.0) {
+                    world.update_cursor = *cpos;
+                    break 'update_chunks;
+                }
+                spent += size;
+                for &viewer in &chunk.viewers {
+                    if let Ok((mut conn, view, overlay,)) = q_conns.get_mut(viewer)
+                        && view.loaded.contains(cpos)
+                        && (! overlay.is_some_and(|overlay| overlay.has_chunk(*cpos)))
+                        && let Some(packet) = section.ptc_update_section([cpos.x, min_section + (cy as i32), cpos.y,])
+                    { let _ = conn.send_packet_play(packet); }
+                }
                 section.clear_dirty();
             }
-            let _ = conn.send_packet_play(LevelChunkWithLightS2CPlayPacket {
-                chunk_x                : pos.x,
-                chunk_z                : pos.y,
-                data,
-                heightmaps             : Nbt::new(),
-                block_entities         : Vec::new().into(),
-                sky_light_mask         : vec![u64::MAX; (chunk.sections.len() + 2).div_ceil(64)].into(),
-                block_light_mask       : Vec::new().into(),
-                empty_sky_light_mask   : Vec::new().into(),
-                empty_block_light_mask : Vec::new().into(),
-                sky_light_array        : vec![LightMask { light_array : vec![u8::MAX; 2048].into() }; (chunk.sections.len() + 2)].into(),
-                block_light_array      : Vec::new().into()
-            });
-            chunk.loaded = true;
         }
+    }
 
-        // Update loaded chunks.
-        'update_chunks : for (cpos, chunk,) in &mut world.chunks {
-            if (chunk.ready && chunk.loaded) {
-                for (y, section) in chunk.sections.iter_mut().enumerate() {
-                    if let Some(packet) = section.ptc_update_section([cpos.x, y as i32, cpos.y,]) {
-                        section.clear_dirty();
+    // Send block entity changes in shared chunks to every viewer.
+    for (mut world,) in &mut q_worlds {
+        for (cpos, chunk,) in &mut world.chunks {
+            if (! chunk.ready) || chunk.block_entities_dirty.is_empty() { continue; }
+            let packets = chunk.ptc_update_block_entities(*cpos);
+            for &viewer in &chunk.viewers {
+                if let Ok((mut conn, view, overlay,)) = q_conns.get_mut(viewer)
+                    && view.loaded.contains(cpos)
+                    && (! overlay.is_some_and(|overlay| overlay.has_chunk(*cpos)))
+                {
+                    for (_, packet,) in &packets {
                         let _ = conn.send_packet_play(packet);
-                        break 'update_chunks;
                     }
                 }
             }
+            for (local, _,) in packets {
+                chunk.block_entities_dirty.remove(&local);
+            }
         }
+    }
 
-        // TODO: Unload out-of-range chunks.
-
+    // Send changes in overlay chunks to their owner.
+    for (mut conn, mut view, overlay,) in &mut q_conns {
+        let Some(mut overlay) = overlay
+            else { continue; };
+        let Some(overlay) = &mut overlay.world
+            else { continue; };
+        for (cpos, chunk,) in &mut overlay.chunks {
+            if (! view.loaded.contains(cpos)) { continue; }
+            if (chunk.biomes_dirty) {
+                let _ = conn.send_packet_play(chunk.ptc_update_biomes(*cpos, biome_direct_bits));
+                chunk.biomes_dirty = false;
+            }
+            if (chunk.light_dirty) {
+                if let Some(packet) = chunk.ptc_update_light(*cpos) {
+                    let _ = conn.send_packet_play(packet);
+                }
+                chunk.light_dirty = false;
+            }
+        }
+        'update_chunks : for pos in overlay.chunks_from_update_cursor() {
+            let cpos = &pos;
+            let Some(chunk) = overlay.chunks.get_mut(cpos)
+                else { continue; };
+            if (! view.loaded.contains(cpos)) { continue; }
+            let dirty_blocks = chunk.sections.iter().map(ChunkSection::dirty_count).sum::<usize>();
+            if (dirty_blocks > FULL_RESEND_DIRTY_BLOCKS) {
+                view.resend(*cpos);
+                for section in &mut chunk.sections {
+                    section.clear_dirty();
+                }
+                continue;
+            }
+            let min_section = chunk.min_section;
+            for (cy, section) in chunk.sections.iter_mut().enumerate() {
+                if (! section.is_dirty()) { continue; }
+                let size = section.ptc_update_section_size();
+                if (spent > 0 && spent + size > r_budget.0) {
+                    overlay.update_cursor = *cpos;
+                    break 'update_chunks;
+                }
+                spent += size;
+                if let Some(packet) = section.ptc_update_section([cpos.x, min_section + (cy as i32), cpos.y,]) {
+                    let _ = conn.send_packet_play(packet);
+                }
+                section.clear_dirty();
+            }
+        }
+        for (cpos, chunk,) in &mut overlay.chunks {
+            if (! view.loaded.contains(cpos)) { continue; }
+            for (local, packet,) in chunk.ptc_update_block_entities(*cpos) {
+                let _ = conn.send_packet_play(packet);
+                chunk.block_entities_dirty.remove(&local);
+            }
+        }
     }
+
 }
-fn try_load_chunk(
-    entity  : Entity,
-    ew_load : &mut EventWriter<WorldChunkLoading>,
-    conn    : &Connection,
-    world   : &mut World,
-    pos     : Vec2<i32>
-) {
-    if (world.chunks.contains_key(&pos)) { return; }
-    world.chunks.insert(pos, Chunk {
-        sections : {
-            let     section  = ChunkSection::empty();
-            let     count    = (world.dim_type.height / 16).max(1);
-            let mut sections = Vec::with_capacity(count as usize);
-            for _ in 0..count {
-                sections.push(section.clone());
-            }
-            sections.push(section);
-            sections
-        },
-        ready  : false,
-        loaded : false
-    });
 
-    trace!("Loading chunk <{}, {}> for peer {}", pos.x, pos.y, conn.peer_addr());
-    ew_load.write(WorldChunkLoading { entity, pos });
+pub(crate) fn release_departed_viewers(
+    mut q_worlds : Query<(&mut World,)>,
+    mut rc_views : RemovedComponents<ChunkView>
+) {
+    for entity in rc_views.read() {
+        for (mut world,) in &mut q_worlds {
+            world.release_all_chunks(entity);
+        }
+    }
 }
 
-// TODO: Unload chunks
 
 
 fn in_section_block_linearise(dx : u8, dy : u8, dz : u8) -> u16 {
@@ -203,3 +709,19 @@ fn in_section_block_delinearise(l : u16) -> [u8; 3] {
         ((l >> 4) as u8) & 0b00001111
     ]
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linearise_round_trips() {
+        for linear_xyz in 0..4096u16 {
+            let [dx, dy, dz] = in_section_block_delinearise(linear_xyz);
+            assert!((dx < 16) && (dy < 16) && (dz < 16));
+            assert_eq!(in_section_block_linearise(dx, dy, dz), linear_xyz);
+        }
+    }
+
+}
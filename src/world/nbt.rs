@@ -0,0 +1,238 @@
+use flywheelmc_common::prelude::*;
+
+
+/// A minimal NBT tag, used for reading and writing region and schematic files.
+#[derive(Clone, Debug)]
+pub(crate) enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(BTreeMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>)
+}
+
+impl Tag {
+
+    fn id(&self) -> u8 {
+        match (self) {
+            Self::Byte(_)      => 1,
+            Self::Short(_)     => 2,
+            Self::Int(_)       => 3,
+            Self::Long(_)      => 4,
+            Self::Float(_)     => 5,
+            Self::Double(_)    => 6,
+            Self::ByteArray(_) => 7,
+            Self::String(_)    => 8,
+            Self::List(_)      => 9,
+            Self::Compound(_)  => 10,
+            Self::IntArray(_)  => 11,
+            Self::LongArray(_) => 12
+        }
+    }
+
+    pub(crate) fn get(&self, key : &str) -> Option<&Tag> {
+        if let Self::Compound(map) = self { map.get(key) } else { None }
+    }
+
+    pub(crate) fn as_int(&self) -> Option<i64> {
+        match (self) {
+            Self::Byte(v)  => Some(*v as i64),
+            Self::Short(v) => Some(*v as i64),
+            Self::Int(v)   => Some(*v as i64),
+            Self::Long(v)  => Some(*v),
+            _              => None
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        if let Self::String(v) = self { Some(v) } else { None }
+    }
+
+    pub(crate) fn as_list(&self) -> Option<&[Tag]> {
+        if let Self::List(v) = self { Some(v) } else { None }
+    }
+
+    pub(crate) fn as_compound(&self) -> Option<&BTreeMap<String, Tag>> {
+        if let Self::Compound(v) = self { Some(v) } else { None }
+    }
+
+    pub(crate) fn as_long_array(&self) -> Option<&[i64]> {
+        if let Self::LongArray(v) = self { Some(v) } else { None }
+    }
+
+    pub(crate) fn as_byte_array(&self) -> Option<&[i8]> {
+        if let Self::ByteArray(v) = self { Some(v) } else { None }
+    }
+
+    pub(crate) fn as_int_array(&self) -> Option<&[i32]> {
+        if let Self::IntArray(v) = self { Some(v) } else { None }
+    }
+
+}
+
+
+fn invalid(msg : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad nbt: {msg}"))
+}
+
+
+/// Reads a root compound, with its name, from `data`.
+pub(crate) fn read_root(data : &[u8]) -> io::Result<Tag> {
+    let mut reader = Reader { data, pos : 0 };
+    if (reader.u8()? != 10) { return Err(invalid("root is not a compound")); }
+    reader.string()?;
+    reader.payload(10)
+}
+
+/// Writes a root compound, with an empty name.
+pub(crate) fn write_root(tag : &Tag) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.push(tag.id());
+    write_string(&mut out, "");
+    write_payload(&mut out, tag);
+    out
+}
+
+/// Reads a root tag without a name, as sent over the network.
+pub(crate) fn read_nameless(data : &[u8]) -> io::Result<Tag> {
+    let mut reader = Reader { data, pos : 0 };
+    let id = reader.u8()?;
+    reader.payload(id)
+}
+
+/// Writes a root tag without a name, as sent over the network.
+pub(crate) fn write_nameless(tag : &Tag) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.push(tag.id());
+    write_payload(&mut out, tag);
+    out
+}
+
+
+struct Reader<'l> {
+    data : &'l [u8],
+    pos  : usize
+}
+
+impl Reader<'_> {
+
+    fn take(&mut self, n : usize) -> io::Result<&[u8]> {
+        let bytes = self.data.get(self.pos..(self.pos + n)).ok_or_else(|| invalid("unexpected end"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> { Ok(self.take(1)?[0]) }
+    fn i16(&mut self) -> io::Result<i16> { Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap())) }
+    fn i32(&mut self) -> io::Result<i32> { Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap())) }
+    fn i64(&mut self) -> io::Result<i64> { Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap())) }
+
+    fn len(&mut self) -> io::Result<usize> {
+        usize::try_from(self.i32()?).map_err(|_| invalid("negative length"))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.i16()? as u16 as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn payload(&mut self, id : u8) -> io::Result<Tag> {
+        Ok(match (id) {
+            1  => Tag::Byte(self.u8()? as i8),
+            2  => Tag::Short(self.i16()?),
+            3  => Tag::Int(self.i32()?),
+            4  => Tag::Long(self.i64()?),
+            5  => Tag::Float(f32::from_bits(self.i32()? as u32)),
+            6  => Tag::Double(f64::from_bits(self.i64()? as u64)),
+            7  => {
+                let len = self.len()?;
+                Tag::ByteArray(self.take(len)?.iter().map(|&b| b as i8).collect())
+            },
+            8  => Tag::String(self.string()?),
+            9  => {
+                let item_id = self.u8()?;
+                let len     = self.len()?;
+                let mut items = Vec::with_capacity(len.min(4096));
+                for _ in 0..len {
+                    items.push(self.payload(item_id)?);
+                }
+                Tag::List(items)
+            },
+            10 => {
+                let mut map = BTreeMap::new();
+                loop {
+                    let item_id = self.u8()?;
+                    if (item_id == 0) { break; }
+                    let name = self.string()?;
+                    map.insert(name, self.payload(item_id)?);
+                }
+                Tag::Compound(map)
+            },
+            11 => {
+                let len = self.len()?;
+                let mut items = Vec::with_capacity(len.min(4096));
+                for _ in 0..len { items.push(self.i32()?); }
+                Tag::IntArray(items)
+            },
+            12 => {
+                let len = self.len()?;
+                let mut items = Vec::with_capacity(len.min(4096));
+                for _ in 0..len { items.push(self.i64()?); }
+                Tag::LongArray(items)
+            },
+            _  => { return Err(invalid("unknown tag type")); }
+        })
+    }
+
+}
+
+
+fn write_string(out : &mut Vec<u8>, value : &str) {
+    out.extend((value.len() as u16).to_be_bytes());
+    out.extend(value.as_bytes());
+}
+
+fn write_payload(out : &mut Vec<u8>, tag : &Tag) {
+    match (tag) {
+        Tag::Byte(v)      => { out.push(*v as u8); },
+        Tag::Short(v)     => { out.extend(v.to_be_bytes()); },
+        Tag::Int(v)       => { out.extend(v.to_be_bytes()); },
+        Tag::Long(v)      => { out.extend(v.to_be_bytes()); },
+        Tag::Float(v)     => { out.extend(v.to_be_bytes()); },
+        Tag::Double(v)    => { out.extend(v.to_be_bytes()); },
+        Tag::ByteArray(v) => {
+            out.extend((v.len() as i32).to_be_bytes());
+            out.extend(v.iter().map(|&b| b as u8));
+        },
+        Tag::String(v)    => { write_string(out, v); },
+        Tag::List(v)      => {
+            // Empty lists are written as lists of `End`.
+            out.push(v.first().map_or(0, Tag::id));
+            out.extend((v.len() as i32).to_be_bytes());
+            for item in v { write_payload(out, item); }
+        },
+        Tag::Compound(v)  => {
+            for (name, item) in v {
+                out.push(item.id());
+                write_string(out, name);
+                write_payload(out, item);
+            }
+            out.push(0);
+        },
+        Tag::IntArray(v)  => {
+            out.extend((v.len() as i32).to_be_bytes());
+            for item in v { out.extend(item.to_be_bytes()); }
+        },
+        Tag::LongArray(v) => {
+            out.extend((v.len() as i32).to_be_bytes());
+            for item in v { out.extend(item.to_be_bytes()); }
+        }
+    }
+}
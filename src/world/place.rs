@@ -0,0 +1,263 @@
+use crate::world::{ World, SetBlockBatch, state_entry };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ BlockState, BlockPos };
+use protocol::registry::RegEntry;
+use std::error::Error;
+use std::fmt::{ self, Display };
+
+
+/// A block to place, either as a decoded block state or a raw registry entry.
+#[derive(Clone)]
+pub enum Block {
+    Entry(RegEntry<BlockState>),
+    State(BlockState)
+}
+
+impl Block {
+
+    /// Resolves this block to a registry entry.
+    pub fn to_entry(&self) -> Result<RegEntry<BlockState>, PlaceRejection> {
+        match (self) {
+            Self::Entry(entry) => {
+                if (BlockState::from_id(entry.id()).is_none()) {
+                    return Err(PlaceRejection::UnknownBlock);
+                }
+                Ok(*entry)
+            },
+            Self::State(state) => state_entry(state).ok_or(PlaceRejection::UnknownBlock)
+        }
+    }
+
+}
+
+impl From<RegEntry<BlockState>> for Block {
+    #[inline]
+    fn from(value : RegEntry<BlockState>) -> Self { Self::Entry(value) }
+}
+
+impl From<BlockState> for Block {
+    #[inline]
+    fn from(value : BlockState) -> Self { Self::State(value) }
+}
+
+
+/// The most blocks a [`BlockShape`] may cover. See [`BlockShape::volume`].
+pub const MAX_SHAPE_VOLUME : u64 = 1 << 21;
+
+
+/// A shape of blocks to fill.
+#[derive(Clone, Debug)]
+pub enum BlockShape {
+
+    /// Every block between two corners (inclusive).
+    Cuboid {
+        a : BlockPos,
+        b : BlockPos
+    },
+
+    /// Every block whose centre is within `radius` of the centre of `centre`.
+    Sphere {
+        centre : BlockPos,
+        radius : f64
+    },
+
+    /// A line of blocks from one block to another (inclusive).
+    Line {
+        from : BlockPos,
+        to   : BlockPos
+    }
+
+}
+
+impl BlockShape {
+
+    /// Returns the smallest cuboid containing every block in this shape.
+    ///
+    /// Corners which would be outside of the `i32` range are clamped to it.
+    pub fn bounds(&self) -> (BlockPos, BlockPos,) {
+        match (self) {
+            Self::Cuboid { a, b } | Self::Line { from : a, to : b } => (
+                BlockPos { x : a.x.min(b.x), y : a.y.min(b.y), z : a.z.min(b.z) },
+                BlockPos { x : a.x.max(b.x), y : a.y.max(b.y), z : a.z.max(b.z) },
+            ),
+            Self::Sphere { centre, radius } => {
+                let r     = radius.clamp(0.0, i32::MAX as f64).floor() as i64;
+                let clamp = |v : i64| v.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+                (
+                    BlockPos { x : clamp((centre.x as i64) - r), y : clamp((centre.y as i64) - r), z : clamp((centre.z as i64) - r) },
+                    BlockPos { x : clamp((centre.x as i64) + r), y : clamp((centre.y as i64) + r), z : clamp((centre.z as i64) + r) },
+                )
+            }
+        }
+    }
+
+    /// Returns the number of blocks [`positions`](Self::positions) has to check.
+    ///
+    /// This is the volume of the [`bounds`](Self::bounds) for cuboids and
+    ///  spheres, and the length for lines.
+    pub fn volume(&self) -> u64 {
+        let (min, max,) = self.bounds();
+        let [dx, dy, dz] = [(min.x, max.x,), (min.y, max.y,), (min.z, max.z,)]
+            .map(|(min, max,)| ((max as i64) - (min as i64) + 1) as u64);
+        match (self) {
+            Self::Cuboid { .. } | Self::Sphere { .. } => dx.saturating_mul(dy).saturating_mul(dz),
+            Self::Line { .. }                         => dx.max(dy).max(dz)
+        }
+    }
+
+    /// Returns every block in this shape.
+    ///
+    /// Fails with [`PlaceRejection::TooLarge`] if the shape's
+    ///  [`volume`](Self::volume) is over [`MAX_SHAPE_VOLUME`].
+    pub fn positions(&self) -> Result<Vec<BlockPos>, PlaceRejection> {
+        if (self.volume() > MAX_SHAPE_VOLUME) {
+            return Err(PlaceRejection::TooLarge);
+        }
+        Ok(match (self) {
+
+            Self::Cuboid { .. } => {
+                let (min, max,) = self.bounds();
+                let mut out = Vec::new();
+                for y in min.y..=max.y {
+                    for z in min.z..=max.z {
+                        for x in min.x..=max.x {
+                            out.push(BlockPos { x, y, z });
+                        }
+                    }
+                }
+                out
+            },
+
+            Self::Sphere { centre, radius } => {
+                let (min, max,) = self.bounds();
+                let     r2  = radius * radius;
+                let mut out = Vec::new();
+                for y in min.y..=max.y {
+                    for z in min.z..=max.z {
+                        for x in min.x..=max.x {
+                            let dx = ((x as i64) - (centre.x as i64)) as f64;
+                            let dy = ((y as i64) - (centre.y as i64)) as f64;
+                            let dz = ((z as i64) - (centre.z as i64)) as f64;
+                            if ((dx * dx) + (dy * dy) + (dz * dz) <= r2) {
+                                out.push(BlockPos { x, y, z });
+                            }
+                        }
+                    }
+                }
+                out
+            },
+
+            Self::Line { from, to } => {
+                let dx    = ((to.x as i64) - (from.x as i64)) as f64;
+                let dy    = ((to.y as i64) - (from.y as i64)) as f64;
+                let dz    = ((to.z as i64) - (from.z as i64)) as f64;
+                let steps = dx.abs().max(dy.abs()).max(dz.abs()) as usize;
+                let mut out = Vec::with_capacity(steps + 1);
+                for i in 0..=steps {
+                    let t = if (steps == 0) { 0.0 } else { (i as f64) / (steps as f64) };
+                    // Every step is between `from` and `to`, so it fits in an `i32`.
+                    let pos = BlockPos {
+                        x : ((from.x as i64) + ((dx * t).round() as i64)) as i32,
+                        y : ((from.y as i64) + ((dy * t).round() as i64)) as i32,
+                        z : ((from.z as i64) + ((dz * t).round() as i64)) as i32
+                    };
+                    if (out.last() != Some(&pos)) {
+                        out.push(pos);
+                    }
+                }
+                out
+            }
+
+        })
+    }
+
+}
+
+
+/// The reason a single block placement was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaceRejection {
+    /// The block is not a known block state.
+    UnknownBlock,
+    /// The chunk containing the position is not loaded.
+    ChunkNotLoaded,
+    /// The position is outside of the world's height range.
+    OutOfHeight,
+    /// The shape covers more than [`MAX_SHAPE_VOLUME`] blocks. Reported at
+    ///  the lowest corner of the shape, and nothing is placed.
+    TooLarge
+}
+
+impl Display for PlaceRejection {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match (self) {
+            Self::UnknownBlock   => "unknown block",
+            Self::ChunkNotLoaded => "chunk not loaded",
+            Self::OutOfHeight    => "out of height range",
+            Self::TooLarge       => "shape too large"
+        })
+    }
+}
+
+/// Every placement rejected by a [`World`] block-setting call.
+///
+/// Placements which are not listed here were applied.
+#[derive(Clone, Debug)]
+pub struct PlaceError {
+    pub rejected : Vec<(BlockPos, PlaceRejection,)>
+}
+
+impl Display for PlaceError {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} block placement(s) rejected", self.rejected.len())?;
+        if let Some((pos, reason,)) = self.rejected.first() {
+            write!(f, " (first at <{}, {}, {}>: {})", pos.x, pos.y, pos.z, reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for PlaceError { }
+
+
+impl World {
+
+    /// Sets the block at `pos`.
+    pub fn set_block(&mut self, pos : BlockPos, block : impl Into<Block>) -> Result<(), PlaceError> {
+        let block = block.into().to_entry();
+        self.place_entries(iter::once((pos, block,)))
+    }
+
+    /// Sets many blocks at once.
+    pub fn set_blocks<I, B>(&mut self, blocks : I) -> Result<(), PlaceError>
+    where
+        I : IntoIterator<Item = (BlockPos, B,)>,
+        B : Into<Block>
+    {
+        self.place_entries(blocks.into_iter().map(|(pos, block,)| (pos, block.into().to_entry(),)))
+    }
+
+    /// Sets every block in `shape` to `block`.
+    pub fn fill(&mut self, shape : &BlockShape, block : impl Into<Block>) -> Result<(), PlaceError> {
+        let positions = shape.positions().map_err(|reason| PlaceError { rejected : vec![(shape.bounds().0, reason,)] })?;
+        let block     = block.into().to_entry();
+        self.place_entries(positions.into_iter().map(|pos| (pos, block,)))
+    }
+
+    fn place_entries<I>(&mut self, blocks : I) -> Result<(), PlaceError>
+    where
+        I : IntoIterator<Item = (BlockPos, Result<RegEntry<BlockState>, PlaceRejection>,)>
+    {
+        let mut rejected = Vec::new();
+        let mut batch    = SetBlockBatch::new(self);
+        for (pos, block,) in blocks {
+            if let Err(reason) = block.and_then(|block| batch.set(pos, block)) {
+                rejected.push((pos, reason,));
+            }
+        }
+        drop(batch);
+        if (rejected.is_empty()) { Ok(()) }
+        else { Err(PlaceError { rejected }) }
+    }
+
+}
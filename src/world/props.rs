@@ -0,0 +1,852 @@
+use flywheelmc_common::prelude::*;
+use protocol::value::BlockState;
+use protocol::registry::RegEntry;
+use std::sync::LazyLock;
+
+
+/// Physical properties of every block state, indexed by registry entry id.
+///
+/// Built once from the block state registry, so lookups do not lock.
+static BLOCK_PROPS : LazyLock<Box<[BlockProps]>> = LazyLock::new(|| {
+    (0u32..).map_while(BlockState::from_id).map(|state| BlockProps::of_state(&state)).collect()
+});
+
+
+/// Physical properties of a block state, used by lighting, heightmaps, and digging.
+#[derive(Clone, Copy)]
+pub(crate) struct BlockProps {
+    /// Whether this block is air.
+    pub(crate) is_air           : bool,
+    /// Whether this block has collision or contains a fluid.
+    pub(crate) blocks_motion    : bool,
+    /// Whether this block breaks as soon as a survival player starts digging it.
+    pub(crate) breaks_instantly : bool,
+    /// How much light this block emits.
+    pub(crate) emission         : u8,
+    /// How much light is lost when passing through this block.
+    pub(crate) opacity          : u8
+}
+
+/// A solid cube which blocks light.
+const SOLID       : BlockProps = BlockProps { is_air : false, blocks_motion : true,  breaks_instantly : false, emission : 0, opacity : 15 };
+/// A block with collision which light passes through.
+const CUTOUT      : BlockProps = BlockProps { is_air : false, blocks_motion : true,  breaks_instantly : false, emission : 0, opacity : 0 };
+/// A block with collision which dims light.
+const TRANSLUCENT : BlockProps = BlockProps { is_air : false, blocks_motion : true,  breaks_instantly : false, emission : 0, opacity : 1 };
+/// A block without collision which light passes through.
+const PASSABLE    : BlockProps = BlockProps { is_air : false, blocks_motion : false, breaks_instantly : false, emission : 0, opacity : 0 };
+/// A plant, which light and entities pass through, and which breaks instantly.
+const PLANT       : BlockProps = BlockProps { is_air : false, blocks_motion : false, breaks_instantly : true,  emission : 0, opacity : 0 };
+/// A fluid, or a block always filled with one.
+const FLUID       : BlockProps = BlockProps { is_air : false, blocks_motion : true,  breaks_instantly : false, emission : 0, opacity : 1 };
+const COBWEB      : BlockProps = BlockProps { is_air : false, blocks_motion : false, breaks_instantly : false, emission : 0, opacity : 1 };
+const AIR         : BlockProps = BlockProps { is_air : true,  blocks_motion : false, breaks_instantly : true,  emission : 0, opacity : 0 };
+
+/// The vanilla properties of every block which is not a [`SOLID`] cube, sorted by name.
+///
+/// The emission of blocks with a `lit` property is their emission when lit.
+const BLOCKS : &[(&str, BlockProps,)] = &[
+    ("acacia_button", PASSABLE),
+    ("acacia_door", CUTOUT),
+    ("acacia_fence", CUTOUT),
+    ("acacia_fence_gate", CUTOUT),
+    ("acacia_hanging_sign", PASSABLE),
+    ("acacia_leaves", TRANSLUCENT),
+    ("acacia_pressure_plate", PASSABLE),
+    ("acacia_sapling", PLANT),
+    ("acacia_sign", PASSABLE),
+    ("acacia_slab", CUTOUT),
+    ("acacia_stairs", CUTOUT),
+    ("acacia_trapdoor", CUTOUT),
+    ("acacia_wall_hanging_sign", PASSABLE),
+    ("acacia_wall_sign", PASSABLE),
+    ("activator_rail", PASSABLE),
+    ("air", AIR),
+    ("allium", PLANT),
+    ("amethyst_cluster", CUTOUT.emits(5)),
+    ("andesite_slab", CUTOUT),
+    ("andesite_stairs", CUTOUT),
+    ("andesite_wall", CUTOUT),
+    ("anvil", CUTOUT),
+    ("attached_melon_stem", PLANT),
+    ("attached_pumpkin_stem", PLANT),
+    ("azalea", CUTOUT.instant()),
+    ("azalea_leaves", TRANSLUCENT),
+    ("azure_bluet", PLANT),
+    ("bamboo", CUTOUT),
+    ("bamboo_button", PASSABLE),
+    ("bamboo_door", CUTOUT),
+    ("bamboo_fence", CUTOUT),
+    ("bamboo_fence_gate", CUTOUT),
+    ("bamboo_hanging_sign", PASSABLE),
+    ("bamboo_mosaic_slab", CUTOUT),
+    ("bamboo_mosaic_stairs", CUTOUT),
+    ("bamboo_pressure_plate", PASSABLE),
+    ("bamboo_sapling", PASSABLE),
+    ("bamboo_sign", PASSABLE),
+    ("bamboo_slab", CUTOUT),
+    ("bamboo_stairs", CUTOUT),
+    ("bamboo_trapdoor", CUTOUT),
+    ("bamboo_wall_hanging_sign", PASSABLE),
+    ("bamboo_wall_sign", PASSABLE),
+    ("barrier", CUTOUT),
+    ("beacon", TRANSLUCENT.emits(15)),
+    ("beetroots", PLANT),
+    ("bell", CUTOUT),
+    ("big_dripleaf", CUTOUT),
+    ("big_dripleaf_stem", PASSABLE),
+    ("birch_button", PASSABLE),
+    ("birch_door", CUTOUT),
+    ("birch_fence", CUTOUT),
+    ("birch_fence_gate", CUTOUT),
+    ("birch_hanging_sign", PASSABLE),
+    ("birch_leaves", TRANSLUCENT),
+    ("birch_pressure_plate", PASSABLE),
+    ("birch_sapling", PLANT),
+    ("birch_sign", PASSABLE),
+    ("birch_slab", CUTOUT),
+    ("birch_stairs", CUTOUT),
+    ("birch_trapdoor", CUTOUT),
+    ("birch_wall_hanging_sign", PASSABLE),
+    ("birch_wall_sign", PASSABLE),
+    ("black_banner", PASSABLE),
+    ("black_bed", CUTOUT),
+    ("black_candle", CUTOUT.emits(3)),
+    ("black_candle_cake", CUTOUT.emits(3)),
+    ("black_carpet", CUTOUT),
+    ("black_shulker_box", CUTOUT),
+    ("black_stained_glass", CUTOUT),
+    ("black_stained_glass_pane", CUTOUT),
+    ("black_wall_banner", PASSABLE),
+    ("blackstone_slab", CUTOUT),
+    ("blackstone_stairs", CUTOUT),
+    ("blackstone_wall", CUTOUT),
+    ("blast_furnace", SOLID.emits(13)),
+    ("blue_banner", PASSABLE),
+    ("blue_bed", CUTOUT),
+    ("blue_candle", CUTOUT.emits(3)),
+    ("blue_candle_cake", CUTOUT.emits(3)),
+    ("blue_carpet", CUTOUT),
+    ("blue_orchid", PLANT),
+    ("blue_shulker_box", CUTOUT),
+    ("blue_stained_glass", CUTOUT),
+    ("blue_stained_glass_pane", CUTOUT),
+    ("blue_wall_banner", PASSABLE),
+    ("brain_coral", PLANT),
+    ("brain_coral_fan", PLANT),
+    ("brain_coral_wall_fan", PLANT),
+    ("brewing_stand", CUTOUT.emits(1)),
+    ("brick_slab", CUTOUT),
+    ("brick_stairs", CUTOUT),
+    ("brick_wall", CUTOUT),
+    ("brown_banner", PASSABLE),
+    ("brown_bed", CUTOUT),
+    ("brown_candle", CUTOUT.emits(3)),
+    ("brown_candle_cake", CUTOUT.emits(3)),
+    ("brown_carpet", CUTOUT),
+    ("brown_mushroom", PLANT.emits(1)),
+    ("brown_shulker_box", CUTOUT),
+    ("brown_stained_glass", CUTOUT),
+    ("brown_stained_glass_pane", CUTOUT),
+    ("brown_wall_banner", PASSABLE),
+    ("bubble_column", FLUID),
+    ("bubble_coral", PLANT),
+    ("bubble_coral_fan", PLANT),
+    ("bubble_coral_wall_fan", PLANT),
+    ("cactus", CUTOUT),
+    ("cake", CUTOUT),
+    ("calibrated_sculk_sensor", CUTOUT.emits(1)),
+    ("campfire", CUTOUT.emits(15)),
+    ("candle", CUTOUT.emits(3)),
+    ("candle_cake", CUTOUT.emits(3)),
+    ("carrots", PLANT),
+    ("cauldron", CUTOUT),
+    ("cave_air", AIR),
+    ("cave_vines", PLANT.emits(14)),
+    ("cave_vines_plant", PLANT.emits(14)),
+    ("chain", CUTOUT),
+    ("cherry_button", PASSABLE),
+    ("cherry_door", CUTOUT),
+    ("cherry_fence", CUTOUT),
+    ("cherry_fence_gate", CUTOUT),
+    ("cherry_hanging_sign", PASSABLE),
+    ("cherry_leaves", TRANSLUCENT),
+    ("cherry_pressure_plate", PASSABLE),
+    ("cherry_sapling", PLANT),
+    ("cherry_sign", PASSABLE),
+    ("cherry_slab", CUTOUT),
+    ("cherry_stairs", CUTOUT),
+    ("cherry_trapdoor", CUTOUT),
+    ("cherry_wall_hanging_sign", PASSABLE),
+    ("cherry_wall_sign", PASSABLE),
+    ("chest", CUTOUT),
+    ("chipped_anvil", CUTOUT),
+    ("chorus_flower", CUTOUT),
+    ("chorus_plant", CUTOUT),
+    ("closed_eyeblossom", PLANT),
+    ("cobbled_deepslate_slab", CUTOUT),
+    ("cobbled_deepslate_stairs", CUTOUT),
+    ("cobbled_deepslate_wall", CUTOUT),
+    ("cobblestone_slab", CUTOUT),
+    ("cobblestone_stairs", CUTOUT),
+    ("cobblestone_wall", CUTOUT),
+    ("cobweb", COBWEB),
+    ("cocoa", CUTOUT),
+    ("comparator", CUTOUT.instant()),
+    ("composter", CUTOUT),
+    ("conduit", CUTOUT.emits(15)),
+    ("copper_bulb", SOLID.emits(15)),
+    ("copper_door", CUTOUT),
+    ("copper_grate", CUTOUT),
+    ("copper_trapdoor", CUTOUT),
+    ("cornflower", PLANT),
+    ("creeper_head", CUTOUT),
+    ("creeper_wall_head", CUTOUT),
+    ("crimson_button", PASSABLE),
+    ("crimson_door", CUTOUT),
+    ("crimson_fence", CUTOUT),
+    ("crimson_fence_gate", CUTOUT),
+    ("crimson_fungus", PLANT),
+    ("crimson_hanging_sign", PASSABLE),
+    ("crimson_pressure_plate", PASSABLE),
+    ("crimson_roots", PLANT),
+    ("crimson_sign", PASSABLE),
+    ("crimson_slab", CUTOUT),
+    ("crimson_stairs", CUTOUT),
+    ("crimson_trapdoor", CUTOUT),
+    ("crimson_wall_hanging_sign", PASSABLE),
+    ("crimson_wall_sign", PASSABLE),
+    ("crying_obsidian", SOLID.emits(10)),
+    ("cut_copper_slab", CUTOUT),
+    ("cut_copper_stairs", CUTOUT),
+    ("cut_red_sandstone_slab", CUTOUT),
+    ("cut_sandstone_slab", CUTOUT),
+    ("cyan_banner", PASSABLE),
+    ("cyan_bed", CUTOUT),
+    ("cyan_candle", CUTOUT.emits(3)),
+    ("cyan_candle_cake", CUTOUT.emits(3)),
+    ("cyan_carpet", CUTOUT),
+    ("cyan_shulker_box", CUTOUT),
+    ("cyan_stained_glass", CUTOUT),
+    ("cyan_stained_glass_pane", CUTOUT),
+    ("cyan_wall_banner", PASSABLE),
+    ("damaged_anvil", CUTOUT),
+    ("dandelion", PLANT),
+    ("dark_oak_button", PASSABLE),
+    ("dark_oak_door", CUTOUT),
+    ("dark_oak_fence", CUTOUT),
+    ("dark_oak_fence_gate", CUTOUT),
+    ("dark_oak_hanging_sign", PASSABLE),
+    ("dark_oak_leaves", TRANSLUCENT),
+    ("dark_oak_pressure_plate", PASSABLE),
+    ("dark_oak_sapling", PLANT),
+    ("dark_oak_sign", PASSABLE),
+    ("dark_oak_slab", CUTOUT),
+    ("dark_oak_stairs", CUTOUT),
+    ("dark_oak_trapdoor", CUTOUT),
+    ("dark_oak_wall_hanging_sign", PASSABLE),
+    ("dark_oak_wall_sign", PASSABLE),
+    ("dark_prismarine_slab", CUTOUT),
+    ("dark_prismarine_stairs", CUTOUT),
+    ("daylight_detector", CUTOUT),
+    ("dead_brain_coral", PLANT),
+    ("dead_brain_coral_fan", PLANT),
+    ("dead_brain_coral_wall_fan", PLANT),
+    ("dead_bubble_coral", PLANT),
+    ("dead_bubble_coral_fan", PLANT),
+    ("dead_bubble_coral_wall_fan", PLANT),
+    ("dead_bush", PLANT),
+    ("dead_fire_coral", PLANT),
+    ("dead_fire_coral_fan", PLANT),
+    ("dead_fire_coral_wall_fan", PLANT),
+    ("dead_horn_coral", PLANT),
+    ("dead_horn_coral_fan", PLANT),
+    ("dead_horn_coral_wall_fan", PLANT),
+    ("dead_tube_coral", PLANT),
+    ("dead_tube_coral_fan", PLANT),
+    ("dead_tube_coral_wall_fan", PLANT),
+    ("decorated_pot", CUTOUT.instant()),
+    ("deepslate_brick_slab", CUTOUT),
+    ("deepslate_brick_stairs", CUTOUT),
+    ("deepslate_brick_wall", CUTOUT),
+    ("deepslate_redstone_ore", SOLID.emits(9)),
+    ("deepslate_tile_slab", CUTOUT),
+    ("deepslate_tile_stairs", CUTOUT),
+    ("deepslate_tile_wall", CUTOUT),
+    ("detector_rail", PASSABLE),
+    ("diorite_slab", CUTOUT),
+    ("diorite_stairs", CUTOUT),
+    ("diorite_wall", CUTOUT),
+    ("dirt_path", CUTOUT),
+    ("dragon_egg", CUTOUT.emits(1)),
+    ("dragon_head", CUTOUT),
+    ("dragon_wall_head", CUTOUT),
+    ("enchanting_table", CUTOUT.emits(7)),
+    ("end_gateway", PASSABLE.emits(15)),
+    ("end_portal", PASSABLE.emits(15)),
+    ("end_portal_frame", CUTOUT.emits(1)),
+    ("end_rod", CUTOUT.emits(14).instant()),
+    ("end_stone_brick_slab", CUTOUT),
+    ("end_stone_brick_stairs", CUTOUT),
+    ("end_stone_brick_wall", CUTOUT),
+    ("ender_chest", CUTOUT.emits(7)),
+    ("exposed_copper_bulb", SOLID.emits(12)),
+    ("exposed_copper_door", CUTOUT),
+    ("exposed_copper_grate", CUTOUT),
+    ("exposed_copper_trapdoor", CUTOUT),
+    ("exposed_cut_copper_slab", CUTOUT),
+    ("exposed_cut_copper_stairs", CUTOUT),
+    ("farmland", CUTOUT),
+    ("fern", PLANT),
+    ("fire", PLANT.emits(15)),
+    ("fire_coral", PLANT),
+    ("fire_coral_fan", PLANT),
+    ("fire_coral_wall_fan", PLANT),
+    ("flower_pot", CUTOUT.instant()),
+    ("flowering_azalea", CUTOUT.instant()),
+    ("flowering_azalea_leaves", TRANSLUCENT),
+    ("frogspawn", PLANT),
+    ("frosted_ice", TRANSLUCENT),
+    ("furnace", SOLID.emits(13)),
+    ("glass", CUTOUT),
+    ("glass_pane", CUTOUT),
+    ("glow_lichen", PASSABLE.emits(7)),
+    ("glowstone", SOLID.emits(15)),
+    ("granite_slab", CUTOUT),
+    ("granite_stairs", CUTOUT),
+    ("granite_wall", CUTOUT),
+    ("gray_banner", PASSABLE),
+    ("gray_bed", CUTOUT),
+    ("gray_candle", CUTOUT.emits(3)),
+    ("gray_candle_cake", CUTOUT.emits(3)),
+    ("gray_carpet", CUTOUT),
+    ("gray_shulker_box", CUTOUT),
+    ("gray_stained_glass", CUTOUT),
+    ("gray_stained_glass_pane", CUTOUT),
+    ("gray_wall_banner", PASSABLE),
+    ("green_banner", PASSABLE),
+    ("green_bed", CUTOUT),
+    ("green_candle", CUTOUT.emits(3)),
+    ("green_candle_cake", CUTOUT.emits(3)),
+    ("green_carpet", CUTOUT),
+    ("green_shulker_box", CUTOUT),
+    ("green_stained_glass", CUTOUT),
+    ("green_stained_glass_pane", CUTOUT),
+    ("green_wall_banner", PASSABLE),
+    ("grindstone", CUTOUT),
+    ("hanging_roots", PLANT),
+    ("heavy_core", CUTOUT),
+    ("heavy_weighted_pressure_plate", PASSABLE),
+    ("honey_block", TRANSLUCENT.instant()),
+    ("hopper", CUTOUT),
+    ("horn_coral", PLANT),
+    ("horn_coral_fan", PLANT),
+    ("horn_coral_wall_fan", PLANT),
+    ("ice", TRANSLUCENT),
+    ("iron_bars", CUTOUT),
+    ("iron_door", CUTOUT),
+    ("iron_trapdoor", CUTOUT),
+    ("jack_o_lantern", SOLID.emits(15)),
+    ("jungle_button", PASSABLE),
+    ("jungle_door", CUTOUT),
+    ("jungle_fence", CUTOUT),
+    ("jungle_fence_gate", CUTOUT),
+    ("jungle_hanging_sign", PASSABLE),
+    ("jungle_leaves", TRANSLUCENT),
+    ("jungle_pressure_plate", PASSABLE),
+    ("jungle_sapling", PLANT),
+    ("jungle_sign", PASSABLE),
+    ("jungle_slab", CUTOUT),
+    ("jungle_stairs", CUTOUT),
+    ("jungle_trapdoor", CUTOUT),
+    ("jungle_wall_hanging_sign", PASSABLE),
+    ("jungle_wall_sign", PASSABLE),
+    ("kelp", FLUID.instant()),
+    ("kelp_plant", FLUID.instant()),
+    ("ladder", CUTOUT),
+    ("lantern", CUTOUT.emits(15)),
+    ("large_amethyst_bud", CUTOUT.emits(4)),
+    ("large_fern", PLANT),
+    ("lava", FLUID.emits(15)),
+    ("lava_cauldron", CUTOUT.emits(15)),
+    ("lectern", CUTOUT),
+    ("lever", PASSABLE),
+    ("light", PASSABLE),
+    ("light_blue_banner", PASSABLE),
+    ("light_blue_bed", CUTOUT),
+    ("light_blue_candle", CUTOUT.emits(3)),
+    ("light_blue_candle_cake", CUTOUT.emits(3)),
+    ("light_blue_carpet", CUTOUT),
+    ("light_blue_shulker_box", CUTOUT),
+    ("light_blue_stained_glass", CUTOUT),
+    ("light_blue_stained_glass_pane", CUTOUT),
+    ("light_blue_wall_banner", PASSABLE),
+    ("light_gray_banner", PASSABLE),
+    ("light_gray_bed", CUTOUT),
+    ("light_gray_candle", CUTOUT.emits(3)),
+    ("light_gray_candle_cake", CUTOUT.emits(3)),
+    ("light_gray_carpet", CUTOUT),
+    ("light_gray_shulker_box", CUTOUT),
+    ("light_gray_stained_glass", CUTOUT),
+    ("light_gray_stained_glass_pane", CUTOUT),
+    ("light_gray_wall_banner", PASSABLE),
+    ("light_weighted_pressure_plate", PASSABLE),
+    ("lightning_rod", CUTOUT),
+    ("lilac", PLANT),
+    ("lily_of_the_valley", PLANT),
+    ("lily_pad", CUTOUT.instant()),
+    ("lime_banner", PASSABLE),
+    ("lime_bed", CUTOUT),
+    ("lime_candle", CUTOUT.emits(3)),
+    ("lime_candle_cake", CUTOUT.emits(3)),
+    ("lime_carpet", CUTOUT),
+    ("lime_shulker_box", CUTOUT),
+    ("lime_stained_glass", CUTOUT),
+    ("lime_stained_glass_pane", CUTOUT),
+    ("lime_wall_banner", PASSABLE),
+    ("magenta_banner", PASSABLE),
+    ("magenta_bed", CUTOUT),
+    ("magenta_candle", CUTOUT.emits(3)),
+    ("magenta_candle_cake", CUTOUT.emits(3)),
+    ("magenta_carpet", CUTOUT),
+    ("magenta_shulker_box", CUTOUT),
+    ("magenta_stained_glass", CUTOUT),
+    ("magenta_stained_glass_pane", CUTOUT),
+    ("magenta_wall_banner", PASSABLE),
+    ("magma_block", SOLID.emits(3)),
+    ("mangrove_button", PASSABLE),
+    ("mangrove_door", CUTOUT),
+    ("mangrove_fence", CUTOUT),
+    ("mangrove_fence_gate", CUTOUT),
+    ("mangrove_hanging_sign", PASSABLE),
+    ("mangrove_leaves", TRANSLUCENT),
+    ("mangrove_pressure_plate", PASSABLE),
+    ("mangrove_propagule", PLANT),
+    ("mangrove_roots", CUTOUT),
+    ("mangrove_sign", PASSABLE),
+    ("mangrove_slab", CUTOUT),
+    ("mangrove_stairs", CUTOUT),
+    ("mangrove_trapdoor", CUTOUT),
+    ("mangrove_wall_hanging_sign", PASSABLE),
+    ("mangrove_wall_sign", PASSABLE),
+    ("medium_amethyst_bud", CUTOUT.emits(2)),
+    ("melon_stem", PLANT),
+    ("moss_carpet", CUTOUT),
+    ("mossy_cobblestone_slab", CUTOUT),
+    ("mossy_cobblestone_stairs", CUTOUT),
+    ("mossy_cobblestone_wall", CUTOUT),
+    ("mossy_stone_brick_slab", CUTOUT),
+    ("mossy_stone_brick_stairs", CUTOUT),
+    ("mossy_stone_brick_wall", CUTOUT),
+    ("moving_piston", PASSABLE),
+    ("mud_brick_slab", CUTOUT),
+    ("mud_brick_stairs", CUTOUT),
+    ("mud_brick_wall", CUTOUT),
+    ("nether_brick_fence", CUTOUT),
+    ("nether_brick_slab", CUTOUT),
+    ("nether_brick_stairs", CUTOUT),
+    ("nether_brick_wall", CUTOUT),
+    ("nether_portal", PASSABLE.emits(11)),
+    ("nether_sprouts", PLANT),
+    ("nether_wart", PLANT),
+    ("oak_button", PASSABLE),
+    ("oak_door", CUTOUT),
+    ("oak_fence", CUTOUT),
+    ("oak_fence_gate", CUTOUT),
+    ("oak_hanging_sign", PASSABLE),
+    ("oak_leaves", TRANSLUCENT),
+    ("oak_pressure_plate", PASSABLE),
+    ("oak_sapling", PLANT),
+    ("oak_sign", PASSABLE),
+    ("oak_slab", CUTOUT),
+    ("oak_stairs", CUTOUT),
+    ("oak_trapdoor", CUTOUT),
+    ("oak_wall_hanging_sign", PASSABLE),
+    ("oak_wall_sign", PASSABLE),
+    ("ochre_froglight", SOLID.emits(15)),
+    ("open_eyeblossom", PLANT),
+    ("orange_banner", PASSABLE),
+    ("orange_bed", CUTOUT),
+    ("orange_candle", CUTOUT.emits(3)),
+    ("orange_candle_cake", CUTOUT.emits(3)),
+    ("orange_carpet", CUTOUT),
+    ("orange_shulker_box", CUTOUT),
+    ("orange_stained_glass", CUTOUT),
+    ("orange_stained_glass_pane", CUTOUT),
+    ("orange_tulip", PLANT),
+    ("orange_wall_banner", PASSABLE),
+    ("oxeye_daisy", PLANT),
+    ("oxidized_copper_bulb", SOLID.emits(4)),
+    ("oxidized_copper_door", CUTOUT),
+    ("oxidized_copper_grate", CUTOUT),
+    ("oxidized_copper_trapdoor", CUTOUT),
+    ("oxidized_cut_copper_slab", CUTOUT),
+    ("oxidized_cut_copper_stairs", CUTOUT),
+    ("pale_hanging_moss", PLANT),
+    ("pale_moss_carpet", CUTOUT),
+    ("pale_oak_button", PASSABLE),
+    ("pale_oak_door", CUTOUT),
+    ("pale_oak_fence", CUTOUT),
+    ("pale_oak_fence_gate", CUTOUT),
+    ("pale_oak_hanging_sign", PASSABLE),
+    ("pale_oak_leaves", TRANSLUCENT),
+    ("pale_oak_pressure_plate", PASSABLE),
+    ("pale_oak_sapling", PLANT),
+    ("pale_oak_sign", PASSABLE),
+    ("pale_oak_slab", CUTOUT),
+    ("pale_oak_stairs", CUTOUT),
+    ("pale_oak_trapdoor", CUTOUT),
+    ("pale_oak_wall_hanging_sign", PASSABLE),
+    ("pale_oak_wall_sign", PASSABLE),
+    ("pearlescent_froglight", SOLID.emits(15)),
+    ("peony", PLANT),
+    ("petrified_oak_slab", CUTOUT),
+    ("piglin_head", CUTOUT),
+    ("piglin_wall_head", CUTOUT),
+    ("pink_banner", PASSABLE),
+    ("pink_bed", CUTOUT),
+    ("pink_candle", CUTOUT.emits(3)),
+    ("pink_candle_cake", CUTOUT.emits(3)),
+    ("pink_carpet", CUTOUT),
+    ("pink_petals", PLANT),
+    ("pink_shulker_box", CUTOUT),
+    ("pink_stained_glass", CUTOUT),
+    ("pink_stained_glass_pane", CUTOUT),
+    ("pink_tulip", PLANT),
+    ("pink_wall_banner", PASSABLE),
+    ("piston_head", CUTOUT),
+    ("pitcher_crop", PLANT),
+    ("pitcher_plant", PLANT),
+    ("player_head", CUTOUT),
+    ("player_wall_head", CUTOUT),
+    ("pointed_dripstone", CUTOUT),
+    ("polished_andesite_slab", CUTOUT),
+    ("polished_andesite_stairs", CUTOUT),
+    ("polished_blackstone_brick_slab", CUTOUT),
+    ("polished_blackstone_brick_stairs", CUTOUT),
+    ("polished_blackstone_brick_wall", CUTOUT),
+    ("polished_blackstone_button", PASSABLE),
+    ("polished_blackstone_pressure_plate", PASSABLE),
+    ("polished_blackstone_slab", CUTOUT),
+    ("polished_blackstone_stairs", CUTOUT),
+    ("polished_blackstone_wall", CUTOUT),
+    ("polished_deepslate_slab", CUTOUT),
+    ("polished_deepslate_stairs", CUTOUT),
+    ("polished_deepslate_wall", CUTOUT),
+    ("polished_diorite_slab", CUTOUT),
+    ("polished_diorite_stairs", CUTOUT),
+    ("polished_granite_slab", CUTOUT),
+    ("polished_granite_stairs", CUTOUT),
+    ("polished_tuff_slab", CUTOUT),
+    ("polished_tuff_stairs", CUTOUT),
+    ("polished_tuff_wall", CUTOUT),
+    ("poppy", PLANT),
+    ("potatoes", PLANT),
+    ("potted_acacia_sapling", CUTOUT.instant()),
+    ("potted_allium", CUTOUT.instant()),
+    ("potted_azalea_bush", CUTOUT.instant()),
+    ("potted_azure_bluet", CUTOUT.instant()),
+    ("potted_bamboo", CUTOUT.instant()),
+    ("potted_birch_sapling", CUTOUT.instant()),
+    ("potted_blue_orchid", CUTOUT.instant()),
+    ("potted_brown_mushroom", CUTOUT.instant()),
+    ("potted_cactus", CUTOUT.instant()),
+    ("potted_cherry_sapling", CUTOUT.instant()),
+    ("potted_closed_eyeblossom", CUTOUT.instant()),
+    ("potted_cornflower", CUTOUT.instant()),
+    ("potted_crimson_fungus", CUTOUT.instant()),
+    ("potted_crimson_roots", CUTOUT.instant()),
+    ("potted_dandelion", CUTOUT.instant()),
+    ("potted_dark_oak_sapling", CUTOUT.instant()),
+    ("potted_dead_bush", CUTOUT.instant()),
+    ("potted_fern", CUTOUT.instant()),
+    ("potted_flowering_azalea_bush", CUTOUT.instant()),
+    ("potted_jungle_sapling", CUTOUT.instant()),
+    ("potted_lily_of_the_valley", CUTOUT.instant()),
+    ("potted_mangrove_propagule", CUTOUT.instant()),
+    ("potted_oak_sapling", CUTOUT.instant()),
+    ("potted_open_eyeblossom", CUTOUT.instant()),
+    ("potted_orange_tulip", CUTOUT.instant()),
+    ("potted_oxeye_daisy", CUTOUT.instant()),
+    ("potted_pale_oak_sapling", CUTOUT.instant()),
+    ("potted_pink_tulip", CUTOUT.instant()),
+    ("potted_poppy", CUTOUT.instant()),
+    ("potted_red_mushroom", CUTOUT.instant()),
+    ("potted_red_tulip", CUTOUT.instant()),
+    ("potted_spruce_sapling", CUTOUT.instant()),
+    ("potted_torchflower", CUTOUT.instant()),
+    ("potted_warped_fungus", CUTOUT.instant()),
+    ("potted_warped_roots", CUTOUT.instant()),
+    ("potted_white_tulip", CUTOUT.instant()),
+    ("potted_wither_rose", CUTOUT.instant()),
+    ("powder_snow", TRANSLUCENT),
+    ("powder_snow_cauldron", CUTOUT),
+    ("powered_rail", PASSABLE),
+    ("prismarine_brick_slab", CUTOUT),
+    ("prismarine_brick_stairs", CUTOUT),
+    ("prismarine_slab", CUTOUT),
+    ("prismarine_stairs", CUTOUT),
+    ("prismarine_wall", CUTOUT),
+    ("pumpkin_stem", PLANT),
+    ("purple_banner", PASSABLE),
+    ("purple_bed", CUTOUT),
+    ("purple_candle", CUTOUT.emits(3)),
+    ("purple_candle_cake", CUTOUT.emits(3)),
+    ("purple_carpet", CUTOUT),
+    ("purple_shulker_box", CUTOUT),
+    ("purple_stained_glass", CUTOUT),
+    ("purple_stained_glass_pane", CUTOUT),
+    ("purple_wall_banner", PASSABLE),
+    ("purpur_slab", CUTOUT),
+    ("purpur_stairs", CUTOUT),
+    ("quartz_slab", CUTOUT),
+    ("quartz_stairs", CUTOUT),
+    ("rail", PASSABLE),
+    ("red_banner", PASSABLE),
+    ("red_bed", CUTOUT),
+    ("red_candle", CUTOUT.emits(3)),
+    ("red_candle_cake", CUTOUT.emits(3)),
+    ("red_carpet", CUTOUT),
+    ("red_mushroom", PLANT),
+    ("red_nether_brick_slab", CUTOUT),
+    ("red_nether_brick_stairs", CUTOUT),
+    ("red_nether_brick_wall", CUTOUT),
+    ("red_sandstone_slab", CUTOUT),
+    ("red_sandstone_stairs", CUTOUT),
+    ("red_sandstone_wall", CUTOUT),
+    ("red_shulker_box", CUTOUT),
+    ("red_stained_glass", CUTOUT),
+    ("red_stained_glass_pane", CUTOUT),
+    ("red_tulip", PLANT),
+    ("red_wall_banner", PASSABLE),
+    ("redstone_lamp", SOLID.emits(15)),
+    ("redstone_ore", SOLID.emits(9)),
+    ("redstone_torch", PASSABLE.emits(7).instant()),
+    ("redstone_wall_torch", PASSABLE.emits(7).instant()),
+    ("redstone_wire", PASSABLE.instant()),
+    ("repeater", CUTOUT.instant()),
+    ("resin_brick_slab", CUTOUT),
+    ("resin_brick_stairs", CUTOUT),
+    ("resin_brick_wall", CUTOUT),
+    ("resin_clump", PASSABLE),
+    ("respawn_anchor", SOLID.emits(15)),
+    ("rose_bush", PLANT),
+    ("sandstone_slab", CUTOUT),
+    ("sandstone_stairs", CUTOUT),
+    ("sandstone_wall", CUTOUT),
+    ("scaffolding", CUTOUT.instant()),
+    ("sculk_catalyst", SOLID.emits(6)),
+    ("sculk_sensor", CUTOUT.emits(1)),
+    ("sculk_shrieker", CUTOUT),
+    ("sculk_vein", PASSABLE),
+    ("sea_lantern", SOLID.emits(15)),
+    ("sea_pickle", CUTOUT.emits(3).instant()),
+    ("seagrass", FLUID.instant()),
+    ("short_grass", PLANT),
+    ("shroomlight", SOLID.emits(15)),
+    ("shulker_box", CUTOUT),
+    ("skeleton_skull", CUTOUT),
+    ("skeleton_wall_skull", CUTOUT),
+    ("slime_block", TRANSLUCENT.instant()),
+    ("small_amethyst_bud", CUTOUT.emits(1)),
+    ("small_dripleaf", PLANT),
+    ("smoker", SOLID.emits(13)),
+    ("smooth_quartz_slab", CUTOUT),
+    ("smooth_quartz_stairs", CUTOUT),
+    ("smooth_red_sandstone_slab", CUTOUT),
+    ("smooth_red_sandstone_stairs", CUTOUT),
+    ("smooth_sandstone_slab", CUTOUT),
+    ("smooth_sandstone_stairs", CUTOUT),
+    ("smooth_stone_slab", CUTOUT),
+    ("sniffer_egg", CUTOUT),
+    ("snow", CUTOUT),
+    ("soul_campfire", CUTOUT.emits(10)),
+    ("soul_fire", PLANT.emits(10)),
+    ("soul_lantern", CUTOUT.emits(10)),
+    ("soul_torch", PASSABLE.emits(10).instant()),
+    ("soul_wall_torch", PASSABLE.emits(10).instant()),
+    ("spawner", CUTOUT),
+    ("spore_blossom", PLANT),
+    ("spruce_button", PASSABLE),
+    ("spruce_door", CUTOUT),
+    ("spruce_fence", CUTOUT),
+    ("spruce_fence_gate", CUTOUT),
+    ("spruce_hanging_sign", PASSABLE),
+    ("spruce_leaves", TRANSLUCENT),
+    ("spruce_pressure_plate", PASSABLE),
+    ("spruce_sapling", PLANT),
+    ("spruce_sign", PASSABLE),
+    ("spruce_slab", CUTOUT),
+    ("spruce_stairs", CUTOUT),
+    ("spruce_trapdoor", CUTOUT),
+    ("spruce_wall_hanging_sign", PASSABLE),
+    ("spruce_wall_sign", PASSABLE),
+    ("stone_brick_slab", CUTOUT),
+    ("stone_brick_stairs", CUTOUT),
+    ("stone_brick_wall", CUTOUT),
+    ("stone_button", PASSABLE),
+    ("stone_pressure_plate", PASSABLE),
+    ("stone_slab", CUTOUT),
+    ("stone_stairs", CUTOUT),
+    ("stonecutter", CUTOUT),
+    ("structure_void", PASSABLE.instant()),
+    ("sugar_cane", PLANT),
+    ("sunflower", PLANT),
+    ("sweet_berry_bush", PLANT),
+    ("tall_grass", PLANT),
+    ("tall_seagrass", FLUID.instant()),
+    ("tnt", SOLID.instant()),
+    ("torch", PASSABLE.emits(14).instant()),
+    ("torchflower", PLANT),
+    ("torchflower_crop", PLANT),
+    ("trapped_chest", CUTOUT),
+    ("trial_spawner", CUTOUT),
+    ("tripwire", PASSABLE.instant()),
+    ("tripwire_hook", PASSABLE.instant()),
+    ("tube_coral", PLANT),
+    ("tube_coral_fan", PLANT),
+    ("tube_coral_wall_fan", PLANT),
+    ("tuff_brick_slab", CUTOUT),
+    ("tuff_brick_stairs", CUTOUT),
+    ("tuff_brick_wall", CUTOUT),
+    ("tuff_slab", CUTOUT),
+    ("tuff_stairs", CUTOUT),
+    ("tuff_wall", CUTOUT),
+    ("turtle_egg", CUTOUT),
+    ("twisting_vines", PLANT),
+    ("twisting_vines_plant", PLANT),
+    ("vault", CUTOUT),
+    ("verdant_froglight", SOLID.emits(15)),
+    ("vine", PASSABLE),
+    ("void_air", AIR),
+    ("wall_torch", PASSABLE.emits(14).instant()),
+    ("warped_button", PASSABLE),
+    ("warped_door", CUTOUT),
+    ("warped_fence", CUTOUT),
+    ("warped_fence_gate", CUTOUT),
+    ("warped_fungus", PLANT),
+    ("warped_hanging_sign", PASSABLE),
+    ("warped_pressure_plate", PASSABLE),
+    ("warped_roots", PLANT),
+    ("warped_sign", PASSABLE),
+    ("warped_slab", CUTOUT),
+    ("warped_stairs", CUTOUT),
+    ("warped_trapdoor", CUTOUT),
+    ("warped_wall_hanging_sign", PASSABLE),
+    ("warped_wall_sign", PASSABLE),
+    ("water", FLUID),
+    ("water_cauldron", CUTOUT),
+    ("waxed_copper_bulb", SOLID.emits(15)),
+    ("waxed_copper_door", CUTOUT),
+    ("waxed_copper_grate", CUTOUT),
+    ("waxed_copper_trapdoor", CUTOUT),
+    ("waxed_cut_copper_slab", CUTOUT),
+    ("waxed_cut_copper_stairs", CUTOUT),
+    ("waxed_exposed_copper_bulb", SOLID.emits(12)),
+    ("waxed_exposed_copper_door", CUTOUT),
+    ("waxed_exposed_copper_grate", CUTOUT),
+    ("waxed_exposed_copper_trapdoor", CUTOUT),
+    ("waxed_exposed_cut_copper_slab", CUTOUT),
+    ("waxed_exposed_cut_copper_stairs", CUTOUT),
+    ("waxed_oxidized_copper_bulb", SOLID.emits(4)),
+    ("waxed_oxidized_copper_door", CUTOUT),
+    ("waxed_oxidized_copper_grate", CUTOUT),
+    ("waxed_oxidized_copper_trapdoor", CUTOUT),
+    ("waxed_oxidized_cut_copper_slab", CUTOUT),
+    ("waxed_oxidized_cut_copper_stairs", CUTOUT),
+    ("waxed_weathered_copper_bulb", SOLID.emits(8)),
+    ("waxed_weathered_copper_door", CUTOUT),
+    ("waxed_weathered_copper_grate", CUTOUT),
+    ("waxed_weathered_copper_trapdoor", CUTOUT),
+    ("waxed_weathered_cut_copper_slab", CUTOUT),
+    ("waxed_weathered_cut_copper_stairs", CUTOUT),
+    ("weathered_copper_bulb", SOLID.emits(8)),
+    ("weathered_copper_door", CUTOUT),
+    ("weathered_copper_grate", CUTOUT),
+    ("weathered_copper_trapdoor", CUTOUT),
+    ("weathered_cut_copper_slab", CUTOUT),
+    ("weathered_cut_copper_stairs", CUTOUT),
+    ("weeping_vines", PLANT),
+    ("weeping_vines_plant", PLANT),
+    ("wheat", PLANT),
+    ("white_banner", PASSABLE),
+    ("white_bed", CUTOUT),
+    ("white_candle", CUTOUT.emits(3)),
+    ("white_candle_cake", CUTOUT.emits(3)),
+    ("white_carpet", CUTOUT),
+    ("white_shulker_box", CUTOUT),
+    ("white_stained_glass", CUTOUT),
+    ("white_stained_glass_pane", CUTOUT),
+    ("white_tulip", PLANT),
+    ("white_wall_banner", PASSABLE),
+    ("wither_rose", PLANT),
+    ("wither_skeleton_skull", CUTOUT),
+    ("wither_skeleton_wall_skull", CUTOUT),
+    ("yellow_banner", PASSABLE),
+    ("yellow_bed", CUTOUT),
+    ("yellow_candle", CUTOUT.emits(3)),
+    ("yellow_candle_cake", CUTOUT.emits(3)),
+    ("yellow_carpet", CUTOUT),
+    ("yellow_shulker_box", CUTOUT),
+    ("yellow_stained_glass", CUTOUT),
+    ("yellow_stained_glass_pane", CUTOUT),
+    ("yellow_wall_banner", PASSABLE),
+    ("zombie_head", CUTOUT),
+    ("zombie_wall_head", CUTOUT),
+];
+
+impl BlockProps {
+
+    const fn emits(mut self, emission : u8) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    const fn instant(mut self) -> Self {
+        self.breaks_instantly = true;
+        self
+    }
+
+    pub(crate) fn of(block : RegEntry<BlockState>) -> Self {
+        BLOCK_PROPS.get(block.id() as usize).copied().unwrap_or(SOLID)
+    }
+
+    /// Looks up the properties of a block in [`BLOCKS`], and applies those which depend on its state.
+    fn of_state(state : &BlockState) -> Self {
+        let id   = state.id.to_string();
+        let name = id.strip_prefix("minecraft:").unwrap_or(&id);
+        let mut props = BLOCKS.binary_search_by_key(&name, |&(name, _,)| name).map_or(SOLID, |i| BLOCKS[i].1);
+
+        let property = |key : &str| state.properties.get(key).map(String::as_str);
+        let number   = |key : &str| property(key).and_then(|value| value.parse::<u8>().ok()).unwrap_or(0);
+
+        if (property("lit") == Some("false")) {
+            props.emission = 0;
+        }
+        props.emission = match (name) {
+            "light" => number("level"),
+            "respawn_anchor" => [0, 3, 7, 11, 15][number("charges").min(4) as usize],
+            "sea_pickle" => if (property("waterlogged") == Some("true")) { 3 + (3 * number("pickles")) } else { 0 },
+            "cave_vines" | "cave_vines_plant" => if (property("berries") == Some("true")) { props.emission } else { 0 },
+            "trial_spawner" => match (property("trial_spawner_state")) {
+                Some("waiting_for_players") => 4,
+                Some("active" | "waiting_for_reward_ejection" | "ejecting_reward") => 8,
+                _ => 0
+            },
+            "vault" => if (property("vault_state") == Some("inactive")) { 6 } else { 12 },
+            _ if (props.emission > 0 && (name.ends_with("candle") || name.ends_with("candle_cake")))
+                => props.emission * number("candles").max(1),
+            _ => props.emission
+        };
+
+        if (property("type") == Some("double") || (name == "snow" && number("layers") >= 8)) {
+            props.opacity = 15;
+        }
+        if (property("waterlogged") == Some("true")) {
+            props.opacity       = props.opacity.max(1);
+            props.blocks_motion = true;
+        }
+        props
+    }
+
+}
@@ -0,0 +1,142 @@
+use crate::world::{ World, in_section_block_linearise };
+use flywheelmc_common::prelude::*;
+use protocol::value::{ BlockState, BlockPos };
+use protocol::registry::RegEntry;
+
+
+/// Returns the chunk position, section index, and in-section block index of a block,
+///  in a world starting at section `min_section` which is `section_count` sections tall.
+fn locate_block(min_section : i32, section_count : usize, pos : BlockPos) -> Option<(Vec2<i32>, usize, u16,)> {
+    let cy   = usize::try_from(pos.y.div_floor(16) - min_section).ok()?;
+    if (cy >= section_count) { return None; }
+    let cpos = Vec2::new(pos.x.div_floor(16), pos.z.div_floor(16));
+    let dx   = pos.x.rem_euclid(16) as u8;
+    let dy   = pos.y.rem_euclid(16) as u8;
+    let dz   = pos.z.rem_euclid(16) as u8;
+    Some((cpos, cy, in_section_block_linearise(dx, dy, dz),))
+}
+
+
+impl World {
+
+    /// Returns the chunk position, section index, and in-section block index of a block.
+    #[inline]
+    pub(super) fn locate_block(&self, pos : BlockPos) -> Option<(Vec2<i32>, usize, u16,)> {
+        locate_block(self.min_section(), self.section_count(), pos)
+    }
+
+    /// Returns the registry entry of the block at `pos`.
+    ///
+    /// Returns `None` if the chunk containing `pos` is not loaded and ready, or if `pos` is out of the world's height range.
+    pub fn get_block_entry(&self, pos : BlockPos) -> Option<RegEntry<BlockState>> {
+        let (cpos, cy, linear_xyz,) = self.locate_block(pos)?;
+        let chunk = self.chunks.get(&cpos)?;
+        if (! chunk.ready) { return None; }
+        chunk.sections.get(cy)?.checked_get(linear_xyz)
+    }
+
+    /// Returns the block state at `pos`, with its properties decoded.
+    ///
+    /// Returns `None` if the chunk containing `pos` is not loaded and ready, or if `pos` is out of the world's height range.
+    pub fn get_block(&self, pos : BlockPos) -> Option<BlockState> {
+        BlockState::from_id(self.get_block_entry(pos)?.id())
+    }
+
+    /// Iterates over every block in the cuboid between `a` and `b` (inclusive), as registry entries.
+    ///
+    /// Blocks in chunks which are not loaded and ready are skipped.
+    pub fn blocks_in(&self, a : BlockPos, b : BlockPos) -> impl Iterator<Item = (BlockPos, RegEntry<BlockState>,)> {
+        let min = BlockPos { x : a.x.min(b.x), y : a.y.min(b.y), z : a.z.min(b.z) };
+        let max = BlockPos { x : a.x.max(b.x), y : a.y.max(b.y), z : a.z.max(b.z) };
+        (min.y..=max.y).flat_map(move |y|
+            (min.z..=max.z).flat_map(move |z|
+                (min.x..=max.x).map(move |x| BlockPos { x, y, z })
+            )
+        ).filter_map(|pos| Some((pos, self.get_block_entry(pos)?,)))
+    }
+
+    /// Iterates over every block in the cuboid between `a` and `b` (inclusive) for which `predicate` returns `true`.
+    ///
+    /// Each distinct block state is only decoded once.
+    pub fn find_blocks<F>(&self, a : BlockPos, b : BlockPos, mut predicate : F) -> impl Iterator<Item = (BlockPos, BlockState,)>
+    where
+        F : FnMut(&BlockState) -> bool
+    {
+        let mut decoded = BTreeMap::<u32, Option<BlockState>>::new();
+        self.blocks_in(a, b).filter_map(move |(pos, entry,)| {
+            let state = decoded.entry(entry.id()).or_insert_with(||
+                BlockState::from_id(entry.id()).filter(|state| predicate(state))
+            );
+            Some((pos, state.clone()?,))
+        })
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::in_section_block_delinearise;
+
+    /// `(min_y, height)` of the overworld, the nether and end, and the largest and smallest custom dimensions.
+    const DIMS : [(i32, u32,); 4] = [(-64, 384,), (0, 256,), (-2032, 4064,), (16, 16,)];
+
+    const COORDS : [i32; 11] = [i32::MIN / 16, -33, -17, -16, -1, 0, 1, 15, 16, 31, (i32::MAX / 16) - 1];
+
+    fn locate(min_y : i32, height : u32, pos : BlockPos) -> Option<(Vec2<i32>, usize, u16,)> {
+        locate_block(min_y.div_floor(16), (height / 16) as usize, pos)
+    }
+
+    #[test]
+    fn locate_round_trips() {
+        for (min_y, height,) in DIMS {
+            let max_y = min_y + (height as i32);
+            for y in (min_y - 40)..(max_y + 40) {
+                for x in COORDS {
+                    for z in COORDS {
+                        let pos = BlockPos { x, y, z };
+                        let Some((cpos, cy, linear_xyz,)) = locate(min_y, height, pos) else {
+                            assert!((y < min_y) || (y >= max_y), "{y} is in <{min_y}, {max_y}> but was not located");
+                            continue;
+                        };
+                        assert!((y >= min_y) && (y < max_y), "{y} is outside <{min_y}, {max_y}> but was located");
+                        let [dx, dy, dz] = in_section_block_delinearise(linear_xyz);
+                        assert!((dx < 16) && (dy < 16) && (dz < 16));
+                        assert_eq!((cpos.x * 16) + (dx as i32), x);
+                        assert_eq!((cpos.y * 16) + (dz as i32), z);
+                        assert_eq!(min_y + ((cy as i32) * 16) + (dy as i32), y);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn locate_height_boundaries() {
+        for (min_y, height,) in DIMS {
+            let max_y   = min_y + (height as i32);
+            let last_cy = ((height / 16) as usize) - 1;
+            let at = |y| locate(min_y, height, BlockPos { x : 0, y, z : 0 })
+                .map(|(_, cy, linear_xyz,)| (cy, in_section_block_delinearise(linear_xyz)[1],));
+            assert_eq!(at(min_y - 1), None);
+            assert_eq!(at(min_y),      Some((0, 0,)));
+            assert_eq!(at(min_y + 15), Some((0, 15,)));
+            assert_eq!(at(min_y + 16), (last_cy > 0).then_some((1, 0,)));
+            assert_eq!(at(max_y - 1),  Some((last_cy, 15,)));
+            assert_eq!(at(max_y),      None);
+        }
+    }
+
+    #[test]
+    fn locate_negative_chunks() {
+        let (cpos, _, linear_xyz,) = locate(-64, 384, BlockPos { x : -1, y : -1, z : -16 }).unwrap();
+        assert_eq!((cpos.x, cpos.y,), (-1, -1,));
+        assert_eq!(in_section_block_delinearise(linear_xyz), [15, 15, 0]);
+        let (cpos, cy, linear_xyz,) = locate(-64, 384, BlockPos { x : -17, y : -64, z : 16 }).unwrap();
+        assert_eq!((cpos.x, cpos.y,), (-2, 1,));
+        assert_eq!(cy, 0);
+        assert_eq!(in_section_block_delinearise(linear_xyz), [15, 0, 0]);
+    }
+
+}
@@ -0,0 +1,448 @@
+use crate::world::{ World, Block, BlockEntity, PlaceError, BLOCK_AIR, state_entry };
+use crate::world::nbt::{ self, Tag };
+use crate::world::anvil::DATA_VERSION;
+use flywheelmc_common::prelude::*;
+use protocol::value::{ Identifier, BlockState, BlockPos, BlockEntityType };
+use protocol::registry::{ Registry, RegEntry };
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::io::{ Read, Write };
+use std::error::Error;
+use std::fmt::{ self, Display };
+use std::path::Path;
+
+
+/// A cuboid of blocks and block entities, read from or written to a schematic file.
+///
+/// Sponge `.schem` (versions 2 and 3) and legacy MCEdit `.schematic` files can be read.
+///  Schematics are always written as Sponge `.schem` version 2.
+#[derive(Clone)]
+pub struct Schematic {
+    width          : u16,
+    height         : u16,
+    length         : u16,
+    palette        : Vec<RegEntry<BlockState>>,
+    /// Palette indices, ordered by Y, then Z, then X.
+    blocks         : Vec<u32>,
+    /// Block entities, by position relative to the schematic origin.
+    block_entities : Vec<([i32; 3], Identifier, Tag,)>
+}
+
+impl Schematic {
+
+    #[inline]
+    pub fn size(&self) -> Vec3<u16> { Vec3::new(self.width, self.height, self.length) }
+
+    #[inline]
+    fn index(&self, x : u16, y : u16, z : u16) -> usize {
+        (x as usize) + ((z as usize) * (self.width as usize)) + ((y as usize) * (self.width as usize) * (self.length as usize))
+    }
+
+    /// Reads a gzipped schematic file.
+    pub fn load(path : impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Reads a gzipped schematic.
+    ///
+    /// Fails if the schematic is malformed, or if it is an MCEdit schematic
+    ///  with block ids that can not be mapped to modern blocks.
+    pub fn from_bytes(bytes : &[u8]) -> io::Result<Self> {
+        let mut data = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut data)?;
+        let root = nbt::read_root(&data)?;
+        // Version 3 nests everything in a `Schematic` compound.
+        let root = root.get("Schematic").unwrap_or(&root);
+        if (root.get("Blocks").is_some_and(|blocks| blocks.as_byte_array().is_some())) {
+            Self::from_mcedit(root)
+        } else {
+            Self::from_sponge(root)
+        }
+    }
+
+    fn from_sponge(root : &Tag) -> io::Result<Self> {
+        let (width, height, length,) = (read_dim(root, "Width")?, read_dim(root, "Height")?, read_dim(root, "Length")?,);
+        // Version 3 nests block data in a `Blocks` compound.
+        let blocks_tag = root.get("Blocks").unwrap_or(root);
+        let palette_tag = blocks_tag.get("Palette").and_then(Tag::as_compound).ok_or_else(|| invalid("missing palette"))?;
+        let mut palette = vec![BLOCK_AIR; palette_tag.len()];
+        let mut unknown = BTreeSet::<&str>::new();
+        for (state, index,) in palette_tag {
+            let index = index.as_int().and_then(|index| usize::try_from(index).ok())
+                .filter(|&index| index < palette.len()).ok_or_else(|| invalid("bad palette index"))?;
+            match (parse_block_state(state)) {
+                Some(block) => { palette[index] = block; },
+                None        => { unknown.insert(state.as_str()); }
+            }
+        }
+        if (! unknown.is_empty()) {
+            let names = unknown.into_iter().collect::<Vec<_>>().join(", ");
+            return Err(invalid(&format!("unknown blocks {names}")));
+        }
+        let data = blocks_tag.get("Data").or_else(|| root.get("BlockData"))
+            .and_then(Tag::as_byte_array).ok_or_else(|| invalid("missing block data"))?;
+        let blocks = read_varints(data)?;
+        if (blocks.len() != (width as usize) * (height as usize) * (length as usize)) {
+            return Err(invalid("block data does not match dimensions"));
+        }
+        if (blocks.iter().any(|&index| (index as usize) >= palette.len())) {
+            return Err(invalid("block data refers to a missing palette entry"));
+        }
+
+        let mut block_entities = Vec::new();
+        for entry in blocks_tag.get("BlockEntities").or_else(|| root.get("BlockEntities")).and_then(Tag::as_list).unwrap_or(&[]) {
+            let Some([x, y, z]) = entry.get("Pos").and_then(Tag::as_int_array).and_then(|pos| pos.try_into().ok())
+                else { continue; };
+            let Some(id) = entry.get("Id").and_then(Tag::as_str)
+                else { continue; };
+            // Version 3 nests block entity data in a `Data` compound.
+            let data = match (entry.get("Data")) {
+                Some(data) => data.clone(),
+                None       => strip_keys(entry, &["Pos", "Id"])
+            };
+            block_entities.push(([x, y, z], Identifier::from(id), data,));
+        }
+
+        Ok(Self { width, height, length, palette, blocks, block_entities })
+    }
+
+    fn from_mcedit(root : &Tag) -> io::Result<Self> {
+        let (width, height, length,) = (read_dim(root, "Width")?, read_dim(root, "Height")?, read_dim(root, "Length")?,);
+        let ids   = root.get("Blocks").and_then(Tag::as_byte_array).ok_or_else(|| invalid("missing blocks"))?;
+        let datas = root.get("Data").and_then(Tag::as_byte_array).unwrap_or(&[]);
+        let extra = root.get("AddBlocks").and_then(Tag::as_byte_array).unwrap_or(&[]);
+        // Some exporters include a mapping from legacy ids to names.
+        let mapping = root.get("SchematicaMapping").or_else(|| root.get("BlockIDs")).and_then(Tag::as_compound);
+
+        let mut palette = Vec::<RegEntry<BlockState>>::new();
+        let mut lookup  = BTreeMap::<(u16, u8), u32>::new();
+        let mut blocks  = Vec::with_capacity(ids.len());
+        let mut unknown = BTreeSet::<(u16, u8)>::new();
+        for (i, &id) in ids.iter().enumerate() {
+            let high = extra.get(i / 2).map_or(0, |&b| if (i % 2 == 0) { (b as u8) >> 4 } else { (b as u8) & 0x0F }) as u16;
+            let id   = ((high << 8) | (id as u8 as u16), datas.get(i).map_or(0, |&d| (d as u8) & 0x0F),);
+            let index = *lookup.entry(id).or_insert_with(|| {
+                let name = mapping
+                    .and_then(|mapping| mapping.iter().find(|(_, v)| v.as_int() == Some(id.0 as i64)).map(|(k, _)| k.clone()))
+                    .or_else(|| legacy_block_name(id.0, id.1));
+                let block = name.as_deref().and_then(parse_block_state);
+                if (block.is_none() && id.0 != 0) { unknown.insert(id); }
+                palette.push(block.unwrap_or(BLOCK_AIR));
+                (palette.len() - 1) as u32
+            });
+            blocks.push(index);
+        }
+        if (! unknown.is_empty()) {
+            let ids = unknown.iter().map(|(id, data,)| format!("{id}:{data}")).collect::<Vec<_>>().join(", ");
+            return Err(invalid(&format!("unknown legacy block ids {ids}")));
+        }
+        if (blocks.len() != (width as usize) * (height as usize) * (length as usize)) {
+            return Err(invalid("block data does not match dimensions"));
+        }
+
+        let mut block_entities = Vec::new();
+        for entry in root.get("TileEntities").and_then(Tag::as_list).unwrap_or(&[]) {
+            let coord = |key : &str| entry.get(key).and_then(Tag::as_int).map(|v| v as i32);
+            let (Some(x), Some(y), Some(z), Some(id),) = (coord("x"), coord("y"), coord("z"), entry.get("id").and_then(Tag::as_str))
+                else { continue; };
+            block_entities.push(([x, y, z], Identifier::from(id), strip_keys(entry, &["x", "y", "z", "id"]),));
+        }
+
+        Ok(Self { width, height, length, palette, blocks, block_entities })
+    }
+
+    /// Writes this schematic to a gzipped Sponge `.schem` file.
+    pub fn save(&self, path : impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_bytes()?)
+    }
+
+    /// Encodes this schematic as a gzipped Sponge `.schem` (version 2).
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut palette = BTreeMap::new();
+        for (i, &entry) in self.palette.iter().enumerate() {
+            palette.insert(format_block_state(entry), Tag::Int(i as i32));
+        }
+        let mut block_data = Vec::new();
+        for &index in &self.blocks {
+            write_varint(&mut block_data, index);
+        }
+        let block_entities = self.block_entities.iter().map(|([x, y, z], id, data,)| {
+            let mut compound = data.as_compound().cloned().unwrap_or_default();
+            compound.insert("Pos".to_string(), Tag::IntArray(vec![*x, *y, *z]));
+            compound.insert("Id".to_string(), Tag::String(id.to_string()));
+            Tag::Compound(compound)
+        }).collect();
+
+        let mut root = BTreeMap::new();
+        root.insert("Version".to_string(), Tag::Int(2));
+        root.insert("DataVersion".to_string(), Tag::Int(DATA_VERSION));
+        root.insert("Width".to_string(), Tag::Short(self.width as i16));
+        root.insert("Height".to_string(), Tag::Short(self.height as i16));
+        root.insert("Length".to_string(), Tag::Short(self.length as i16));
+        root.insert("Offset".to_string(), Tag::IntArray(vec![0, 0, 0]));
+        root.insert("PaletteMax".to_string(), Tag::Int(self.palette.len() as i32));
+        root.insert("Palette".to_string(), Tag::Compound(palette));
+        root.insert("BlockData".to_string(), Tag::ByteArray(block_data.into_iter().map(|b| b as i8).collect()));
+        root.insert("BlockEntities".to_string(), Tag::List(block_entities));
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&nbt::write_root(&Tag::Compound(root)))?;
+        encoder.finish()
+    }
+
+}
+
+
+impl World {
+
+    /// Pastes a schematic with its minimum corner at `at`.
+    ///
+    /// Block entities whose type is not in `block_entity_types` are skipped.
+    pub fn paste_schematic(&mut self, schematic : &Schematic, at : BlockPos, block_entity_types : &Registry<BlockEntityType>) -> Result<(), PlaceError> {
+        let mut blocks = Vec::with_capacity(schematic.blocks.len());
+        for y in 0..schematic.height {
+            for z in 0..schematic.length {
+                for x in 0..schematic.width {
+                    let block = schematic.palette[schematic.blocks[schematic.index(x, y, z)] as usize];
+                    blocks.push((BlockPos { x : at.x + (x as i32), y : at.y + (y as i32), z : at.z + (z as i32) }, Block::Entry(block),));
+                }
+            }
+        }
+        let result = self.set_blocks(blocks);
+
+        for ([x, y, z], id, data,) in &schematic.block_entities {
+            let Some(kind) = block_entity_types.get_entry(id) else {
+                warn!("Skipping block entity of unknown type {} in schematic", id);
+                continue;
+            };
+            if let Some(block_entity) = BlockEntity::from_data_tag(kind, data) {
+                let _ = self.set_block_entity(BlockPos { x : at.x + x, y : at.y + y, z : at.z + z }, block_entity);
+            }
+        }
+
+        result
+    }
+
+    /// Copies the cuboid between `a` and `b` (inclusive) into a schematic.
+    ///
+    /// Blocks in chunks which are not loaded and ready are copied as air.
+    pub fn copy_schematic(&self, a : BlockPos, b : BlockPos, block_entity_types : &Registry<BlockEntityType>) -> Result<Schematic, SchematicTooLarge> {
+        let min  = BlockPos { x : a.x.min(b.x), y : a.y.min(b.y), z : a.z.min(b.z) };
+        let max  = BlockPos { x : a.x.max(b.x), y : a.y.max(b.y), z : a.z.max(b.z) };
+        let size = ((max.x as i64) - (min.x as i64) + 1, (max.y as i64) - (min.y as i64) + 1, (max.z as i64) - (min.z as i64) + 1,);
+        let (Ok(width), Ok(height), Ok(length),) = (u16::try_from(size.0), u16::try_from(size.1), u16::try_from(size.2),)
+            else { return Err(SchematicTooLarge { size }); };
+        let mut schematic = Schematic {
+            width,
+            height,
+            length,
+            palette        : Vec::new(),
+            blocks         : Vec::new(),
+            block_entities : Vec::new()
+        };
+        let mut lookup = BTreeMap::<u32, u32>::new();
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    let pos   = BlockPos { x, y, z };
+                    let block = self.get_block_entry(pos).unwrap_or(BLOCK_AIR);
+                    let index = *lookup.entry(block.id()).or_insert_with(|| {
+                        schematic.palette.push(block);
+                        (schematic.palette.len() - 1) as u32
+                    });
+                    schematic.blocks.push(index);
+                    if let Some(block_entity) = self.get_block_entity(pos)
+                        && let Some(id) = block_entity_types.get_key(block_entity.kind)
+                        && let Some(data) = block_entity.data_tag()
+                    { schematic.block_entities.push(([x - min.x, y - min.y, z - min.z], id.clone(), data,)); }
+                }
+            }
+        }
+        Ok(schematic)
+    }
+
+}
+
+
+/// A cuboid too large to copy into a [`Schematic`], which holds at most 65535 blocks along each axis.
+#[derive(Clone, Copy, Debug)]
+pub struct SchematicTooLarge {
+    pub size : (i64, i64, i64,)
+}
+
+impl Display for SchematicTooLarge {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}x{} cuboid is too large for a schematic", self.size.0, self.size.1, self.size.2)
+    }
+}
+
+impl Error for SchematicTooLarge { }
+
+
+fn invalid(msg : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad schematic: {msg}"))
+}
+
+/// Reads the `Width`, `Height`, or `Length` of a schematic.
+fn read_dim(root : &Tag, key : &str) -> io::Result<u16> {
+    match (root.get(key)) {
+        // Dimensions are written as shorts, but are unsigned.
+        Some(Tag::Short(v)) => Ok(*v as u16),
+        Some(tag)           => tag.as_int().and_then(|v| u16::try_from(v).ok()).ok_or_else(|| invalid("dimensions out of range")),
+        None                => Err(invalid("missing dimensions"))
+    }
+}
+
+/// Returns a copy of a compound, without the given keys.
+fn strip_keys(tag : &Tag, keys : &[&str]) -> Tag {
+    let mut compound = tag.as_compound().cloned().unwrap_or_default();
+    for key in keys { compound.remove(*key); }
+    Tag::Compound(compound)
+}
+
+fn read_varints(data : &[i8]) -> io::Result<Vec<u32>> {
+    let mut out   = Vec::new();
+    let mut value = 0u32;
+    let mut shift = 0;
+    for &b in data {
+        let b = b as u8;
+        value |= ((b & 0x7F) as u32) << shift;
+        if (b & 0x80 == 0) {
+            out.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+            if (shift >= 32) { return Err(invalid("varint too long")); }
+        }
+    }
+    Ok(out)
+}
+
+fn write_varint(out : &mut Vec<u8>, mut value : u32) {
+    loop {
+        if (value & !0x7F == 0) {
+            out.push(value as u8);
+            return;
+        }
+        out.push(((value & 0x7F) as u8) | 0x80);
+        value >>= 7;
+    }
+}
+
+/// Parses a block state string such as `minecraft:oak_stairs[facing=north,half=top]`.
+///
+/// Returns `None` for unknown blocks. Unknown properties are ignored.
+fn parse_block_state(s : &str) -> Option<RegEntry<BlockState>> {
+    let (name, properties,) = match (s.split_once('[')) {
+        Some((name, rest,)) => (name, rest.trim_end_matches(']'),),
+        None                => (s, "",)
+    };
+    let mut state = BlockState::default_for(&Identifier::from(name))?;
+    for property in properties.split(',').filter(|p| ! p.is_empty()) {
+        if let Some((key, value,)) = property.split_once('=')
+            && state.properties.contains_key(key)
+        { state.properties.insert(key.to_string(), value.to_string()); }
+    }
+    state_entry(&state)
+}
+
+/// Formats a block state as a string such as `minecraft:oak_stairs[facing=north,half=top]`.
+fn format_block_state(block : RegEntry<BlockState>) -> String {
+    let Some(state) = BlockState::from_id(block.id())
+        else { return "minecraft:air".to_string(); };
+    let mut out = state.id.to_string();
+    if (! state.properties.is_empty()) {
+        out.push('[');
+        out.push_str(&state.properties.iter().map(|(key, value,)| format!("{key}={value}")).collect::<Vec<_>>().join(","));
+        out.push(']');
+    }
+    out
+}
+
+/// Maps common legacy (pre-1.13) block ids to modern block names.
+fn legacy_block_name(id : u16, data : u8) -> Option<String> {
+    const COLOURS : [&str; 16] = [
+        "white", "orange", "magenta", "light_blue", "yellow", "lime", "pink", "gray",
+        "light_gray", "cyan", "purple", "blue", "brown", "green", "red", "black"
+    ];
+    let colour = COLOURS[(data & 0x0F) as usize];
+    Some(match (id) {
+        0   => "minecraft:air",
+        1   => match (data) {
+            1 => "minecraft:granite", 2 => "minecraft:polished_granite",
+            3 => "minecraft:diorite", 4 => "minecraft:polished_diorite",
+            5 => "minecraft:andesite", 6 => "minecraft:polished_andesite",
+            _ => "minecraft:stone"
+        },
+        2   => "minecraft:grass_block",
+        3   => "minecraft:dirt",
+        4   => "minecraft:cobblestone",
+        5   => match (data) {
+            1 => "minecraft:spruce_planks", 2 => "minecraft:birch_planks", 3 => "minecraft:jungle_planks",
+            4 => "minecraft:acacia_planks", 5 => "minecraft:dark_oak_planks",
+            _ => "minecraft:oak_planks"
+        },
+        7   => "minecraft:bedrock",
+        8 | 9   => "minecraft:water",
+        10 | 11 => "minecraft:lava",
+        12  => if (data == 1) { "minecraft:red_sand" } else { "minecraft:sand" },
+        13  => "minecraft:gravel",
+        14  => "minecraft:gold_ore",
+        15  => "minecraft:iron_ore",
+        16  => "minecraft:coal_ore",
+        17  => match (data & 0b11) {
+            1 => "minecraft:spruce_log", 2 => "minecraft:birch_log", 3 => "minecraft:jungle_log",
+            _ => "minecraft:oak_log"
+        },
+        18  => match (data & 0b11) {
+            1 => "minecraft:spruce_leaves", 2 => "minecraft:birch_leaves", 3 => "minecraft:jungle_leaves",
+            _ => "minecraft:oak_leaves"
+        },
+        20  => "minecraft:glass",
+        24  => "minecraft:sandstone",
+        35  => { return Some(format!("minecraft:{colour}_wool")); },
+        41  => "minecraft:gold_block",
+        42  => "minecraft:iron_block",
+        45  => "minecraft:bricks",
+        47  => "minecraft:bookshelf",
+        48  => "minecraft:mossy_cobblestone",
+        49  => "minecraft:obsidian",
+        50  => "minecraft:torch",
+        54  => "minecraft:chest",
+        56  => "minecraft:diamond_ore",
+        57  => "minecraft:diamond_block",
+        58  => "minecraft:crafting_table",
+        73  => "minecraft:redstone_ore",
+        79  => "minecraft:ice",
+        80  => "minecraft:snow_block",
+        82  => "minecraft:clay",
+        85  => "minecraft:oak_fence",
+        86  => "minecraft:carved_pumpkin",
+        87  => "minecraft:netherrack",
+        88  => "minecraft:soul_sand",
+        89  => "minecraft:glowstone",
+        91  => "minecraft:jack_o_lantern",
+        95  => { return Some(format!("minecraft:{colour}_stained_glass")); },
+        98  => match (data) {
+            1 => "minecraft:mossy_stone_bricks", 2 => "minecraft:cracked_stone_bricks", 3 => "minecraft:chiseled_stone_bricks",
+            _ => "minecraft:stone_bricks"
+        },
+        112 => "minecraft:nether_bricks",
+        121 => "minecraft:end_stone",
+        133 => "minecraft:emerald_block",
+        152 => "minecraft:redstone_block",
+        155 => "minecraft:quartz_block",
+        159 => { return Some(format!("minecraft:{colour}_terracotta")); },
+        160 => { return Some(format!("minecraft:{colour}_stained_glass_pane")); },
+        169 => "minecraft:sea_lantern",
+        171 => { return Some(format!("minecraft:{colour}_carpet")); },
+        172 => "minecraft:terracotta",
+        173 => "minecraft:coal_block",
+        174 => "minecraft:packed_ice",
+        251 => { return Some(format!("minecraft:{colour}_concrete")); },
+        252 => { return Some(format!("minecraft:{colour}_concrete_powder")); },
+        _   => { return None; }
+    }.to_string())
+}
@@ -1,40 +1,54 @@
-use crate::world::{ World, in_section_block_linearise };
+use crate::world::{ World, BlockProps, PlaceRejection, in_section_block_delinearise };
 use flywheelmc_common::prelude::*;
-use protocol::value::BlockState;
+use protocol::value::{ BlockState, BlockPos };
 use protocol::registry::RegEntry;
 
 
 pub(super) struct SetBlockBatch<'l> {
     world              : &'l mut World,
-    chunks_to_collapse : BTreeSet<(i32, u8, i32,)>
+    chunks_to_collapse : BTreeSet<(i32, u8, i32,)>,
+    /// Changed blocks in ready chunks which emit or block light differently.
+    blocks_to_relight  : Vec<BlockPos>
 }
 
 impl<'l> SetBlockBatch<'l> {
 
     pub(super) fn new(world : &'l mut World) -> Self { Self {
         world,
-        chunks_to_collapse : BTreeSet::new()
+        chunks_to_collapse : BTreeSet::new(),
+        blocks_to_relight  : Vec::new()
     } }
 
-    pub(super) fn is_chunk_loaded(&self, cpos : Vec2<i32>) -> bool {
-        self.world.chunks.contains_key(&cpos)
-    }
-
 
-    pub(super) fn set(&mut self, (x, y, z) : (i64, u16, i64,), block : RegEntry<BlockState>) {
-        let cpos = Vec2::new(x.div_floor(16) as i32, z.div_floor(16) as i32);
-        let cy   = y.div_floor(16) as u8;
-        let dx = x.rem_euclid(16) as u8;
-        let dy = y.rem_euclid(16) as u8;
-        let dz = z.rem_euclid(16) as u8;
+    pub(super) fn set(&mut self, pos : BlockPos, block : RegEntry<BlockState>) -> Result<(), PlaceRejection> {
+        let (cpos, cy, linear_xyz,) = self.world.locate_block(pos)
+            .ok_or(PlaceRejection::OutOfHeight)?;
         let Some(chunk) = self.world.chunks.get_mut(&cpos)
-            else { return; };
-        let Some(section) = chunk.sections.get_mut(cy as usize)
-            else { return; };
-        if (self.chunks_to_collapse.insert((cpos.x, cy, cpos.y,))) {
-            section.split_out_all();
+            else { return Err(PlaceRejection::ChunkNotLoaded); };
+        let Some(section) = chunk.sections.get_mut(cy)
+            else { return Err(PlaceRejection::OutOfHeight); };
+        self.chunks_to_collapse.insert((cpos.x, cy as u8, cpos.y,));
+        // Kept over whatever the chunk loads as.
+        if (! chunk.ready) {
+            chunk.early_edits.blocks.insert((cy, linear_xyz,), block);
+        }
+        let old = section.set(linear_xyz, block);
+        if (old != block) {
+            let [dx, dy, dz] = in_section_block_delinearise(linear_xyz);
+            let local_y      = (cy * 16) + (dy as usize);
+            // Replacing a block removes its block entity. The client does the same.
+            chunk.block_entities.remove(&(dx, local_y as u16, dz,));
+            chunk.unsaved = true;
+            if (chunk.ready) {
+                chunk.update_heightmaps(dx, local_y, dz, block);
+                self.world.block_changes.push((pos, old, block, self.world.change_cause,));
+                let (old_props, new_props,) = (BlockProps::of(old), BlockProps::of(block),);
+                if (old_props.opacity != new_props.opacity || old_props.emission != new_props.emission) {
+                    self.blocks_to_relight.push(pos);
+                }
+            }
         }
-        section.overwrite_run_state(in_section_block_linearise(dx, dy, dz), block);
+        Ok(())
     }
 
 }
@@ -46,5 +60,8 @@ impl<'l> Drop for SetBlockBatch<'l> {
                 && let Some(section) = chunk.sections.get_mut(cy as usize)
             { section.collapse(); }
         }
+        // Chunks which are not ready yet are lit when they are marked ready.
+        let blocks_to_relight = mem::take(&mut self.blocks_to_relight);
+        self.world.relight_blocks(&blocks_to_relight);
     }
 }